@@ -0,0 +1,223 @@
+use anyhow::Result;
+use std::io::{Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+/// Whether a local path is an archive that can be unpacked and scanned.
+pub fn is_archive(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".zip") || path.ends_with(".tar.gz") || path.ends_with(".tgz")
+}
+
+/// Cache directory for a local archive: `<cache_dir>/unpacked/<file name>-<hash>`,
+/// where the hash of the absolute path keeps equally named archives apart.
+pub fn get_cache_path(archive: &Path, cache_dir: &str) -> PathBuf {
+    let hash = crate::loader::stable_hash(archive.as_os_str().as_encoded_bytes());
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    crate::paths::expand(cache_dir)
+        .join("unpacked")
+        .join(format!("{}-{:016x}", name, hash))
+}
+
+/// Unpack a local `.zip` or `.tar.gz` archive into `dest`, unless `dest` was
+/// unpacked after the archive was last modified.
+pub fn unpack_if_changed(archive: &Path, dest: &Path) -> Result<()> {
+    let modified = std::fs::metadata(archive)?.modified()?;
+    if let Ok(unpacked) = std::fs::metadata(dest).and_then(|m| m.modified()) {
+        if unpacked >= modified {
+            return Ok(());
+        }
+    }
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::File::open(archive)?;
+    if archive.to_string_lossy().to_lowercase().ends_with(".zip") {
+        unpack_zip(file, dest)
+    } else {
+        unpack_tar_gz(file, dest, false)
+    }
+}
+
+/// Unpack a `.tar.gz` stream into `dest`, replacing its previous contents.
+/// With `strip_root`, the single top-level directory that forge archives
+/// wrap everything in is removed.
+pub fn unpack_tar_gz(reader: impl Read, dest: &Path, strip_root: bool) -> Result<()> {
+    let staging = staging_path(dest);
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(&staging)?;
+
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(reader));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let Some(relative) = sanitize(&path, strip_root) else {
+            continue;
+        };
+        let target = staging.join(relative);
+        if entry.header().entry_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if entry.header().entry_type().is_file() {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            entry.unpack(&target)?;
+        }
+    }
+
+    replace_dir(&staging, dest)
+}
+
+/// Unpack a `.zip` archive into `dest`, replacing its previous contents.
+pub fn unpack_zip(reader: impl Read + Seek, dest: &Path) -> Result<()> {
+    let staging = staging_path(dest);
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(&staging)?;
+
+    let mut archive = zip::ZipArchive::new(reader)?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let Some(relative) = entry.enclosed_name().and_then(|p| sanitize(&p, false)) else {
+            continue;
+        };
+        let target = staging.join(relative);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if entry.is_file() {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::io::copy(&mut entry, &mut std::fs::File::create(&target)?)?;
+        }
+    }
+
+    replace_dir(&staging, dest)
+}
+
+/// Keep only plain relative components so entries can't escape the destination.
+fn sanitize(path: &Path, strip_root: bool) -> Option<PathBuf> {
+    let mut components = path.components().filter(|c| *c != Component::CurDir);
+    if strip_root {
+        components.next();
+    }
+    let mut relative = PathBuf::new();
+    for component in components {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            _ => return None,
+        }
+    }
+    (!relative.as_os_str().is_empty()).then_some(relative)
+}
+
+fn staging_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    dest.with_file_name(name)
+}
+
+fn replace_dir(staging: &Path, dest: &Path) -> Result<()> {
+    if dest.exists() {
+        std::fs::remove_dir_all(dest)?;
+    }
+    std::fs::rename(staging, dest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_sanitize_strips_root() {
+        assert_eq!(
+            sanitize(Path::new("repo-main/prompts/a.md"), true),
+            Some(PathBuf::from("prompts/a.md"))
+        );
+        assert_eq!(sanitize(Path::new("repo-main/"), true), None);
+        assert_eq!(
+            sanitize(Path::new("./a.md"), false),
+            Some(PathBuf::from("a.md"))
+        );
+    }
+
+    #[test]
+    fn test_sanitize_rejects_escapes() {
+        assert_eq!(sanitize(Path::new("repo/../../etc/passwd"), true), None);
+        assert_eq!(sanitize(Path::new("/etc/passwd"), false), None);
+    }
+
+    #[test]
+    fn test_unpack_tar_gz() {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let content = b"Hello";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "repo-main/prompts/a.md", &content[..])
+            .unwrap();
+        let bytes = builder.into_inner().unwrap().finish().unwrap();
+
+        let dir = TempDir::new("unpack");
+        let dest = dir.join("unpacked");
+        unpack_tar_gz(&bytes[..], &dest, true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dest.join("prompts/a.md")).unwrap(),
+            "Hello"
+        );
+    }
+
+    #[test]
+    fn test_unpack_zip() {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer
+            .start_file("prompts/a.md", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut writer, b"Hello").unwrap();
+        writer
+            .start_file("../escape.md", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let dir = TempDir::new("unzip");
+        let dest = dir.join("unpacked");
+        unpack_zip(std::io::Cursor::new(bytes), &dest).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dest.join("prompts/a.md")).unwrap(),
+            "Hello"
+        );
+        assert!(!dest.join("escape.md").exists());
+        assert!(!dir.join("escape.md").exists());
+    }
+
+    #[test]
+    fn test_get_cache_path_is_stable() {
+        // Pinned, since a changed hash would orphan existing caches
+        assert_eq!(
+            get_cache_path(Path::new("/srv/prompts.zip"), "/cache"),
+            PathBuf::from("/cache/unpacked/prompts.zip-5a0e7e60b79c3b76")
+        );
+        assert_ne!(
+            get_cache_path(Path::new("/srv/prompts.zip"), "/cache"),
+            get_cache_path(Path::new("/opt/prompts.zip"), "/cache")
+        );
+    }
+
+    #[test]
+    fn test_is_archive() {
+        assert!(is_archive("prompts.zip"));
+        assert!(is_archive("prompts.tar.gz"));
+        assert!(is_archive("Prompts.TGZ"));
+        assert!(!is_archive("prompts"));
+        assert!(!is_archive("prompts.skpack"));
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A JSONL file with a line per `prompts/get` request, opened for appending
+/// so restarts keep earlier entries.
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+/// One `prompts/get` request. Argument values are left out since they may
+/// hold whatever the user typed.
+#[derive(Serialize)]
+pub struct Entry<'a> {
+    pub timestamp: String,
+    pub prompt: Option<&'a str>,
+    pub arguments: Vec<&'a str>,
+    /// The `clientInfo` sent with `initialize`, like `{"name": "...", "version": "..."}`.
+    pub client: Option<Value>,
+    pub duration_ms: f64,
+    pub outcome: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a str>,
+}
+
+impl<'a> Entry<'a> {
+    /// An entry timestamped now. `error` is the message sent to the client
+    /// when the request failed.
+    pub fn new(
+        prompt: Option<&'a str>,
+        arguments: Option<&'a Value>,
+        client: Option<Value>,
+        duration: Duration,
+        error: Option<&'a str>,
+    ) -> Self {
+        let mut arguments: Vec<&str> = arguments
+            .and_then(Value::as_object)
+            .map(|a| a.keys().map(String::as_str).collect())
+            .unwrap_or_default();
+        arguments.sort_unstable();
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            prompt,
+            arguments,
+            client,
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            outcome: if error.is_some() { "error" } else { "ok" },
+            error,
+        }
+    }
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open audit log {}: {}", path.display(), e))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Append `entry` as one line. Failures are logged rather than failing
+    /// the request.
+    pub fn record(&self, entry: &Entry) {
+        let result = serde_json::to_string(entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| Ok(writeln!(self.file.lock().unwrap(), "{}", line)?));
+        if let Err(e) = result {
+            tracing::warn!(error = %e, "Failed to write audit log");
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::prompt::estimate_tokens;
+use anyhow::Result;
+
+/// What to do with a rendered prompt over its budget.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BudgetPolicy {
+    /// Fail the request.
+    #[default]
+    Reject,
+    /// Warn and return the content anyway.
+    Warn,
+}
+
+pub fn get_budget_policy(policy: &str) -> Result<BudgetPolicy> {
+    match policy {
+        "reject" => Ok(BudgetPolicy::Reject),
+        "warn" => Ok(BudgetPolicy::Warn),
+        _ => anyhow::bail!("Unknown over-budget policy: {}", policy),
+    }
+}
+
+/// Limits on the size of a rendered prompt, so a huge argument, included file
+/// or post-render command output doesn't end up in the client's context.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderBudget {
+    pub max_bytes: Option<u64>,
+    /// Compared with [`estimate_tokens`] of the rendered text.
+    pub max_tokens: Option<usize>,
+    pub on_exceed: BudgetPolicy,
+}
+
+impl RenderBudget {
+    /// Check the rendered `text` of a prompt, failing or warning if it's over
+    /// budget.
+    pub fn check(&self, prompt_name: &str, text: &str) -> Result<(), String> {
+        let Some(excess) = self.excess(text) else {
+            return Ok(());
+        };
+        match self.on_exceed {
+            BudgetPolicy::Reject => Err(format!("Rendered prompt '{}' is {}", prompt_name, excess)),
+            BudgetPolicy::Warn => {
+                tracing::warn!(prompt = prompt_name, "Rendered prompt is {}", excess);
+                Ok(())
+            }
+        }
+    }
+
+    fn excess(&self, text: &str) -> Option<String> {
+        let bytes = text.len() as u64;
+        if let Some(max) = self.max_bytes.filter(|max| bytes > *max) {
+            return Some(format!("{} bytes, over the budget of {}", bytes, max));
+        }
+        let max = self.max_tokens?;
+        let tokens = estimate_tokens(text);
+        (tokens > max).then(|| format!("about {} tokens, over the budget of {}", tokens, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let text = "x".repeat(100);
+        assert!(RenderBudget::default().check("review", &text).is_ok());
+
+        let budget = RenderBudget {
+            max_bytes: Some(64),
+            ..Default::default()
+        };
+        assert_eq!(
+            budget.check("review", &text).unwrap_err(),
+            "Rendered prompt 'review' is 100 bytes, over the budget of 64"
+        );
+        assert!(budget.check("review", &text[..64]).is_ok());
+
+        let budget = RenderBudget {
+            max_tokens: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(
+            budget.check("review", &text).unwrap_err(),
+            "Rendered prompt 'review' is about 25 tokens, over the budget of 20"
+        );
+
+        let budget = RenderBudget {
+            on_exceed: BudgetPolicy::Warn,
+            ..budget
+        };
+        assert!(budget.check("review", &text).is_ok());
+    }
+
+    #[test]
+    fn test_get_budget_policy() {
+        assert_eq!(get_budget_policy("warn").unwrap(), BudgetPolicy::Warn);
+        assert!(get_budget_policy("truncate").is_err());
+    }
+}
@@ -0,0 +1,373 @@
+use crate::paths;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Last-used timestamps of cache entries, relative to the cache root.
+const METADATA_FILE: &str = ".shinkuro-cache.json";
+
+/// Sources are resolved concurrently, so metadata updates are serialized.
+static METADATA_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Default, Serialize, Deserialize)]
+struct CacheMetadata {
+    /// Seconds since the Unix epoch, keyed by entry path with `/` separators.
+    #[serde(default)]
+    last_used: HashMap<String, u64>,
+}
+
+/// The cache folder with `~` expanded.
+pub fn root(cache_dir: &str) -> PathBuf {
+    paths::expand(cache_dir)
+}
+
+/// Number of path components identifying one entry below each top-level cache
+/// folder, e.g. `git/<owner>/<name>` or `unpacked/<name>`.
+fn entry_depth(kind: &str) -> Option<usize> {
+    match kind {
+        "git" | "archive" | "http" | "s3" => Some(3),
+        "unpacked" | "index" => Some(2),
+        _ => None,
+    }
+}
+
+/// The cache entry containing `path`, relative to the cache root.
+fn entry_of(root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(root).ok()?;
+    let kind = relative.components().next()?.as_os_str().to_str()?;
+    let depth = entry_depth(kind)?;
+    let entry: PathBuf = relative.components().take(depth).collect();
+    (entry.components().count() == depth).then_some(entry)
+}
+
+fn key(entry: &Path) -> String {
+    entry.to_string_lossy().replace('\\', "/")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn load_metadata(root: &Path) -> CacheMetadata {
+    std::fs::read(root.join(METADATA_FILE))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn save_metadata(root: &Path, metadata: &CacheMetadata) -> Result<()> {
+    std::fs::create_dir_all(root)?;
+    std::fs::write(
+        root.join(METADATA_FILE),
+        serde_json::to_vec_pretty(metadata)?,
+    )?;
+    Ok(())
+}
+
+/// Record that the cache entry containing `path` was just used. Paths outside
+/// the cache, like local folders, are ignored.
+pub fn touch(cache_dir: &str, path: &Path) {
+    let root = root(cache_dir);
+    let Some(entry) = entry_of(&root, path) else {
+        return;
+    };
+    let _guard = METADATA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut metadata = load_metadata(&root);
+    metadata.last_used.insert(key(&entry), now());
+    if let Err(e) = save_metadata(&root, &metadata) {
+        tracing::warn!(error = %e, "Failed to update cache metadata");
+    }
+}
+
+/// A cached source, like a git checkout or a fetched HTTP manifest.
+pub struct Entry {
+    /// Relative to the cache root, e.g. `git/github.com/prompts-0123abcd`.
+    pub path: PathBuf,
+    /// Seconds since the Unix epoch.
+    pub last_used: u64,
+    /// When a file in the entry last changed, i.e. it was last fetched with
+    /// changes, in seconds since the Unix epoch.
+    pub updated: u64,
+    pub size: u64,
+}
+
+/// All entries in the cache with their last use (falling back to the
+/// modification time for entries recorded before metadata existed) and size.
+fn entries(root: &Path, metadata: &CacheMetadata) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let Ok(kinds) = std::fs::read_dir(root) else {
+        return entries;
+    };
+    for kind in kinds.flatten() {
+        let Some(depth) = kind.file_name().to_str().and_then(entry_depth) else {
+            continue;
+        };
+        for item in walkdir::WalkDir::new(kind.path())
+            .min_depth(depth - 1)
+            .max_depth(depth - 1)
+            .into_iter()
+            .flatten()
+        {
+            let Ok(relative) = item.path().strip_prefix(root) else {
+                continue;
+            };
+            // Staging folders of interrupted downloads are cleaned up on the next fetch
+            if relative.to_string_lossy().ends_with(".tmp") {
+                continue;
+            }
+            let modified = item
+                .metadata()
+                .ok()
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs());
+            let (size, updated) = size_of(item.path());
+            entries.push(Entry {
+                last_used: metadata
+                    .last_used
+                    .get(&key(relative))
+                    .copied()
+                    .unwrap_or(modified),
+                updated: updated.max(modified),
+                size,
+                path: relative.to_path_buf(),
+            });
+        }
+    }
+    entries
+}
+
+/// The total size of the files below `path` and the latest time one changed.
+fn size_of(path: &Path) -> (u64, u64) {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .fold((0, 0), |(size, updated), m| {
+            let modified = m
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs());
+            (size + m.len(), updated.max(modified))
+        })
+}
+
+/// All entries in the cache, sorted by path.
+pub fn list(cache_dir: &str) -> Vec<Entry> {
+    let root = root(cache_dir);
+    let _guard = METADATA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = entries(&root, &load_metadata(&root));
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+/// Remove the entry containing `path`, or every entry if `path` is `None`,
+/// returning how many were removed.
+pub fn clear(cache_dir: &str, path: Option<&Path>) -> Result<usize> {
+    let root = root(cache_dir);
+    let _guard = METADATA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut metadata = load_metadata(&root);
+    let entries: Vec<PathBuf> = match path {
+        Some(path) => {
+            let entry = entry_of(&root, &root.join(path))
+                .ok_or_else(|| anyhow::anyhow!("{} is not a cache entry", path.display()))?;
+            if !root.join(&entry).exists() {
+                anyhow::bail!("{} is not cached", entry.display());
+            }
+            vec![entry]
+        }
+        None => entries(&root, &metadata)
+            .into_iter()
+            .map(|e| e.path)
+            .collect(),
+    };
+    for entry in &entries {
+        let path = root.join(entry);
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)?;
+        } else {
+            std::fs::remove_file(&path)?;
+        }
+        metadata.last_used.remove(&key(entry));
+    }
+    if !entries.is_empty() {
+        save_metadata(&root, &metadata)?;
+    }
+    Ok(entries.len())
+}
+
+/// The entries as a table of path, size, last use and last update.
+pub fn table(entries: &[Entry]) -> String {
+    let mut rows = vec![[
+        "ENTRY".to_string(),
+        "SIZE".to_string(),
+        "LAST USED".to_string(),
+        "UPDATED".to_string(),
+    ]];
+    for entry in entries {
+        rows.push([
+            key(&entry.path),
+            format_size(entry.size),
+            format_time(entry.last_used),
+            format_time(entry.updated),
+        ]);
+    }
+    crate::list::format_table(&rows)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn format_time(secs: u64) -> String {
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .map(|time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// Remove entries unused for longer than `max_age`, then the least recently
+/// used ones until the cache is no larger than `max_size` bytes.
+pub fn prune(cache_dir: &str, max_age: Option<Duration>, max_size: Option<u64>) -> Result<()> {
+    let root = root(cache_dir);
+    let _guard = METADATA_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut metadata = load_metadata(&root);
+    let mut entries = entries(&root, &metadata);
+    entries.sort_by_key(|e| e.last_used);
+
+    let now = now();
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+    let mut removed = 0;
+    for entry in &entries {
+        let expired =
+            max_age.is_some_and(|age| now.saturating_sub(entry.last_used) > age.as_secs());
+        let oversized = max_size.is_some_and(|size| total > size);
+        if !expired && !oversized {
+            continue;
+        }
+        let path = root.join(&entry.path);
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => {
+                total -= entry.size;
+                removed += 1;
+                metadata.last_used.remove(&key(&entry.path));
+            }
+            Err(e) => {
+                tracing::warn!(file = %path.display(), error = %e, "Failed to remove cache entry")
+            }
+        }
+    }
+
+    if removed > 0 {
+        tracing::info!("Pruned {} cache entries", removed);
+        save_metadata(&root, &metadata)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_entry_of() {
+        let root = Path::new("/cache");
+        assert_eq!(
+            entry_of(root, Path::new("/cache/git/owner/repo/prompts")),
+            Some(PathBuf::from("git/owner/repo"))
+        );
+        assert_eq!(
+            entry_of(root, Path::new("/cache/unpacked/prompts.zip-0123")),
+            Some(PathBuf::from("unpacked/prompts.zip-0123"))
+        );
+        assert_eq!(entry_of(root, Path::new("/cache/git/owner")), None);
+        assert_eq!(entry_of(root, Path::new("/home/user/prompts")), None);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(4 * 1024 * 1024), "4.0 MB");
+    }
+
+    #[test]
+    fn test_list_and_clear() {
+        let dir = TempDir::new("cache-clear");
+        let cache_dir = dir.to_str().unwrap();
+        for entry in ["git/host/a-01", "git/host/b-02", "s3/bucket/prefix"] {
+            std::fs::create_dir_all(dir.join(entry)).unwrap();
+            std::fs::write(dir.join(entry).join("a.md"), "hello").unwrap();
+        }
+        let entries = list(cache_dir);
+        let paths: Vec<_> = entries.iter().map(|e| key(&e.path)).collect();
+        assert_eq!(
+            paths,
+            ["git/host/a-01", "git/host/b-02", "s3/bucket/prefix"]
+        );
+        assert_eq!(entries[0].size, 5);
+        assert!(table(&entries).starts_with("ENTRY             SIZE  LAST USED"));
+
+        assert_eq!(
+            clear(cache_dir, Some(Path::new("git/host/a-01/docs"))).unwrap(),
+            1
+        );
+        assert!(!dir.join("git/host/a-01").exists());
+        assert!(clear(cache_dir, Some(Path::new("git/host/a-01"))).is_err());
+        assert!(clear(cache_dir, Some(Path::new("git"))).is_err());
+        assert_eq!(clear(cache_dir, None).unwrap(), 2);
+        assert!(list(cache_dir).is_empty());
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = TempDir::new("cache-prune");
+        let cache_dir = dir.to_str().unwrap();
+        for (entry, size) in [("git/a/old", 10), ("git/b/new", 20), ("http/host/file", 30)] {
+            std::fs::create_dir_all(dir.join(entry)).unwrap();
+            std::fs::write(dir.join(entry).join("a.md"), "x".repeat(size)).unwrap();
+        }
+        let mut metadata = CacheMetadata::default();
+        metadata.last_used.insert("git/a/old".to_string(), 0);
+        metadata
+            .last_used
+            .insert("git/b/new".to_string(), now() - 10);
+        save_metadata(&dir, &metadata).unwrap();
+        touch(cache_dir, &dir.join("http/host/file"));
+
+        prune(cache_dir, Some(Duration::from_secs(3600)), None).unwrap();
+        assert!(!dir.join("git/a/old").exists());
+        assert!(dir.join("git/b/new").exists());
+
+        prune(cache_dir, None, Some(40)).unwrap();
+        assert!(!dir.join("git/b/new").exists());
+        assert!(dir.join("http/host/file").exists());
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Config files looked for in the current directory.
+const LOCAL_FILES: [&str; 3] = ["shinkuro.toml", "shinkuro.yaml", "shinkuro.yml"];
+
+/// Config files looked for in `~/.config/shinkuro/`.
+const USER_FILES: [&str; 3] = ["config.toml", "config.yaml", "config.yml"];
+
+/// The first config file found in `cwd`, then in `~/.config/shinkuro/`.
+pub fn discover(cwd: &Path) -> Option<PathBuf> {
+    let user = crate::paths::expand("~/.config/shinkuro");
+    LOCAL_FILES
+        .iter()
+        .map(|name| cwd.join(name))
+        .chain(USER_FILES.iter().map(|name| user.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Read a TOML or YAML config file, chosen by extension, into option names
+/// and values.
+pub fn load(path: &Path) -> Result<serde_json::Map<String, Value>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read config {}: {}", path.display(), e))?;
+    let parsed = match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml" | "yml") => serde_yaml::from_str::<Option<Value>>(&text)
+            .map(Option::unwrap_or_default)
+            .map_err(anyhow::Error::from),
+        _ => toml::from_str::<Value>(&text).map_err(anyhow::Error::from),
+    };
+    match parsed.map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e))? {
+        Value::Object(options) => Ok(options),
+        Value::Null => Ok(serde_json::Map::new()),
+        _ => anyhow::bail!("Config {} must be a table of options", path.display()),
+    }
+}
+
+/// The top-level options of `config` with those of `[profile.<name>]`
+/// applied on top. A `token_env` option names the environment variable the
+/// git token is read from.
+pub fn select_profile(
+    mut config: serde_json::Map<String, Value>,
+    profile: Option<&String>,
+) -> Result<serde_json::Map<String, Value>> {
+    let mut profiles = match config.remove("profile") {
+        Some(Value::Object(profiles)) => profiles,
+        Some(_) => anyhow::bail!("profile must be a table of profiles"),
+        None => serde_json::Map::new(),
+    };
+    if let Some(name) = profile {
+        match profiles.remove(name) {
+            Some(Value::Object(options)) => config.extend(options),
+            Some(_) => anyhow::bail!("Profile {} must be a table of options", name),
+            None => anyhow::bail!("Unknown profile: {}", name),
+        }
+    }
+    if let Some(var) = config.remove("token_env") {
+        let var = var
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("token_env must be an environment variable name"))?;
+        let token = std::env::var(var)
+            .map_err(|_| anyhow::anyhow!("token_env names {}, which is not set", var))?;
+        config.insert("git_token".to_string(), Value::String(token));
+    }
+    Ok(config)
+}
+
+/// Command-line arguments for the options in `config` that `matches` didn't
+/// get from the command line or the environment, so flags override
+/// environment variables, which override the config file. Option names are
+/// the long flags, with `-` or `_`.
+pub fn to_args(
+    config: &serde_json::Map<String, Value>,
+    command: &clap::Command,
+    matches: &ArgMatches,
+) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    for (key, value) in config {
+        let id = key.replace('-', "_");
+        let arg = command
+            .get_arguments()
+            // The config file can't name another one, and profiles are tables
+            .find(|arg| {
+                arg.get_id() == id.as_str()
+                    && arg.is_global_set()
+                    && !matches!(id.as_str(), "config" | "profile")
+            })
+            .ok_or_else(|| anyhow::anyhow!("Unknown option in config: {}", key))?;
+        if matches!(
+            matches.value_source(&id),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+        let flag = format!("--{}", arg.get_long().unwrap_or(&id));
+        let values = match value {
+            Value::Array(values) => values.clone(),
+            value => vec![value.clone()],
+        };
+        for value in values {
+            let value = match value {
+                Value::String(s) => s,
+                Value::Bool(b) if !arg.get_action().takes_values() => {
+                    if b {
+                        args.push(flag.clone());
+                    }
+                    continue;
+                }
+                Value::Bool(b) => b.to_string(),
+                Value::Number(n) => n.to_string(),
+                _ => anyhow::bail!("Invalid value for {} in config: {}", key, value),
+            };
+            args.push(flag.clone());
+            args.push(value);
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use clap::{CommandFactory, FromArgMatches, Parser};
+
+    #[derive(Parser, Debug)]
+    struct Args {
+        #[arg(long, global = true)]
+        config: Option<PathBuf>,
+        #[arg(
+            long,
+            global = true,
+            env = "SHINKURO_TEST_FORMAT",
+            default_value = "brace"
+        )]
+        variable_format: String,
+        #[arg(long, global = true)]
+        auto_pull: bool,
+        #[arg(long, global = true, default_value_t = true, action = clap::ArgAction::Set)]
+        single_branch: bool,
+        #[arg(long = "extra-source", global = true, value_delimiter = ',')]
+        extra_sources: Vec<String>,
+        #[arg(long, global = true)]
+        max_depth: Option<usize>,
+    }
+
+    fn parse(argv: &[&str], config: &str) -> Result<Args> {
+        let options = toml::from_str::<Value>(config)?
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        let matches = Args::command().try_get_matches_from(argv)?;
+        let extra = to_args(&options, &Args::command(), &matches)?;
+        let argv: Vec<String> = argv[..1]
+            .iter()
+            .map(|s| s.to_string())
+            .chain(extra)
+            .chain(argv[1..].iter().map(|s| s.to_string()))
+            .collect();
+        Ok(Args::from_arg_matches(
+            &Args::command().try_get_matches_from(argv)?,
+        )?)
+    }
+
+    #[test]
+    fn test_to_args() {
+        let config = r#"
+            variable-format = "jinja"
+            auto_pull = true
+            single_branch = false
+            extra_sources = ["./a", "git@github.com:o/r.git"]
+            max_depth = 2
+        "#;
+        let args = parse(&["shinkuro"], config).unwrap();
+        assert_eq!(args.variable_format, "jinja");
+        assert!(args.auto_pull);
+        assert!(!args.single_branch);
+        assert_eq!(args.extra_sources, ["./a", "git@github.com:o/r.git"]);
+        assert_eq!(args.max_depth, Some(2));
+
+        // Flags win over the config file
+        let args = parse(&["shinkuro", "--variable-format", "dollar"], config).unwrap();
+        assert_eq!(args.variable_format, "dollar");
+
+        assert!(parse(&["shinkuro"], "unknown = 1").is_err());
+        assert!(parse(&["shinkuro"], "config = \"other.toml\"").is_err());
+        assert!(parse(&["shinkuro"], "max_depth = { a = 1 }").is_err());
+    }
+
+    #[test]
+    fn test_select_profile() {
+        let config = toml::from_str::<Value>(
+            r#"
+            variable_format = "jinja"
+            filter_tag = ["shared"]
+
+            [profile.work]
+            git_url = "https://github.com/work/prompts.git"
+            filter_tag = ["work"]
+            token_env = "SHINKURO_TEST_WORK_TOKEN"
+
+            [profile.personal]
+            folder = "~/prompts"
+            "#,
+        )
+        .unwrap()
+        .as_object()
+        .cloned()
+        .unwrap();
+
+        let options = select_profile(config.clone(), None).unwrap();
+        assert!(!options.contains_key("profile"));
+        assert_eq!(options["filter_tag"], serde_json::json!(["shared"]));
+
+        std::env::set_var("SHINKURO_TEST_WORK_TOKEN", "secret");
+        let options = select_profile(config.clone(), Some(&"work".to_string())).unwrap();
+        assert_eq!(options["variable_format"], "jinja");
+        assert_eq!(options["filter_tag"], serde_json::json!(["work"]));
+        assert_eq!(options["git_token"], "secret");
+        assert!(!options.contains_key("token_env"));
+
+        let options = select_profile(config.clone(), Some(&"personal".to_string())).unwrap();
+        assert_eq!(options["folder"], "~/prompts");
+        assert!(!options.contains_key("git_url"));
+
+        assert!(select_profile(config, Some(&"other".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = TempDir::new("config");
+        let yaml = dir.join("shinkuro.yaml");
+        std::fs::write(&yaml, "folder: ./prompts\nfilter_tag: [rust]\n").unwrap();
+        let options = load(&yaml).unwrap();
+        assert_eq!(options["folder"], "./prompts");
+        assert_eq!(options["filter_tag"], serde_json::json!(["rust"]));
+        assert_eq!(discover(&dir), Some(yaml.clone()));
+        std::fs::write(&yaml, "- not a table\n").unwrap();
+        assert!(load(&yaml).is_err());
+    }
+}
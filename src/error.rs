@@ -0,0 +1,40 @@
+/// Errors from loading, rendering and serving prompts.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A source couldn't be fetched or read.
+    #[error("Failed to load {name}: {error}")]
+    Source {
+        name: String,
+        #[source]
+        error: anyhow::Error,
+    },
+    /// A prompt's metadata or template is invalid.
+    #[error("{0}")]
+    Parse(String),
+    /// Argument values are missing or don't pass validation.
+    #[error("{0}")]
+    Argument(String),
+    /// The template or post-render command failed.
+    #[error("{0}")]
+    Render(String),
+    #[error("Prompt not found")]
+    PromptNotFound(String),
+    /// A request is missing parameters or has invalid ones.
+    #[error("{0}")]
+    InvalidParams(String),
+    #[error("Method not found")]
+    MethodNotFound(String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// The JSON-RPC error code reported to MCP clients.
+    pub fn code(&self) -> i32 {
+        match self {
+            Error::MethodNotFound(_) => -32601,
+            Error::InvalidParams(_) | Error::PromptNotFound(_) | Error::Argument(_) => -32602,
+            Error::Source { .. } | Error::Parse(_) | Error::Render(_) => -32603,
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::init::yaml;
+use crate::naming;
+use crate::prompt::MarkdownPrompt;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Formats prompts can be exported to with `shinkuro export`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// Claude Code slash commands, one markdown file per command.
+    ClaudeCommands,
+    /// Cursor rules, one `.mdc` file per rule.
+    Cursor,
+    /// A single JSONL file with a chat message template per line.
+    OpenaiJsonl,
+}
+
+impl ExportFormat {
+    /// Where the tool looks for the files, relative to the project.
+    pub fn default_out(self) -> &'static str {
+        match self {
+            ExportFormat::ClaudeCommands => ".claude/commands",
+            ExportFormat::Cursor => ".cursor/rules",
+            ExportFormat::OpenaiJsonl => "prompts.jsonl",
+        }
+    }
+}
+
+pub fn get_export_format(format: &str) -> Result<ExportFormat> {
+    match format {
+        "claude-commands" => Ok(ExportFormat::ClaudeCommands),
+        "cursor" => Ok(ExportFormat::Cursor),
+        "openai-jsonl" => Ok(ExportFormat::OpenaiJsonl),
+        _ => anyhow::bail!("Unknown export format: {}", format),
+    }
+}
+
+/// Write each prompt into `out` in `format`, replacing existing files, and
+/// return the files written. `out` is the file itself for single-file formats.
+pub fn export(
+    prompts: &[MarkdownPrompt],
+    format: ExportFormat,
+    out: &Path,
+) -> Result<Vec<PathBuf>> {
+    if format == ExportFormat::OpenaiJsonl {
+        let mut lines = String::new();
+        for prompt in prompts {
+            lines.push_str(&serde_json::to_string(&openai_json(prompt)?)?);
+            lines.push('\n');
+        }
+        if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(out, lines)?;
+        return Ok(vec![out.to_path_buf()]);
+    }
+    std::fs::create_dir_all(out)?;
+    let mut written = Vec::new();
+    for prompt in prompts {
+        let name = naming::suggest_prompt_name(&prompt.name);
+        let (file, text) = match format {
+            ExportFormat::ClaudeCommands => (format!("{}.md", name), claude_command(prompt)?),
+            ExportFormat::Cursor => (format!("{}.mdc", name), cursor_rule(prompt)?),
+            ExportFormat::OpenaiJsonl => unreachable!("written as a single file"),
+        };
+        let path = out.join(file);
+        std::fs::write(&path, text)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// A slash command file. A single argument becomes `$ARGUMENTS`, the whole
+/// text after the command, and several become positional `$1`, `$2`, ... in
+/// the order they are declared, listed in `argument-hint` with optional ones
+/// in brackets. Built-in variables are kept as written.
+fn claude_command(prompt: &MarkdownPrompt) -> Result<String> {
+    let variables: HashMap<String, String> = match prompt.arguments.as_slice() {
+        [argument] => HashMap::from([(argument.name.clone(), "$ARGUMENTS".to_string())]),
+        arguments => arguments
+            .iter()
+            .enumerate()
+            .map(|(i, argument)| (argument.name.clone(), format!("${}", i + 1)))
+            .collect(),
+    };
+    let mut text = format!("---\ndescription: {}\n", yaml(&prompt.description));
+    if !prompt.arguments.is_empty() {
+        let hint: Vec<_> = prompt
+            .arguments
+            .iter()
+            .map(|argument| {
+                if argument.required {
+                    format!("<{}>", argument.name)
+                } else {
+                    format!("[{}]", argument.name)
+                }
+            })
+            .collect();
+        text.push_str(&format!("argument-hint: {}\n", yaml(&hint.join(" "))));
+    }
+    text.push_str("---\n\n");
+    text.push_str(prompt.substitute(&variables)?.trim_start());
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// A Cursor rule that the agent applies when its description is relevant.
+/// Rules take no arguments, so defaults are filled in and other placeholders
+/// are kept as written.
+fn cursor_rule(prompt: &MarkdownPrompt) -> Result<String> {
+    let mut text = format!(
+        "---\ndescription: {}\nglobs:\nalwaysApply: false\n---\n\n",
+        yaml(&prompt.description)
+    );
+    text.push_str(prompt.substitute(&prompt.arg_defaults)?.trim_start());
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// A prompt as `{name, description, messages, variables}`, with arguments
+/// rewritten to `{{name}}` and listed in `variables`, for platforms that
+/// ingest JSON chat templates.
+fn openai_json(prompt: &MarkdownPrompt) -> Result<Value> {
+    let variables: HashMap<String, String> = prompt
+        .arguments
+        .iter()
+        .map(|argument| (argument.name.clone(), format!("{{{{{}}}}}", argument.name)))
+        .collect();
+    Ok(json!({
+        "name": prompt.name,
+        "description": prompt.description,
+        "messages": [{ "role": "user", "content": prompt.substitute(&variables)? }],
+        "variables": prompt.arguments.iter().map(|argument| json!({
+            "name": argument.name,
+            "description": argument.description,
+            "required": argument.required,
+            "default": prompt.arg_defaults.get(&argument.name),
+        })).collect::<Vec<_>>(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter::BraceFormatter;
+    use crate::model::{Argument, PromptData};
+    use std::sync::Arc;
+
+    fn prompt(content: &str, arguments: &[(&str, Option<&str>)]) -> MarkdownPrompt {
+        let data = PromptData {
+            name: "review".to_string(),
+            title: "Review".to_string(),
+            description: "Review: code".to_string(),
+            content: content.to_string(),
+            arguments: arguments
+                .iter()
+                .map(|(name, default)| Argument {
+                    name: name.to_string(),
+                    default: default.map(str::to_string),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap()
+    }
+
+    #[test]
+    fn test_claude_command() {
+        assert_eq!(
+            claude_command(&prompt("Review {code} on {today}", &[("code", None)])).unwrap(),
+            "---\ndescription: 'Review: code'\nargument-hint: <code>\n---\n\nReview $ARGUMENTS on {today}\n"
+        );
+        assert_eq!(
+            claude_command(&prompt(
+                "Review {code} in {lang}",
+                &[("code", None), ("lang", Some("rust"))]
+            ))
+            .unwrap(),
+            "---\ndescription: 'Review: code'\nargument-hint: <code> [lang]\n---\n\nReview $1 in $2\n"
+        );
+        assert_eq!(
+            claude_command(&prompt("Just review\n", &[])).unwrap(),
+            "---\ndescription: 'Review: code'\n---\n\nJust review\n"
+        );
+    }
+
+    #[test]
+    fn test_cursor_rule() {
+        assert_eq!(
+            cursor_rule(&prompt(
+                "Review {code} in {lang}",
+                &[("code", None), ("lang", Some("rust"))]
+            ))
+            .unwrap(),
+            "---\ndescription: 'Review: code'\nglobs:\nalwaysApply: false\n---\n\nReview {code} in rust\n"
+        );
+    }
+
+    #[test]
+    fn test_openai_json() {
+        let json = openai_json(&prompt(
+            "Review {code} in {lang} on {today}",
+            &[("code", None), ("lang", Some("rust"))],
+        ))
+        .unwrap();
+        assert_eq!(
+            json["messages"],
+            json!([{ "role": "user", "content": "Review {{code}} in {{lang}} on {today}" }])
+        );
+        assert_eq!(
+            json["variables"],
+            json!([
+                { "name": "code", "description": "", "required": true, "default": null },
+                { "name": "lang", "description": "", "required": false, "default": "rust" },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_get_export_format() {
+        assert_eq!(
+            get_export_format("claude-commands").unwrap(),
+            ExportFormat::ClaudeCommands
+        );
+        assert_eq!(get_export_format("cursor").unwrap(), ExportFormat::Cursor);
+        assert!(get_export_format("windsurf").is_err());
+    }
+}
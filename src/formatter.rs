@@ -1,114 +1,1606 @@
 use anyhow::Result;
-use std::collections::{HashMap, HashSet};
+use handlebars::template::{Parameter, TemplateElement};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
 
-#[derive(Clone, Debug)]
-pub enum Formatter {
-    Brace,
-    Dollar,
+/// A placeholder syntax: finds the arguments a prompt's content uses and
+/// substitutes their values. Formats are looked up by name in a
+/// `FormatterRegistry`.
+pub trait Formatter: Send + Sync + std::fmt::Debug {
+    /// Argument names with their inline default, if any.
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders>;
+
+    /// Parse content, with its raw blocks masked, for rendering.
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>>;
+
+    /// How a placeholder for `name` is written, for scaffolding prompts.
+    fn placeholder(&self, name: &str) -> String {
+        format!("{{{}}}", name)
+    }
+
+    /// `text` written so it renders as is, for generating prompts. Unless a
+    /// format knows its escapes, text that would render differently is wrapped
+    /// in a raw block.
+    fn escape(&self, text: &str) -> String {
+        let literal = self.extract_arguments(text).is_ok_and(|a| a.is_empty())
+            && self
+                .format(text, &HashMap::new())
+                .is_ok_and(|rendered| rendered == text);
+        if literal {
+            text.to_string()
+        } else {
+            format!("{}{}{}", RAW_START, text, RAW_END)
+        }
+    }
+
+    /// This formatter with `partials` available to templates, by name, or
+    /// `None` if the format has no partials.
+    fn with_partials(
+        &self,
+        _partials: &BTreeMap<String, String>,
+    ) -> Result<Option<Arc<dyn Formatter>>> {
+        Ok(None)
+    }
+
+    /// Replace `{file:path}` placeholders with the text `read` returns for
+    /// each path, escaped so it's inserted as written. Formats without file
+    /// placeholders return the content unchanged.
+    fn include_files(
+        &self,
+        content: &str,
+        _read: &mut dyn FnMut(&str) -> Result<String>,
+    ) -> Result<String> {
+        Ok(content.to_string())
+    }
+
+    /// Argument names, skipping raw blocks.
+    fn extract_arguments(&self, content: &str) -> Result<HashSet<String>> {
+        let (masked, _, _) = mask_raw(content);
+        Ok(self.extract_placeholders(&masked)?.into_keys().collect())
+    }
+
+    /// Inline defaults like `{branch:-main}`, by argument name.
+    fn inline_defaults(&self, content: &str) -> Result<HashMap<String, String>> {
+        let (masked, _, _) = mask_raw(content);
+        Ok(self
+            .extract_placeholders(&masked)?
+            .into_iter()
+            .filter_map(|(name, default)| Some((name, default?)))
+            .collect())
+    }
+
+    /// Compile `content` once to format it many times, setting raw blocks aside.
+    fn prepare(&self, content: &str) -> Result<Prepared> {
+        let (masked, _, raw) = mask_raw(content);
+        Ok(Prepared {
+            template: self.compile(&masked)?,
+            raw: raw.into_iter().map(String::from).collect(),
+        })
+    }
+
+    /// Substitute `variables`, keeping raw blocks as written.
+    fn format(&self, content: &str, variables: &HashMap<String, String>) -> Result<String> {
+        self.prepare(content)?.format(variables)
+    }
+
+    /// Like `format`, but fails on unresolved placeholders, see
+    /// `Prepared::format_strict`.
+    fn format_strict(&self, content: &str, variables: &HashMap<String, String>) -> Result<String> {
+        self.prepare(content)?.format_strict(variables)
+    }
 }
 
-impl Formatter {
-    pub fn extract_arguments(&self, content: &str) -> Result<HashSet<String>> {
-        match self {
-            Formatter::Brace => extract_brace_args(content),
-            Formatter::Dollar => extract_dollar_args(content),
+/// Content compiled by a formatter.
+pub trait Template: Send + Sync + std::fmt::Debug {
+    /// Render, adding the placeholders kept as written to `unresolved`.
+    fn render(
+        &self,
+        variables: &HashMap<String, String>,
+        unresolved: &mut Vec<String>,
+    ) -> Result<String>;
+}
+
+/// A prompt's content compiled at load time, so renders don't parse it again.
+#[derive(Debug)]
+pub struct Prepared {
+    template: Box<dyn Template>,
+    raw: Vec<String>,
+}
+
+impl Prepared {
+    /// Substitute `variables`, keeping raw blocks as written.
+    pub fn format(&self, variables: &HashMap<String, String>) -> Result<String> {
+        let text = self.template.render(variables, &mut Vec::new())?;
+        Ok(unmask(&text, RAW_MARK, &self.raw))
+    }
+
+    /// Like `format`, but fails listing every placeholder left as written
+    /// because nothing resolved it, e.g. a missing field of a JSON value.
+    /// Template engines apply their own rules for undefined variables.
+    pub fn format_strict(&self, variables: &HashMap<String, String>) -> Result<String> {
+        let mut unresolved = Vec::new();
+        let text = self.template.render(variables, &mut unresolved)?;
+        if !unresolved.is_empty() {
+            unresolved.dedup();
+            anyhow::bail!("Unresolved placeholders: {}", unresolved.join(", "));
         }
+        Ok(unmask(&text, RAW_MARK, &self.raw))
     }
+}
 
-    pub fn format(&self, content: &str, variables: &HashMap<String, String>) -> String {
-        match self {
-            Formatter::Brace => format_brace(content, variables),
-            Formatter::Dollar => format_dollar(content, variables),
+/// Builds the formatter for a format name.
+type FormatterFactory = Box<dyn Fn() -> Arc<dyn Formatter> + Send + Sync>;
+
+/// Formats by name, for `--variable-format` and the `variable_format`
+/// frontmatter key. The default registry has the built-in formats.
+pub struct FormatterRegistry {
+    factories: BTreeMap<String, FormatterFactory>,
+}
+
+impl Default for FormatterRegistry {
+    fn default() -> Self {
+        let mut registry = FormatterRegistry {
+            factories: BTreeMap::new(),
+        };
+        registry.register("brace", || Arc::new(BraceFormatter));
+        registry.register("dollar", || Arc::new(DollarFormatter));
+        registry.register("dollar-brace", || Arc::new(DollarBraceFormatter));
+        #[cfg(feature = "templating-jinja")]
+        registry.register("jinja", || Arc::new(JinjaFormatter::default()));
+        registry.register("handlebars", || Arc::new(HandlebarsFormatter::default()));
+        registry.register("tera", || Arc::new(TeraFormatter::default()));
+        registry.register("mustache", || Arc::new(MustacheFormatter));
+        registry
+    }
+}
+
+impl FormatterRegistry {
+    /// Make a format available by `name`, replacing any with the same name.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn() -> Arc<dyn Formatter> + Send + Sync + 'static,
+    ) {
+        self.factories.insert(name.into(), Box::new(factory));
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<dyn Formatter>> {
+        match self.factories.get(name) {
+            Some(factory) => Ok(factory()),
+            #[cfg(not(feature = "templating-jinja"))]
+            None if name == "jinja" => anyhow::bail!(
+                "The jinja format needs shinkuro built with the `templating-jinja` feature"
+            ),
+            None => {
+                let names: Vec<_> = self.factories.keys().map(String::as_str).collect();
+                anyhow::bail!(
+                    "Unknown formatter: {}, expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// `{name}`, with `{{` and `}}` for literal braces, `{?name}` conditional and
+/// `{*name}` repeat blocks, and `{file:path}` placeholders.
+#[derive(Debug)]
+pub struct BraceFormatter;
+
+impl Formatter for BraceFormatter {
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders> {
+        extract_brace_args(content)
+    }
+
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+        Ok(Box::new(BraceTemplate(parse_brace_blocks(content)?)))
+    }
+
+    fn escape(&self, text: &str) -> String {
+        text.replace('{', "{{").replace('}', "}}")
+    }
+
+    fn include_files(
+        &self,
+        content: &str,
+        read: &mut dyn FnMut(&str) -> Result<String>,
+    ) -> Result<String> {
+        // Raw blocks are put back as written, for `format` to find again
+        let (masked, written, _) = mask_raw(content);
+        let mut result = String::with_capacity(content.len());
+        let mut rest = masked.as_str();
+        while let Some(start) = rest.find('{') {
+            if rest[start + 1..].starts_with('{') {
+                result.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                continue;
+            }
+            let file = rest[start + 1..]
+                .find('}')
+                .and_then(|len| Some((file_placeholder(&rest[start + 1..start + 1 + len])?, len)));
+            let Some((path, len)) = file else {
+                result.push_str(&rest[..start + 1]);
+                rest = &rest[start + 1..];
+                continue;
+            };
+            result.push_str(&rest[..start]);
+            result.push_str(&self.escape(&read(path)?));
+            rest = &rest[start + len + 2..];
+        }
+        result.push_str(rest);
+        Ok(unmask(&result, RAW_MARK, &written))
+    }
+}
+
+/// `$name`, with `$$` for a literal `$` and `${name}` for placeholders
+/// followed by name characters.
+#[derive(Debug)]
+pub struct DollarFormatter;
+
+impl Formatter for DollarFormatter {
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders> {
+        extract_parts(&parse_dollar(content))
+    }
+
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+        Ok(Box::new(PartsTemplate(parse_dollar(content))))
+    }
+
+    fn placeholder(&self, name: &str) -> String {
+        format!("${}", name)
+    }
+
+    fn escape(&self, text: &str) -> String {
+        text.replace('$', "$$")
+    }
+}
+
+/// `${name}`, with `$${` for a literal `${`.
+#[derive(Debug)]
+pub struct DollarBraceFormatter;
+
+impl Formatter for DollarBraceFormatter {
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders> {
+        extract_parts(&split_delimited(content, "${", "}", "$", |_| true))
+    }
+
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+        Ok(Box::new(PartsTemplate(split_delimited(
+            content,
+            "${",
+            "}",
+            "$",
+            |_| true,
+        ))))
+    }
+
+    fn placeholder(&self, name: &str) -> String {
+        format!("${{{}}}", name)
+    }
+
+    fn escape(&self, text: &str) -> String {
+        text.replace("${", "$${")
+    }
+}
+
+/// Names between custom delimiters, e.g. `<<name>>`, with a backslash
+/// before `open` for a literal delimiter.
+#[derive(Debug)]
+pub struct DelimitedFormatter {
+    open: String,
+    close: String,
+}
+
+impl DelimitedFormatter {
+    pub fn new(open: &str, close: &str) -> Result<Self> {
+        if open.is_empty() || close.is_empty() {
+            anyhow::bail!("Variable delimiters must not be empty");
+        }
+        Ok(DelimitedFormatter {
+            open: open.to_string(),
+            close: close.to_string(),
+        })
+    }
+}
+
+impl Formatter for DelimitedFormatter {
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders> {
+        extract_parts(&split_delimited(
+            content,
+            &self.open,
+            &self.close,
+            "\\",
+            is_delimited_name,
+        ))
+    }
+
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+        Ok(Box::new(PartsTemplate(split_delimited(
+            content,
+            &self.open,
+            &self.close,
+            "\\",
+            is_delimited_name,
+        ))))
+    }
+
+    fn placeholder(&self, name: &str) -> String {
+        format!("{}{}{}", self.open, name, self.close)
+    }
+}
+
+fn without_defaults(args: HashSet<String>) -> Placeholders {
+    args.into_iter().map(|a| (a, None)).collect()
+}
+
+/// Jinja templates rendered with minijinja, with conditionals, loops and filters.
+#[cfg(feature = "templating-jinja")]
+#[derive(Debug)]
+pub struct JinjaFormatter(minijinja::Environment<'static>);
+
+#[cfg(feature = "templating-jinja")]
+impl Default for JinjaFormatter {
+    fn default() -> Self {
+        JinjaFormatter(jinja_environment())
+    }
+}
+
+#[cfg(feature = "templating-jinja")]
+impl Formatter for JinjaFormatter {
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders> {
+        extract_jinja_args(&self.0, content).map(without_defaults)
+    }
+
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+        let mut env = self.0.clone();
+        env.add_template_owned(TEMPLATE_NAME, content.to_string())?;
+        Ok(Box::new(JinjaTemplate(env)))
+    }
+
+    fn placeholder(&self, name: &str) -> String {
+        format!("{{{{ {} }}}}", name)
+    }
+}
+
+#[cfg(feature = "templating-jinja")]
+#[derive(Debug)]
+struct JinjaTemplate(minijinja::Environment<'static>);
+
+#[cfg(feature = "templating-jinja")]
+impl Template for JinjaTemplate {
+    fn render(
+        &self,
+        variables: &HashMap<String, String>,
+        _unresolved: &mut Vec<String>,
+    ) -> Result<String> {
+        Ok(self.0.get_template(TEMPLATE_NAME)?.render(variables)?)
+    }
+}
+
+/// Handlebars templates, with the partials of the prompt's folder registered.
+#[derive(Debug)]
+pub struct HandlebarsFormatter(handlebars::Handlebars<'static>);
+
+impl Default for HandlebarsFormatter {
+    fn default() -> Self {
+        let mut registry = handlebars::Handlebars::new();
+        // Prompts are plain text, never HTML
+        registry.register_escape_fn(handlebars::no_escape);
+        HandlebarsFormatter(registry)
+    }
+}
+
+impl Formatter for HandlebarsFormatter {
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders> {
+        extract_handlebars_args(&self.0, content).map(without_defaults)
+    }
+
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+        let mut registry = self.0.clone();
+        registry.register_template_string(TEMPLATE_NAME, content)?;
+        Ok(Box::new(HandlebarsTemplate(registry)))
+    }
+
+    fn placeholder(&self, name: &str) -> String {
+        format!("{{{{{}}}}}", name)
+    }
+
+    fn with_partials(
+        &self,
+        partials: &BTreeMap<String, String>,
+    ) -> Result<Option<Arc<dyn Formatter>>> {
+        if partials.is_empty() {
+            return Ok(None);
+        }
+        let mut registry = self.0.clone();
+        for (name, partial) in partials {
+            registry
+                .register_partial(name, partial)
+                .map_err(|e| anyhow::anyhow!("Invalid partial '{}': {}", name, e))?;
         }
+        Ok(Some(Arc::new(HandlebarsFormatter(registry))))
+    }
+}
+
+#[derive(Debug)]
+struct HandlebarsTemplate(handlebars::Handlebars<'static>);
+
+impl Template for HandlebarsTemplate {
+    fn render(
+        &self,
+        variables: &HashMap<String, String>,
+        _unresolved: &mut Vec<String>,
+    ) -> Result<String> {
+        Ok(self.0.render(TEMPLATE_NAME, variables)?)
+    }
+}
+
+/// Tera templates, without access to the environment or other templates.
+#[derive(Debug)]
+pub struct TeraFormatter(tera::Tera);
+
+impl Default for TeraFormatter {
+    fn default() -> Self {
+        TeraFormatter(tera_environment())
     }
 }
 
+impl Formatter for TeraFormatter {
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders> {
+        extract_tera_args(&self.0, content).map(without_defaults)
+    }
+
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+        let mut tera = self.0.clone();
+        tera.add_raw_template(TEMPLATE_NAME, content)
+            .map_err(tera_error)?;
+        Ok(Box::new(TeraTemplate(tera)))
+    }
+
+    fn placeholder(&self, name: &str) -> String {
+        format!("{{{{ {} }}}}", name)
+    }
+}
+
+#[derive(Debug)]
+struct TeraTemplate(tera::Tera);
+
+impl Template for TeraTemplate {
+    fn render(
+        &self,
+        variables: &HashMap<String, String>,
+        _unresolved: &mut Vec<String>,
+    ) -> Result<String> {
+        let context = tera::Context::from_serialize(variables)?;
+        self.0.render(TEMPLATE_NAME, &context).map_err(tera_error)
+    }
+}
+
+/// Logic-less Mustache templates, escaping `{{name}}` like mustache.js.
+#[derive(Debug)]
+pub struct MustacheFormatter;
+
+impl Formatter for MustacheFormatter {
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders> {
+        let args = crate::mustache::extract_arguments(content)?;
+        if let Some(name) = args.iter().find(|name| !validate_variable_name(name)) {
+            anyhow::bail!("Invalid variable name: {}", name);
+        }
+        Ok(without_defaults(args))
+    }
+
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+        Ok(Box::new(MustacheTemplate(crate::mustache::compile(
+            content,
+        )?)))
+    }
+
+    fn placeholder(&self, name: &str) -> String {
+        format!("{{{{{}}}}}", name)
+    }
+}
+
+#[derive(Debug)]
+struct MustacheTemplate(crate::mustache::Template);
+
+impl Template for MustacheTemplate {
+    fn render(
+        &self,
+        variables: &HashMap<String, String>,
+        _unresolved: &mut Vec<String>,
+    ) -> Result<String> {
+        Ok(self.0.render(variables))
+    }
+}
+
+/// Wraps another formatter so placeholders in Markdown code, both fenced
+/// blocks and inline spans, are left as written.
+#[derive(Debug)]
+pub struct SkipCodeFormatter(pub Arc<dyn Formatter>);
+
+impl Formatter for SkipCodeFormatter {
+    fn extract_placeholders(&self, content: &str) -> Result<Placeholders> {
+        self.0.extract_placeholders(&mask_code(content).0)
+    }
+
+    fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+        let (masked, code) = mask_code(content);
+        Ok(Box::new(SkipCodeTemplate {
+            inner: self.0.compile(&masked)?,
+            code: code.into_iter().map(String::from).collect(),
+        }))
+    }
+
+    fn placeholder(&self, name: &str) -> String {
+        self.0.placeholder(name)
+    }
+
+    fn with_partials(
+        &self,
+        partials: &BTreeMap<String, String>,
+    ) -> Result<Option<Arc<dyn Formatter>>> {
+        Ok(self
+            .0
+            .with_partials(partials)?
+            .map(|inner| Arc::new(SkipCodeFormatter(inner)) as Arc<dyn Formatter>))
+    }
+
+    fn include_files(
+        &self,
+        content: &str,
+        read: &mut dyn FnMut(&str) -> Result<String>,
+    ) -> Result<String> {
+        let (masked, code) = mask_code(content);
+        let text = self.0.include_files(&masked, read)?;
+        Ok(unmask(&text, CODE_MARK, &code))
+    }
+}
+
+#[derive(Debug)]
+struct SkipCodeTemplate {
+    inner: Box<dyn Template>,
+    code: Vec<String>,
+}
+
+impl Template for SkipCodeTemplate {
+    fn render(
+        &self,
+        variables: &HashMap<String, String>,
+        unresolved: &mut Vec<String>,
+    ) -> Result<String> {
+        let text = self.inner.render(variables, unresolved)?;
+        Ok(unmask(&text, CODE_MARK, &self.code))
+    }
+}
+
+/// Marks a masked code span, from Unicode's private use area so no format
+/// treats it as syntax.
+const CODE_MARK: char = '\u{E000}';
+/// Marks a masked raw block, distinct from `CODE_MARK` as code is masked
+/// inside content with raw blocks masked.
+const RAW_MARK: char = '\u{E001}';
+
+const RAW_START: &str = "<!-- shinkuro:raw -->";
+const RAW_END: &str = "<!-- shinkuro:endraw -->";
+
+/// Replace raw blocks, from `RAW_START` to `RAW_END` or the end of the
+/// content, with numbered marks. Returns the masked content, the blocks as
+/// written and their content without the markers. A marker at the start of
+/// a line is removed with the newline that follows it.
+fn mask_raw(content: &str) -> (String, Vec<&str>, Vec<&str>) {
+    let mut masked = String::with_capacity(content.len());
+    let mut written = Vec::new();
+    let mut raw = Vec::new();
+    let mut rest = content;
+    // Skip the newline after a marker starting a line
+    let skip_newline = |before: &str, after: &str| {
+        let starts_line = before.is_empty() || before.ends_with('\n');
+        match after
+            .strip_prefix("\r\n")
+            .or_else(|| after.strip_prefix('\n'))
+        {
+            Some(stripped) if starts_line => after.len() - stripped.len(),
+            _ => 0,
+        }
+    };
+    while let Some(start) = rest.find(RAW_START) {
+        let inner_start = start + RAW_START.len();
+        let inner_start = inner_start + skip_newline(&rest[..start], &rest[inner_start..]);
+        let (inner_end, end) = match rest[inner_start..].find(RAW_END) {
+            Some(found) => {
+                let inner_end = inner_start + found;
+                let end = inner_end + RAW_END.len();
+                (
+                    inner_end,
+                    end + skip_newline(&rest[..inner_end], &rest[end..]),
+                )
+            }
+            None => (rest.len(), rest.len()),
+        };
+        masked.push_str(&rest[..start]);
+        masked.push(RAW_MARK);
+        masked.push_str(&raw.len().to_string());
+        masked.push(RAW_MARK);
+        written.push(&rest[start..end]);
+        raw.push(&rest[inner_start..inner_end]);
+        rest = &rest[end..];
+    }
+    masked.push_str(rest);
+    (masked, written, raw)
+}
+
+/// Replace fenced code blocks and inline code spans with numbered marks,
+/// returning the masked content and the code by number.
+fn mask_code<'a>(content: &'a str) -> (String, Vec<&'a str>) {
+    let mut masked = String::with_capacity(content.len());
+    let mut code = Vec::new();
+    let mut mark = |code_span: &'a str, masked: &mut String| {
+        masked.push(CODE_MARK);
+        masked.push_str(&code.len().to_string());
+        masked.push(CODE_MARK);
+        code.push(code_span);
+    };
+    let mut pos = 0;
+    // Prose between fences, whose inline spans are masked once it ends
+    let mut prose = 0;
+    while pos < content.len() {
+        let line_end = content[pos..]
+            .find('\n')
+            .map_or(content.len(), |i| pos + i + 1);
+        let Some(fence) = code_fence(&content[pos..line_end]) else {
+            pos = line_end;
+            continue;
+        };
+        mask_inline_code(&content[prose..pos], &mut masked, &mut mark);
+        // The block runs to a closing fence at least as long, or the end of the content
+        let mut end = line_end;
+        while end < content.len() {
+            let next = content[end..]
+                .find('\n')
+                .map_or(content.len(), |i| end + i + 1);
+            let closing = code_fence(&content[end..next]).is_some_and(|closing| {
+                closing.starts_with(fence) && content[end..next].trim().len() == closing.len()
+            });
+            end = next;
+            if closing {
+                break;
+            }
+        }
+        // Keep the newline ending the block outside the mark
+        let block_end = if content[..end].ends_with('\n') {
+            end - 1
+        } else {
+            end
+        };
+        mark(&content[pos..block_end], &mut masked);
+        masked.push_str(&content[block_end..end]);
+        pos = end;
+        prose = end;
+    }
+    mask_inline_code(&content[prose..], &mut masked, &mut mark);
+    (masked, code)
+}
+
+/// The fence of a line opening or closing a fenced code block, e.g. "```".
+fn code_fence(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let fence_char = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let length = trimmed.len() - trimmed.trim_start_matches(fence_char).len();
+    (length >= 3).then(|| &trimmed[..length])
+}
+
+/// Mask inline code spans: a run of backticks up to the next run of the same
+/// length. Runs without a match are kept as written.
+fn mask_inline_code<'a>(
+    text: &'a str,
+    masked: &mut String,
+    mark: &mut impl FnMut(&'a str, &mut String),
+) {
+    let mut pos = 0;
+    let mut search = 0;
+    while let Some(found) = text[search..].find('`') {
+        let start = search + found;
+        let length = text[start..].len() - text[start..].trim_start_matches('`').len();
+        let run = &text[start..start + length];
+        let mut close = start + length;
+        let end = loop {
+            let Some(found) = text[close..].find(run) else {
+                break None;
+            };
+            let candidate = close + found;
+            let after = &text[candidate..];
+            let candidate_length = after.len() - after.trim_start_matches('`').len();
+            if candidate_length == length {
+                break Some(candidate + length);
+            }
+            close = candidate + candidate_length;
+        };
+        match end {
+            Some(end) => {
+                masked.push_str(&text[pos..start]);
+                mark(&text[start..end], masked);
+                pos = end;
+                search = end;
+            }
+            None => search = start + length,
+        }
+    }
+    masked.push_str(&text[pos..]);
+}
+
+/// Put the spans masked with `mark` back.
+fn unmask(text: &str, mark: char, spans: &[impl AsRef<str>]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(mark) {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + mark.len_utf8()..];
+        let span = after.find(mark).and_then(|end| {
+            let index: usize = after[..end].parse().ok()?;
+            Some((spans.get(index)?.as_ref(), end))
+        });
+        match span {
+            Some((span, end)) => {
+                result.push_str(span);
+                rest = &after[end + mark.len_utf8()..];
+            }
+            None => {
+                result.push(mark);
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// A Unicode identifier (`XID_Start` or `_`, then `XID_Continue`), so names
+/// like `名前` work as well as `user_name`.
 pub fn validate_variable_name(name: &str) -> bool {
     if name.is_empty() {
         return false;
     }
     let mut chars = name.chars();
     let first = chars.next().unwrap();
-    if !first.is_ascii_alphabetic() && first != '_' {
+    if !unicode_ident::is_xid_start(first) && first != '_' {
         return false;
     }
-    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    chars.all(unicode_ident::is_xid_continue)
 }
 
-fn extract_brace_args(content: &str) -> Result<HashSet<String>> {
-    let mut args = HashSet::new();
-    let mut chars = content.chars().peekable();
+/// A variable name optionally followed by `.`-separated fields or array
+/// indexes, e.g. `user.name` or `items.0`.
+pub fn validate_variable_path(path: &str) -> bool {
+    let mut segments = path.split('.');
+    segments.next().is_some_and(validate_variable_name)
+        && segments.all(|s| {
+            validate_variable_name(s) || (!s.is_empty() && s.chars().all(|c| c.is_ascii_digit()))
+        })
+}
 
-    while let Some(c) = chars.next() {
-        if c == '{' {
-            if chars.peek() == Some(&'{') {
-                chars.next();
+/// The argument a variable path reads, e.g. `user` for `user.name`.
+fn path_root(path: &str) -> &str {
+    path.split('.').next().unwrap_or(path)
+}
+
+/// The value at `path`. Fields are looked up in the argument's value parsed
+/// as JSON; strings are used as is and anything else as JSON text.
+fn lookup_path(variables: &HashMap<String, String>, path: &str) -> Option<String> {
+    let Some((root, fields)) = path.split_once('.') else {
+        return variables.get(path).cloned();
+    };
+    let mut value: serde_json::Value = serde_json::from_str(variables.get(root)?).ok()?;
+    for field in fields.split('.') {
+        value = match value {
+            serde_json::Value::Object(mut map) => map.remove(field)?,
+            serde_json::Value::Array(mut items) => {
+                let index: usize = field.parse().ok()?;
+                (index < items.len()).then(|| items.swap_remove(index))?
+            }
+            _ => return None,
+        };
+    }
+    Some(match value {
+        serde_json::Value::String(s) => s,
+        value => value.to_string(),
+    })
+}
+
+/// Argument names with the inline default of any of their placeholders.
+pub type Placeholders = HashMap<String, Option<String>>;
+
+/// Split a placeholder like `branch:-main|upper` into the variable and its
+/// default, ignoring any filters.
+fn split_inline_default(placeholder: &str) -> (&str, Option<&str>) {
+    let (variable, _) = split_filters(placeholder);
+    match variable.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (variable, None),
+    }
+}
+
+/// Split a placeholder like `text|trim|truncate:200` into the variable and
+/// its filters.
+fn split_filters(placeholder: &str) -> (&str, std::str::Split<'_, char>) {
+    let mut parts = placeholder.split('|');
+    (parts.next().unwrap_or_default(), parts)
+}
+
+/// Apply a filter like `upper` or `truncate:200` to a value.
+fn apply_filter(value: String, filter: &str) -> Result<String> {
+    let (name, argument) = match filter.trim().split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (filter.trim(), None),
+    };
+    let path = || std::path::Path::new(&value);
+    let filtered = match (name, argument) {
+        ("upper", None) => value.to_uppercase(),
+        ("lower", None) => value.to_lowercase(),
+        ("trim", None) => value.trim().to_string(),
+        ("capitalize", None) => {
+            let mut chars = value.chars();
+            chars.next().map_or(String::new(), |first| {
+                first.to_uppercase().chain(chars).collect()
+            })
+        }
+        ("basename", None) => path()
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned()),
+        ("dirname", None) => path()
+            .parent()
+            .map_or(String::new(), |dir| dir.to_string_lossy().into_owned()),
+        ("json", None) => serde_json::Value::String(value).to_string(),
+        ("join", None) => list_items(&value).join(", "),
+        ("join", Some(separator)) => {
+            let separator = ['"', '\'']
+                .into_iter()
+                .find_map(|q| separator.strip_prefix(q)?.strip_suffix(q))
+                .unwrap_or(separator);
+            list_items(&value).join(separator)
+        }
+        ("truncate", Some(length)) => {
+            let length: usize = length
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid length for truncate: {}", length))?;
+            value.chars().take(length).collect()
+        }
+        _ => anyhow::bail!("Unknown filter: {}", filter),
+    };
+    Ok(filtered)
+}
+
+/// Record a placeholder, checking its name.
+fn add_placeholder(args: &mut Placeholders, placeholder: &str) -> Result<()> {
+    let (path, default) = split_inline_default(placeholder);
+    if !validate_variable_path(path) {
+        anyhow::bail!("Invalid variable name: {}", path);
+    }
+    for filter in split_filters(placeholder).1 {
+        apply_filter(String::new(), filter)?;
+    }
+    let entry = args.entry(path_root(path).to_string()).or_default();
+    if let Some(default) = default {
+        *entry = Some(default.to_string());
+    }
+    Ok(())
+}
+
+/// The value for a placeholder, falling back to its inline default when the
+/// variable is missing or empty, with its filters applied. `None` if there's
+/// neither a value nor a default.
+fn placeholder_value(variables: &HashMap<String, String>, placeholder: &str) -> Option<String> {
+    let (path, default) = split_inline_default(placeholder);
+    let value = match (lookup_path(variables, path), default) {
+        (Some(value), Some(default)) if value.is_empty() => default.to_string(),
+        (None, Some(default)) => default.to_string(),
+        (value, _) => value?,
+    };
+    // Filters were checked when the arguments were extracted
+    Some(split_filters(placeholder).1.fold(value, |value, filter| {
+        apply_filter(value.clone(), filter).unwrap_or(value)
+    }))
+}
+
+fn extract_brace_args(content: &str) -> Result<Placeholders> {
+    fn collect(nodes: &[BraceNode], args: &mut Placeholders) -> Result<()> {
+        for node in nodes {
+            match node {
+                BraceNode::Text(parts) => {
+                    for part in parts {
+                        if let Part::Placeholder { placeholder, .. } = part {
+                            if !placeholder.is_empty() && file_placeholder(placeholder).is_none() {
+                                add_placeholder(args, placeholder)?;
+                            }
+                        }
+                    }
+                }
+                BraceNode::Block {
+                    kind,
+                    path,
+                    children,
+                } => {
+                    let entry = args.entry(path_root(path).to_string()).or_default();
+                    // A condition makes its variable optional, like an inline default
+                    if *kind == BlockKind::Condition {
+                        entry.get_or_insert_with(String::new);
+                    }
+                    collect(children, args)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    let mut args = Placeholders::new();
+    collect(&parse_brace_blocks(content)?, &mut args)?;
+    Ok(args)
+}
+
+/// The path of a `{file:path}` placeholder. `{file:-default}` is an inline
+/// default for a variable named `file` instead.
+fn file_placeholder(tag: &str) -> Option<&str> {
+    tag.strip_prefix("file:")
+        .filter(|path| !path.is_empty() && !path.starts_with('-'))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BlockKind {
+    /// `{?name}...{/name}`: the content is kept when `name` is non-empty.
+    Condition,
+    /// `{*name}...{/name}`: the content is repeated for each item of `name`.
+    Repeat,
+}
+
+impl BlockKind {
+    fn sigil(&self) -> char {
+        match self {
+            BlockKind::Condition => '?',
+            BlockKind::Repeat => '*',
+        }
+    }
+}
+
+/// Brace format content split into text, which holds the placeholders, and blocks.
+#[derive(Debug)]
+enum BraceNode {
+    Text(Vec<Part>),
+    Block {
+        kind: BlockKind,
+        path: String,
+        children: Vec<BraceNode>,
+    },
+}
+
+/// Parse the `{?name}`, `{*name}` and `{/name}` block tags. Tags alone on
+/// their line are removed with the line, so blocks leave no blank lines behind.
+fn parse_brace_blocks(content: &str) -> Result<Vec<BraceNode>> {
+    let mut blocks: Vec<(BlockKind, &str, Vec<BraceNode>)> = Vec::new();
+    let mut nodes = Vec::new();
+    // Start of the text not yet added, and where to look for the next tag
+    let mut pos = 0;
+    let mut search = 0;
+    while let Some(found) = content[search..].find('{') {
+        let start = search + found;
+        if content[start + 1..].starts_with('{') {
+            search = start + 2;
+            continue;
+        }
+        let Some(len) = content[start + 1..].find('}') else {
+            break;
+        };
+        let end = start + len + 2;
+        let tag = &content[start + 1..end - 1];
+        let (kind, path) = match tag.chars().next() {
+            Some('?') => (Some(BlockKind::Condition), &tag[1..]),
+            Some('*') => (Some(BlockKind::Repeat), &tag[1..]),
+            Some('/') => (None, &tag[1..]),
+            _ => {
+                search = end;
                 continue;
             }
-            let mut name = String::new();
-            let mut found_close = false;
-            while let Some(c) = chars.next() {
-                if c == '}' {
-                    found_close = true;
-                    break;
+        };
+        let valid = match kind {
+            Some(BlockKind::Repeat) => validate_variable_name(path),
+            _ => validate_variable_path(path),
+        };
+        if !valid {
+            anyhow::bail!("Invalid variable name: {}", path);
+        }
+
+        let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+        let rest = &content[end..];
+        let line_end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+        let (text_end, next) = if line_start >= pos
+            && content[line_start..start].trim().is_empty()
+            && rest[..line_end].trim().is_empty()
+        {
+            (line_start, end + line_end)
+        } else {
+            (start, end)
+        };
+        if text_end > pos {
+            nodes.push(BraceNode::Text(parse_brace(&content[pos..text_end])));
+        }
+        match kind {
+            Some(kind) => blocks.push((kind, path, std::mem::take(&mut nodes))),
+            None => match blocks.pop() {
+                Some((kind, open, parent)) if open == path => {
+                    let children = std::mem::replace(&mut nodes, parent);
+                    nodes.push(BraceNode::Block {
+                        kind,
+                        path: path.to_string(),
+                        children,
+                    });
+                }
+                _ => anyhow::bail!("Unexpected {{/{}}}", path),
+            },
+        }
+        pos = next;
+        search = next;
+    }
+    if let Some((kind, path, _)) = blocks.last() {
+        anyhow::bail!("Unclosed block {{{}{}}}", kind.sigil(), path);
+    }
+    if pos < content.len() {
+        nodes.push(BraceNode::Text(parse_brace(&content[pos..])));
+    }
+    Ok(nodes)
+}
+
+/// The items of a list value: the elements of a JSON array, or else the
+/// value itself unless it's empty.
+fn list_items(value: &str) -> Vec<String> {
+    match serde_json::from_str(value) {
+        Ok(serde_json::Value::Array(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                serde_json::Value::String(s) => s,
+                item => item.to_string(),
+            })
+            .collect(),
+        _ if value.is_empty() => Vec::new(),
+        _ => vec![value.to_string()],
+    }
+}
+
+/// Format brace content, keeping the blocks whose condition holds and
+/// repeating each repeat block with its variable set to each item in turn.
+fn format_brace_blocks(
+    nodes: &[BraceNode],
+    variables: &HashMap<String, String>,
+    unresolved: &mut Vec<String>,
+) -> String {
+    let mut result = String::new();
+    for node in nodes {
+        match node {
+            BraceNode::Text(parts) => result.push_str(&format_parts(parts, variables, unresolved)),
+            BraceNode::Block {
+                kind: BlockKind::Condition,
+                path,
+                children,
+            } => {
+                if lookup_path(variables, path).is_some_and(|v| !v.is_empty()) {
+                    result.push_str(&format_brace_blocks(children, variables, unresolved));
                 }
-                name.push(c);
             }
-            if found_close && !name.is_empty() {
-                if !validate_variable_name(&name) {
-                    anyhow::bail!("Invalid variable name: {}", name);
+            BraceNode::Block {
+                kind: BlockKind::Repeat,
+                path,
+                children,
+            } => {
+                let value = variables.get(path).map_or("", String::as_str);
+                let mut item_variables = variables.clone();
+                for item in list_items(value) {
+                    item_variables.insert(path.clone(), item);
+                    result.push_str(&format_brace_blocks(children, &item_variables, unresolved));
                 }
-                args.insert(name);
             }
         }
     }
+    result
+}
+
+#[derive(Debug)]
+struct BraceTemplate(Vec<BraceNode>);
+
+impl Template for BraceTemplate {
+    fn render(
+        &self,
+        variables: &HashMap<String, String>,
+        unresolved: &mut Vec<String>,
+    ) -> Result<String> {
+        Ok(format_brace_blocks(&self.0, variables, unresolved))
+    }
+}
+
+/// Read a `$` variable path like `ticket.summary`. A `.` is only part of the
+/// path when a name character follows, so `$user.` ends with the name.
+fn read_dollar_path(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let is_name_char = |c: &char| unicode_ident::is_xid_continue(*c);
+    let mut name = String::new();
+    loop {
+        while let Some(c) = chars.next_if(is_name_char) {
+            name.push(c);
+        }
+        let mut lookahead = chars.clone();
+        if name.is_empty()
+            || lookahead.next() != Some('.')
+            || !lookahead.peek().is_some_and(is_name_char)
+        {
+            return name;
+        }
+        name.push('.');
+        chars.next();
+    }
+}
+
+/// Read a `{name}` or `{name:-default}` placeholder after a `$`, leaving
+/// `chars` untouched if there isn't one.
+fn read_dollar_braces(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<String> {
+    let mut lookahead = chars.clone();
+    if lookahead.next() != Some('{') {
+        return None;
+    }
+    let mut placeholder = String::new();
+    for c in lookahead.by_ref() {
+        if c == '}' {
+            let (path, _) = split_inline_default(&placeholder);
+            if !validate_variable_path(path) {
+                return None;
+            }
+            *chars = lookahead;
+            return Some(placeholder);
+        }
+        placeholder.push(c);
+    }
+    None
+}
+
+/// Variables the template reads without defining them, excluding globals
+/// like `range`.
+#[cfg(feature = "templating-jinja")]
+fn extract_jinja_args(
+    env: &minijinja::Environment<'static>,
+    content: &str,
+) -> Result<HashSet<String>> {
+    let template = env
+        .template_from_str(content)
+        .map_err(|e| anyhow::anyhow!("Invalid template: {}", e))?;
+    let globals: HashSet<&str> = env.globals().map(|(name, _)| name).collect();
+    let mut args = HashSet::new();
+    for name in template.undeclared_variables(false) {
+        if globals.contains(name.as_str()) {
+            continue;
+        }
+        if !validate_variable_name(&name) {
+            anyhow::bail!("Invalid variable name: {}", name);
+        }
+        args.insert(name);
+    }
     Ok(args)
 }
 
-fn extract_dollar_args(content: &str) -> Result<HashSet<String>> {
+#[cfg(feature = "templating-jinja")]
+fn jinja_environment() -> minijinja::Environment<'static> {
+    let mut env = minijinja::Environment::new();
+    // Prompts are plain text, never HTML
+    env.set_auto_escape_callback(|_| minijinja::AutoEscape::None);
+    env.set_keep_trailing_newline(true);
+    env
+}
+
+fn extract_handlebars_args(
+    registry: &handlebars::Handlebars<'static>,
+    content: &str,
+) -> Result<HashSet<String>> {
+    let template = handlebars::template::Template::compile(content)
+        .map_err(|e| anyhow::anyhow!("Invalid template: {}", e))?;
     let mut args = HashSet::new();
-    let mut chars = content.chars().peekable();
+    collect_handlebars_args(registry, &template, &mut args, &mut Vec::new());
+    for name in &args {
+        if !validate_variable_name(name) {
+            anyhow::bail!("Invalid variable name: {}", name);
+        }
+    }
+    Ok(args)
+}
 
-    while let Some(c) = chars.next() {
-        if c == '$' {
-            if chars.peek() == Some(&'$') {
-                chars.next();
-                continue;
+/// Collect the top-level names `template` reads from the root context,
+/// following partials. Bodies of blocks that change the context, like `each`
+/// and `with`, are skipped since names there refer to the new context.
+fn collect_handlebars_args<'a>(
+    registry: &'a handlebars::Handlebars<'static>,
+    template: &'a handlebars::template::Template,
+    args: &mut HashSet<String>,
+    partials: &mut Vec<&'a str>,
+) {
+    for element in &template.elements {
+        match element {
+            TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper) => {
+                if helper.params.is_empty() && helper.hash.is_empty() {
+                    collect_parameter(&helper.name, args);
+                }
+                helper
+                    .params
+                    .iter()
+                    .chain(helper.hash.values())
+                    .for_each(|p| collect_parameter(p, args));
             }
-            let mut name = String::new();
-            while let Some(&c) = chars.peek() {
-                if c.is_ascii_alphanumeric() || c == '_' {
-                    name.push(c);
-                    chars.next();
-                } else {
-                    break;
+            TemplateElement::HelperBlock(helper) => {
+                helper
+                    .params
+                    .iter()
+                    .chain(helper.hash.values())
+                    .for_each(|p| collect_parameter(p, args));
+                let same_context = matches!(
+                    &helper.name,
+                    Parameter::Name(name) if name == "if" || name == "unless"
+                );
+                if same_context {
+                    if let Some(body) = &helper.template {
+                        collect_handlebars_args(registry, body, args, partials);
+                    }
+                }
+                if let Some(inverse) = &helper.inverse {
+                    collect_handlebars_args(registry, inverse, args, partials);
+                }
+            }
+            TemplateElement::PartialExpression(partial)
+            | TemplateElement::PartialBlock(partial) => {
+                partial
+                    .params
+                    .iter()
+                    .chain(partial.hash.values())
+                    .for_each(|p| collect_parameter(p, args));
+                if let Parameter::Name(name) = &partial.name {
+                    if !partials.contains(&name.as_str()) {
+                        if let Some(body) = registry.get_template(name) {
+                            partials.push(name);
+                            collect_handlebars_args(registry, body, args, partials);
+                            partials.pop();
+                        }
+                    }
+                }
+                if let Some(body) = &partial.template {
+                    collect_handlebars_args(registry, body, args, partials);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_parameter(parameter: &Parameter, args: &mut HashSet<String>) {
+    match parameter {
+        Parameter::Name(name) => {
+            args.insert(name.clone());
+        }
+        Parameter::Path(handlebars::Path::Relative((segments, _))) => {
+            if let Some(handlebars::PathSeg::Named(name)) = segments.first() {
+                if name != "this" {
+                    args.insert(name.clone());
+                }
+            }
+        }
+        Parameter::Subexpression(subexpression) => {
+            if let TemplateElement::Expression(helper) = subexpression.as_element() {
+                helper
+                    .params
+                    .iter()
+                    .chain(helper.hash.values())
+                    .for_each(|p| collect_parameter(p, args));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Tera's messages are spread over the error's sources, e.g. which variable
+/// was missing, so join them.
+fn tera_error(error: tera::Error) -> anyhow::Error {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(&error);
+    while let Some(e) = source {
+        message.push_str(&format!(": {}", e));
+        source = e.source();
+    }
+    anyhow::anyhow!(message)
+}
+
+fn tera_environment() -> tera::Tera {
+    let mut tera = tera::Tera::default();
+    // Prompts are plain text, never HTML
+    tera.autoescape_on(Vec::new());
+    // Prompt libraries may come from untrusted repositories
+    tera.register_function("get_env", |_: &HashMap<String, tera::Value>| {
+        Err(tera::Error::msg("get_env is disabled"))
+    });
+    tera
+}
+
+fn extract_tera_args(tera: &tera::Tera, content: &str) -> Result<HashSet<String>> {
+    let mut tera = tera.clone();
+    tera.add_raw_template(TEMPLATE_NAME, content)
+        .map_err(|e| anyhow::anyhow!("Invalid template: {}", tera_error(e)))?;
+    let template = tera.get_template(TEMPLATE_NAME).map_err(tera_error)?;
+    let mut args = HashSet::new();
+    collect_tera_nodes(&template.ast, &mut Vec::new(), &mut args);
+    for name in &args {
+        if !validate_variable_name(name) {
+            anyhow::bail!("Invalid variable name: {}", name);
+        }
+    }
+    Ok(args)
+}
+
+/// The name template engines register a prompt's content under.
+const TEMPLATE_NAME: &str = "prompt";
+
+/// Collect the names `nodes` read from the context, skipping those declared
+/// by `set` and `for` (`locals`).
+fn collect_tera_nodes(
+    nodes: &[tera::ast::Node],
+    locals: &mut Vec<String>,
+    args: &mut HashSet<String>,
+) {
+    use tera::ast::Node;
+    for node in nodes {
+        match node {
+            Node::VariableBlock(_, expr) => collect_tera_expr(expr, locals, args),
+            Node::Set(_, set) => {
+                collect_tera_expr(&set.value, locals, args);
+                locals.push(set.key.clone());
+            }
+            Node::FilterSection(_, section, _) => {
+                section
+                    .filter
+                    .args
+                    .values()
+                    .for_each(|e| collect_tera_expr(e, locals, args));
+                collect_tera_nodes(&section.body, locals, args);
+            }
+            Node::Block(_, block, _) => collect_tera_nodes(&block.body, locals, args),
+            Node::Forloop(_, forloop, _) => {
+                collect_tera_expr(&forloop.container, locals, args);
+                let depth = locals.len();
+                locals.extend(forloop.key.iter().cloned());
+                locals.push(forloop.value.clone());
+                locals.push("loop".to_string());
+                collect_tera_nodes(&forloop.body, locals, args);
+                locals.truncate(depth);
+                if let Some(empty) = &forloop.empty_body {
+                    collect_tera_nodes(empty, locals, args);
                 }
             }
-            if !name.is_empty() {
-                if !validate_variable_name(&name) {
-                    anyhow::bail!("Invalid variable name: {}", name);
+            Node::If(condition, _) => {
+                for (_, expr, body) in &condition.conditions {
+                    collect_tera_expr(expr, locals, args);
+                    collect_tera_nodes(body, locals, args);
+                }
+                if let Some((_, body)) = &condition.otherwise {
+                    collect_tera_nodes(body, locals, args);
                 }
-                args.insert(name);
             }
+            _ => {}
+        }
+    }
+}
+
+fn collect_tera_expr(expr: &tera::ast::Expr, locals: &[String], args: &mut HashSet<String>) {
+    collect_tera_value(&expr.val, locals, args);
+    for filter in &expr.filters {
+        filter
+            .args
+            .values()
+            .for_each(|e| collect_tera_expr(e, locals, args));
+    }
+}
+
+fn collect_tera_value(value: &tera::ast::ExprVal, locals: &[String], args: &mut HashSet<String>) {
+    use tera::ast::ExprVal;
+    let mut ident = |ident: &str| {
+        let root = ident.split(['.', '[']).next().unwrap_or_default();
+        if !root.starts_with("__tera") && !locals.iter().any(|l| l == root) {
+            args.insert(root.to_string());
+        }
+    };
+    match value {
+        ExprVal::Ident(name) => ident(name),
+        ExprVal::Test(test) => {
+            ident(&test.ident);
+            test.args
+                .iter()
+                .for_each(|e| collect_tera_expr(e, locals, args));
+        }
+        ExprVal::Math(math) => {
+            collect_tera_expr(&math.lhs, locals, args);
+            collect_tera_expr(&math.rhs, locals, args);
+        }
+        ExprVal::Logic(logic) => {
+            collect_tera_expr(&logic.lhs, locals, args);
+            collect_tera_expr(&logic.rhs, locals, args);
+        }
+        ExprVal::In(within) => {
+            collect_tera_expr(&within.lhs, locals, args);
+            collect_tera_expr(&within.rhs, locals, args);
+        }
+        ExprVal::FunctionCall(call) => call
+            .args
+            .values()
+            .for_each(|e| collect_tera_expr(e, locals, args)),
+        ExprVal::MacroCall(call) => call
+            .args
+            .values()
+            .for_each(|e| collect_tera_expr(e, locals, args)),
+        ExprVal::Array(values) => values
+            .iter()
+            .for_each(|e| collect_tera_expr(e, locals, args)),
+        ExprVal::StringConcat(concat) => concat
+            .values
+            .iter()
+            .for_each(|v| collect_tera_value(v, locals, args)),
+        _ => {}
+    }
+}
+
+/// A literal part of content, or a placeholder with the text it was written as.
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Placeholder {
+        placeholder: String,
+        written: String,
+    },
+    /// A `$name` path, which may turn out to be a name followed by text.
+    DollarPath(String),
+}
+
+/// Add literal text, merging it with the text before it.
+fn push_text(parts: &mut Vec<Part>, text: &str) {
+    match parts.last_mut() {
+        Some(Part::Text(last)) => last.push_str(text),
+        _ if text.is_empty() => {}
+        _ => parts.push(Part::Text(text.to_string())),
+    }
+}
+
+fn push_placeholder(parts: &mut Vec<Part>, placeholder: &str, written: String) {
+    parts.push(Part::Placeholder {
+        placeholder: placeholder.to_string(),
+        written,
+    });
+}
+
+fn extract_parts(parts: &[Part]) -> Result<Placeholders> {
+    let mut args = HashMap::new();
+    for part in parts {
+        match part {
+            Part::Text(_) => {}
+            Part::Placeholder { placeholder, .. } => add_placeholder(&mut args, placeholder)?,
+            Part::DollarPath(name) => add_placeholder(&mut args, name)?,
         }
     }
     Ok(args)
 }
 
-fn format_brace(content: &str, variables: &HashMap<String, String>) -> String {
-    let mut result = String::with_capacity(content.len());
+fn format_parts(
+    parts: &[Part],
+    variables: &HashMap<String, String>,
+    unresolved: &mut Vec<String>,
+) -> String {
+    let mut result = String::new();
+    for part in parts {
+        match part {
+            Part::Text(text) => result.push_str(text),
+            // Unknown names are kept as written
+            Part::Placeholder {
+                placeholder,
+                written,
+            } => match placeholder_value(variables, placeholder) {
+                Some(value) => result.push_str(&value),
+                None => {
+                    result.push_str(written);
+                    unresolved.push(written.clone());
+                }
+            },
+            Part::DollarPath(name) => {
+                if let Some(value) = lookup_path(variables, name) {
+                    result.push_str(&value);
+                } else if let Some(value) = variables.get(path_root(name)) {
+                    // Not a field after all, e.g. `$user.Thanks` for a plain string
+                    result.push_str(value);
+                    result.push_str(&name[path_root(name).len()..]);
+                } else {
+                    let written = format!("${}", name);
+                    result.push_str(&written);
+                    unresolved.push(written);
+                }
+            }
+        }
+    }
+    result
+}
+
+#[derive(Debug)]
+struct PartsTemplate(Vec<Part>);
+
+impl Template for PartsTemplate {
+    fn render(
+        &self,
+        variables: &HashMap<String, String>,
+        unresolved: &mut Vec<String>,
+    ) -> Result<String> {
+        Ok(format_parts(&self.0, variables, unresolved))
+    }
+}
+
+/// Split `content` at placeholders between `open` and `close` whose name
+/// `is_name` accepts. `escape` followed by `open` is a literal `open`.
+fn split_delimited(
+    content: &str,
+    open: &str,
+    close: &str,
+    escape: &str,
+    is_name: fn(&str) -> bool,
+) -> Vec<Part> {
+    let escaped_open = format!("{}{}", escape, open);
+    let mut parts = Vec::new();
+    let mut rest = content;
+    loop {
+        let escaped = rest.find(&escaped_open);
+        let Some(start) = [escaped, rest.find(open)].into_iter().flatten().min() else {
+            break;
+        };
+        push_text(&mut parts, &rest[..start]);
+        rest = &rest[start..];
+        if Some(start) == escaped {
+            push_text(&mut parts, &rest[escape.len()..escaped_open.len()]);
+            rest = &rest[escaped_open.len()..];
+        } else if let Some(end) = rest[open.len()..]
+            .find(close)
+            .map(|end| open.len() + end)
+            .filter(|&end| is_name(&rest[open.len()..end]))
+        {
+            push_placeholder(
+                &mut parts,
+                &rest[open.len()..end],
+                rest[..end + close.len()].to_string(),
+            );
+            rest = &rest[end + close.len()..];
+        } else {
+            push_text(&mut parts, &rest[..open.len()]);
+            rest = &rest[open.len()..];
+        }
+    }
+    push_text(&mut parts, rest);
+    parts
+}
+
+/// Custom delimiters only enclose placeholders with a valid name, so code
+/// using the same characters is left alone.
+fn is_delimited_name(placeholder: &str) -> bool {
+    validate_variable_path(split_inline_default(placeholder).0)
+}
+
+/// Split brace format text at `{name}` placeholders, turning `{{` and `}}`
+/// into literal braces.
+fn parse_brace(content: &str) -> Vec<Part> {
+    let mut parts = Vec::new();
     let mut chars = content.chars().peekable();
 
     while let Some(c) = chars.next() {
         if c == '{' {
-            if chars.peek() == Some(&'{') {
-                chars.next();
-                result.push('{');
+            if chars.next_if_eq(&'{').is_some() {
+                push_text(&mut parts, "{");
                 continue;
             }
             let mut name = String::new();
             let mut found_close = false;
-            while let Some(c) = chars.next() {
+            for c in chars.by_ref() {
                 if c == '}' {
                     found_close = true;
                     break;
@@ -116,74 +1608,48 @@ fn format_brace(content: &str, variables: &HashMap<String, String>) -> String {
                 name.push(c);
             }
             if found_close {
-                if let Some(value) = variables.get(&name) {
-                    result.push_str(value);
-                } else {
-                    result.push('{');
-                    result.push_str(&name);
-                    result.push('}');
-                }
+                push_placeholder(&mut parts, &name, format!("{{{}}}", name));
             } else {
-                result.push('{');
-                result.push_str(&name);
+                push_text(&mut parts, "{");
+                push_text(&mut parts, &name);
             }
         } else if c == '}' {
-            if chars.peek() == Some(&'}') {
-                chars.next();
-                result.push('}');
-            } else {
-                result.push(c);
-            }
+            chars.next_if_eq(&'}');
+            push_text(&mut parts, "}");
         } else {
-            result.push(c);
+            push_text(&mut parts, c.encode_utf8(&mut [0; 4]));
         }
     }
-    result
+    parts
 }
 
-fn format_dollar(content: &str, variables: &HashMap<String, String>) -> String {
-    let mut result = String::with_capacity(content.len());
+/// Split dollar format content at `$name` and `${name}` placeholders,
+/// turning `$$` into a literal `$`.
+fn parse_dollar(content: &str) -> Vec<Part> {
+    let mut parts = Vec::new();
     let mut chars = content.chars().peekable();
 
     while let Some(c) = chars.next() {
         if c == '$' {
-            if chars.peek() == Some(&'$') {
-                chars.next();
-                result.push('$');
+            if chars.next_if_eq(&'$').is_some() {
+                push_text(&mut parts, "$");
                 continue;
             }
-            let mut name = String::new();
-            while let Some(&c) = chars.peek() {
-                if c.is_ascii_alphanumeric() || c == '_' {
-                    name.push(c);
-                    chars.next();
-                } else {
-                    break;
-                }
+            if let Some(placeholder) = read_dollar_braces(&mut chars) {
+                push_placeholder(&mut parts, &placeholder, format!("${{{}}}", placeholder));
+                continue;
             }
-            if !name.is_empty() {
-                if let Some(value) = variables.get(&name) {
-                    result.push_str(value);
-                } else {
-                    result.push('$');
-                    result.push_str(&name);
-                }
+            let name = read_dollar_path(&mut chars);
+            if name.is_empty() {
+                push_text(&mut parts, "$");
             } else {
-                result.push('$');
+                parts.push(Part::DollarPath(name));
             }
         } else {
-            result.push(c);
+            push_text(&mut parts, c.encode_utf8(&mut [0; 4]));
         }
     }
-    result
-}
-
-pub fn get_formatter(format_type: &str) -> Result<Formatter> {
-    match format_type {
-        "brace" => Ok(Formatter::Brace),
-        "dollar" => Ok(Formatter::Dollar),
-        _ => anyhow::bail!("Unknown formatter: {}", format_type),
-    }
+    parts
 }
 
 #[cfg(test)]
@@ -196,6 +1662,8 @@ mod tests {
         assert!(validate_variable_name("_private"));
         assert!(validate_variable_name("var123"));
         assert!(validate_variable_name("CamelCase"));
+        assert!(validate_variable_name("名前"));
+        assert!(validate_variable_name("_ñame2"));
     }
 
     #[test]
@@ -205,11 +1673,13 @@ mod tests {
         assert!(!validate_variable_name("var-name"));
         assert!(!validate_variable_name("var name"));
         assert!(!validate_variable_name("var.name"));
+        assert!(!validate_variable_name("名前。"));
+        assert!(!validate_variable_name("٣x"));
     }
 
     #[test]
     fn test_brace_formatter_extract_arguments() {
-        let formatter = Formatter::Brace;
+        let formatter = BraceFormatter;
         let args = formatter
             .extract_arguments("Hello {user} from {project}")
             .unwrap();
@@ -218,9 +1688,487 @@ mod tests {
         assert!(args.contains("project"));
     }
 
+    #[test]
+    fn test_validate_variable_path() {
+        assert!(validate_variable_path("user"));
+        assert!(validate_variable_path("user.name"));
+        assert!(validate_variable_path("items.0.title"));
+        assert!(!validate_variable_path("0.name"));
+        assert!(!validate_variable_path("user..name"));
+        assert!(!validate_variable_path("user.name-x"));
+    }
+
+    #[test]
+    fn test_nested_variables() {
+        let vars = HashMap::from([
+            (
+                "user".to_string(),
+                r#"{"name": "Alice", "roles": ["admin", "dev"], "age": 30}"#.to_string(),
+            ),
+            ("plain".to_string(), "text".to_string()),
+        ]);
+        let formatter = BraceFormatter;
+        let args = formatter
+            .extract_arguments("{user.name} {user.roles.1} {plain}")
+            .unwrap();
+        assert_eq!(
+            args,
+            HashSet::from(["user".to_string(), "plain".to_string()])
+        );
+        assert_eq!(
+            formatter
+                .format(
+                    "{user.name} is {user.age}, {user.roles.1}; {user.email} {plain.x}",
+                    &vars
+                )
+                .unwrap(),
+            "Alice is 30, dev; {user.email} {plain.x}"
+        );
+
+        let formatter = DollarFormatter;
+        let args = formatter
+            .extract_arguments("$user.name wrote $plain.")
+            .unwrap();
+        assert_eq!(
+            args,
+            HashSet::from(["user".to_string(), "plain".to_string()])
+        );
+        assert_eq!(
+            formatter
+                .format("$user.roles wrote $plain. $plain.More", &vars)
+                .unwrap(),
+            r#"["admin","dev"] wrote text. text.More"#
+        );
+    }
+
+    #[test]
+    fn test_inline_defaults() {
+        let vars = HashMap::from([
+            ("branch".to_string(), String::new()),
+            ("style".to_string(), "terse".to_string()),
+        ]);
+        let cases: [(Box<dyn Formatter>, _, _); 4] = [
+            (
+                Box::new(BraceFormatter),
+                "On {branch:-main}, {style:-concise} {user:-you} {{x:-y}}",
+                "On main, terse you {x:-y}",
+            ),
+            (
+                Box::new(DollarFormatter),
+                "On ${branch:-main}, $style ${user:-you} ${not valid}",
+                "On main, terse you ${not valid}",
+            ),
+            (
+                Box::new(DollarBraceFormatter),
+                "On ${branch:-main}, ${style:-concise} ${user:-you}",
+                "On main, terse you",
+            ),
+            (
+                Box::new(DelimitedFormatter::new("<<", ">>").unwrap()),
+                "On <<branch:-main>>, <<style:-concise>> <<user:-you>> << x:-y >>",
+                "On main, terse you << x:-y >>",
+            ),
+        ];
+        for (formatter, content, expected) in cases {
+            assert_eq!(formatter.format(content, &vars).unwrap(), expected);
+            let defaults = formatter.inline_defaults(content).unwrap();
+            assert_eq!(defaults["branch"], "main");
+            assert_eq!(defaults["user"], "you");
+            assert!(formatter
+                .extract_arguments(content)
+                .unwrap()
+                .contains("style"));
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let vars = HashMap::from([
+            ("name".to_string(), "alice".to_string()),
+            ("path".to_string(), "src/lib/mod.rs".to_string()),
+            ("text".to_string(), "  a \"long\" text  ".to_string()),
+        ]);
+        let formatter = BraceFormatter;
+        let content = "{name|capitalize} {name|upper} {path|basename} {path|dirname} {text|trim|truncate:6} {text|trim|json} {lang:-rust|upper}";
+        assert_eq!(
+            formatter.format(content, &vars).unwrap(),
+            r#"Alice ALICE mod.rs src/lib a "lon "a \"long\" text" RUST"#
+        );
+        assert_eq!(
+            formatter.extract_arguments(content).unwrap(),
+            ["name", "path", "text", "lang"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+        assert_eq!(formatter.inline_defaults(content).unwrap()["lang"], "rust");
+
+        let result = formatter.extract_arguments("{name|shout}");
+        assert_eq!(result.unwrap_err().to_string(), "Unknown filter: shout");
+        let result = formatter.extract_arguments("{name|truncate:x}");
+        assert!(result.is_err());
+        assert_eq!(
+            DollarBraceFormatter.format("${name|upper}", &vars).unwrap(),
+            "ALICE"
+        );
+    }
+
+    #[test]
+    fn test_conditional_blocks() {
+        let content = "Review {file}.\n{?context}\nAdditional context: {context}\n{/context}\nBe brief.{?lang} Use {lang}.{/lang}";
+        let args = BraceFormatter.extract_placeholders(content).unwrap();
+        assert_eq!(args["file"], None);
+        assert_eq!(args["context"], Some(String::new()));
+        assert_eq!(args["lang"], Some(String::new()));
+
+        let vars = HashMap::from([
+            ("file".to_string(), "main.rs".to_string()),
+            ("context".to_string(), "hotfix".to_string()),
+            ("lang".to_string(), String::new()),
+        ]);
+        assert_eq!(
+            BraceFormatter.format(content, &vars).unwrap(),
+            "Review main.rs.\nAdditional context: hotfix\nBe brief."
+        );
+        let vars = HashMap::from([
+            ("file".to_string(), "main.rs".to_string()),
+            ("lang".to_string(), "Rust".to_string()),
+        ]);
+        assert_eq!(
+            BraceFormatter.format(content, &vars).unwrap(),
+            "Review main.rs.\nBe brief. Use Rust."
+        );
+
+        let nested = "{?a}A{?b}B{/b}{/a}|{{?a}}";
+        let vars = HashMap::from([("b".to_string(), "x".to_string())]);
+        assert_eq!(BraceFormatter.format(nested, &vars).unwrap(), "|{?a}");
+
+        let error = |content| {
+            BraceFormatter
+                .extract_arguments(content)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error("{?a}text"), "Unclosed block {?a}");
+        assert_eq!(error("{?a}{?b}{/a}{/b}"), "Unexpected {/a}");
+        assert_eq!(error("{?a b}{/a b}"), "Invalid variable name: a b");
+    }
+
+    #[test]
+    fn test_list_values() {
+        let vars = HashMap::from([
+            (
+                "files".to_string(),
+                r#"["src/a.rs","src/b.rs"]"#.to_string(),
+            ),
+            ("tags".to_string(), "solo".to_string()),
+            ("none".to_string(), "[]".to_string()),
+        ]);
+        let format = |content| BraceFormatter.format(content, &vars).unwrap();
+        assert_eq!(
+            format(r#"{files|join:", "} | {files|join:' + '} | {files|join} | {tags|join}"#),
+            "src/a.rs, src/b.rs | src/a.rs + src/b.rs | src/a.rs, src/b.rs | solo"
+        );
+        assert_eq!(
+            format("Review these files:\n{*files}\n- {files|basename} ({files})\n{/files}\nThanks"),
+            "Review these files:\n- a.rs (src/a.rs)\n- b.rs (src/b.rs)\nThanks"
+        );
+        assert_eq!(format("[{*none}x{/none}{*missing}y{/missing}]"), "[]");
+        assert_eq!(
+            format("{*files}{?tags}{tags} {/tags}{/files}"),
+            "solo solo "
+        );
+
+        let vars = HashMap::from([(
+            "changes".to_string(),
+            r#"[{"path": "a.rs", "lines": 3}, {"path": "b.rs", "lines": 5}]"#.to_string(),
+        )]);
+        assert_eq!(
+            BraceFormatter
+                .format(
+                    "{*changes}{changes.path}: {changes.lines}; {/changes}",
+                    &vars
+                )
+                .unwrap(),
+            "a.rs: 3; b.rs: 5; "
+        );
+
+        let args = BraceFormatter
+            .extract_placeholders("{*files}{files}{/files}")
+            .unwrap();
+        assert_eq!(args, HashMap::from([("files".to_string(), None)]));
+        let result = BraceFormatter.extract_arguments("{*user.files}{/user.files}");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid variable name: user.files"
+        );
+    }
+
+    #[test]
+    fn test_include_files() {
+        let content = "Rules:\n{file:./rules.md}\n{{file:x}} {file:-none} {file}";
+        let args = BraceFormatter.extract_placeholders(content).unwrap();
+        assert_eq!(
+            args,
+            HashMap::from([("file".to_string(), Some("none".to_string()))])
+        );
+
+        let mut paths = Vec::new();
+        let included = BraceFormatter
+            .include_files(content, &mut |path| {
+                paths.push(path.to_string());
+                Ok("Use {braces} as written".to_string())
+            })
+            .unwrap();
+        assert_eq!(paths, vec!["./rules.md"]);
+        let vars = HashMap::from([("file".to_string(), "a.rs".to_string())]);
+        assert_eq!(
+            BraceFormatter.format(&included, &vars).unwrap(),
+            "Rules:\nUse {braces} as written\n{file:x} a.rs a.rs"
+        );
+
+        let result = BraceFormatter.include_files("{file:a}", &mut |_| anyhow::bail!("missing"));
+        assert_eq!(result.unwrap_err().to_string(), "missing");
+        let unchanged = DollarFormatter
+            .include_files("{file:a} $x", &mut |_| unreachable!())
+            .unwrap();
+        assert_eq!(unchanged, "{file:a} $x");
+    }
+
+    #[test]
+    fn test_skip_code() {
+        let content = "Fix {bug} in `{file}`, not ``a ` {b}``.\n\n```rust\nlet s = format!(\"{}\", x);\n{?x}\n```\n~~~\n{y}\n~~~~\n{?more}More: {more}{/more} `open\n    ```\n{indented}";
+        let formatter = SkipCodeFormatter(Arc::new(BraceFormatter));
+        let mut args: Vec<_> = formatter
+            .extract_arguments(content)
+            .unwrap()
+            .into_iter()
+            .collect();
+        args.sort();
+        assert_eq!(args, vec!["bug", "indented", "more"]);
+        assert!(BraceFormatter.extract_arguments(content).is_err());
+
+        let vars = HashMap::from([
+            ("bug".to_string(), "the crash".to_string()),
+            ("more".to_string(), "`x`".to_string()),
+        ]);
+        assert_eq!(
+            formatter.format(content, &vars).unwrap(),
+            "Fix the crash in `{file}`, not ``a ` {b}``.\n\n```rust\nlet s = format!(\"{}\", x);\n{?x}\n```\n~~~\n{y}\n~~~~\nMore: `x` `open\n    ```\n{indented}"
+        );
+
+        let unclosed = "```\n{a}";
+        assert!(formatter.extract_arguments(unclosed).unwrap().is_empty());
+        assert_eq!(formatter.format(unclosed, &vars).unwrap(), unclosed);
+        let dollar = SkipCodeFormatter(Arc::new(DollarFormatter));
+        assert_eq!(
+            dollar.format("$bug `$bug`", &vars).unwrap(),
+            "the crash `$bug`"
+        );
+    }
+
+    #[cfg(feature = "templating-jinja")]
+    #[test]
+    fn test_raw_blocks() {
+        let content = "Hi {name}.\n<!-- shinkuro:raw -->\nWrite `{name}` for the {name} argument.\n<!-- shinkuro:endraw -->\nInline <!-- shinkuro:raw -->{x}<!-- shinkuro:endraw --> done.";
+        let vars = HashMap::from([("name".to_string(), "Alice".to_string())]);
+        let expected = "Hi Alice.\nWrite `{name}` for the {name} argument.\nInline {x} done.";
+        assert_eq!(BraceFormatter.format(content, &vars).unwrap(), expected);
+        assert_eq!(
+            BraceFormatter.format_strict(content, &vars).unwrap(),
+            expected
+        );
+        let args = BraceFormatter.extract_arguments(content).unwrap();
+        assert_eq!(args, HashSet::from(["name".to_string()]));
+
+        let jinja = get_formatter("jinja").unwrap();
+        let content = "{{ a }} <!-- shinkuro:raw -->{{ b }} {% if %}";
+        assert_eq!(
+            jinja.extract_arguments(content).unwrap(),
+            HashSet::from(["a".to_string()])
+        );
+        let vars = HashMap::from([("a".to_string(), "A".to_string())]);
+        assert_eq!(jinja.format(content, &vars).unwrap(), "A {{ b }} {% if %}");
+
+        let content = "<!-- shinkuro:raw -->{file:a}<!-- shinkuro:endraw -->{file:b}";
+        let included = BraceFormatter
+            .include_files(content, &mut |path| Ok(path.to_uppercase()))
+            .unwrap();
+        assert_eq!(
+            included,
+            "<!-- shinkuro:raw -->{file:a}<!-- shinkuro:endraw -->B"
+        );
+        assert_eq!(
+            BraceFormatter.format(&included, &HashMap::new()).unwrap(),
+            "{file:a}B"
+        );
+    }
+
+    #[cfg(feature = "templating-jinja")]
+    #[test]
+    fn test_prepare() {
+        for (name, content, expected) in [
+            (
+                "brace",
+                "{{{greeting}}}, {name}!",
+                "{Hi}, Alice! / {Hey}, Bob!",
+            ),
+            (
+                "dollar",
+                "$$$greeting, ${name}!",
+                "$Hi, Alice! / $Hey, Bob!",
+            ),
+            (
+                "dollar-brace",
+                "$${x} ${greeting}, ${name}!",
+                "${x} Hi, Alice! / ${x} Hey, Bob!",
+            ),
+            (
+                "jinja",
+                "{{ greeting }}, {{ name }}!",
+                "Hi, Alice! / Hey, Bob!",
+            ),
+            (
+                "handlebars",
+                "{{greeting}}, {{name}}!",
+                "Hi, Alice! / Hey, Bob!",
+            ),
+            (
+                "tera",
+                "{{ greeting }}, {{ name }}!",
+                "Hi, Alice! / Hey, Bob!",
+            ),
+            (
+                "mustache",
+                "{{greeting}}, {{name}}!",
+                "Hi, Alice! / Hey, Bob!",
+            ),
+        ] {
+            let prepared = get_formatter(name).unwrap().prepare(content).unwrap();
+            let first = HashMap::from([
+                ("greeting".to_string(), "Hi".to_string()),
+                ("name".to_string(), "Alice".to_string()),
+            ]);
+            let second = HashMap::from([
+                ("greeting".to_string(), "Hey".to_string()),
+                ("name".to_string(), "Bob".to_string()),
+            ]);
+            let rendered = format!(
+                "{} / {}",
+                prepared.format(&first).unwrap(),
+                prepared.format(&second).unwrap()
+            );
+            assert_eq!(rendered, expected, "{}", name);
+        }
+
+        let prepared = SkipCodeFormatter(Arc::new(BraceFormatter))
+            .prepare("`{name}` {name} <!-- shinkuro:raw -->{name}<!-- shinkuro:endraw -->")
+            .unwrap();
+        let variables = HashMap::from([("name".to_string(), "Alice".to_string())]);
+        assert_eq!(
+            prepared.format(&variables).unwrap(),
+            "`{name}` Alice {name}"
+        );
+        assert!(BraceFormatter.prepare("{?a}unclosed").is_err());
+    }
+
+    #[cfg(feature = "templating-jinja")]
+    #[test]
+    fn test_placeholder() {
+        let registry = FormatterRegistry::default();
+        let mut formatters: Vec<Arc<dyn Formatter>> = [
+            "brace",
+            "dollar",
+            "dollar-brace",
+            "jinja",
+            "handlebars",
+            "tera",
+            "mustache",
+        ]
+        .iter()
+        .map(|name| registry.get(name).unwrap())
+        .collect();
+        formatters.push(Arc::new(DelimitedFormatter::new("<<", ">>").unwrap()));
+        let variables = HashMap::from([("name".to_string(), "Alice".to_string())]);
+        for formatter in formatters {
+            let content = format!("Hi {}!", formatter.placeholder("name"));
+            assert_eq!(
+                formatter.extract_arguments(&content).unwrap(),
+                HashSet::from(["name".to_string()]),
+                "{:?}",
+                formatter
+            );
+            assert_eq!(formatter.format(&content, &variables).unwrap(), "Hi Alice!");
+        }
+    }
+
+    #[cfg(feature = "templating-jinja")]
+    #[test]
+    fn test_escape() {
+        let registry = FormatterRegistry::default();
+        let mut formatters: Vec<Arc<dyn Formatter>> = [
+            "brace",
+            "dollar",
+            "dollar-brace",
+            "jinja",
+            "handlebars",
+            "tera",
+            "mustache",
+        ]
+        .iter()
+        .map(|name| registry.get(name).unwrap())
+        .collect();
+        formatters.push(Arc::new(DelimitedFormatter::new("<<", ">>").unwrap()));
+        formatters.push(Arc::new(SkipCodeFormatter(Arc::new(BraceFormatter))));
+        let text = "Use {x}, {{y}}, $z, ${w}, <<v>>, {% if a %} and `{u}`";
+        for formatter in formatters {
+            let escaped = formatter.escape(text);
+            assert!(
+                formatter.extract_arguments(&escaped).unwrap().is_empty(),
+                "{:?}",
+                formatter
+            );
+            assert_eq!(
+                formatter.format(&escaped, &HashMap::new()).unwrap(),
+                text,
+                "{:?}",
+                formatter
+            );
+        }
+        assert_eq!(BraceFormatter.escape("{a}"), "{{a}}");
+        assert_eq!(registry.get("jinja").unwrap().escape("plain"), "plain");
+    }
+
+    #[test]
+    fn test_format_strict() {
+        let vars = HashMap::from([
+            ("user".to_string(), r#"{"name": "Alice"}"#.to_string()),
+            ("plain".to_string(), "text".to_string()),
+        ]);
+        let content = "{user.name} {user.email} {plain.x} {user.email} {plain}";
+        assert_eq!(
+            BraceFormatter.format(content, &vars).unwrap(),
+            "Alice {user.email} {plain.x} {user.email} text"
+        );
+        let result = BraceFormatter.format_strict(content, &vars);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Unresolved placeholders: {user.email}, {plain.x}, {user.email}"
+        );
+        let result = DollarFormatter.format_strict("$user.name $missing", &vars);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Unresolved placeholders: $missing"
+        );
+        assert_eq!(
+            BraceFormatter.format_strict("{user.name}", &vars).unwrap(),
+            "Alice"
+        );
+    }
+
     #[test]
     fn test_brace_formatter_extract_arguments_invalid() {
-        let formatter = Formatter::Brace;
+        let formatter = BraceFormatter;
         let result = formatter.extract_arguments("Hello {123}");
         assert!(result.is_err());
         assert!(result
@@ -231,24 +2179,26 @@ mod tests {
 
     #[test]
     fn test_brace_formatter_format() {
-        let formatter = Formatter::Brace;
+        let formatter = BraceFormatter;
         let mut vars = HashMap::new();
         vars.insert("user".to_string(), "Alice".to_string());
-        let result = formatter.format("Hello {user}!", &vars);
+        let result = formatter.format("Hello {user}!", &vars).unwrap();
         assert_eq!(result, "Hello Alice!");
     }
 
     #[test]
     fn test_brace_formatter_escape() {
-        let formatter = Formatter::Brace;
+        let formatter = BraceFormatter;
         let vars = HashMap::new();
-        let result = formatter.format("Use {{var}} for variables", &vars);
+        let result = formatter
+            .format("Use {{var}} for variables", &vars)
+            .unwrap();
         assert_eq!(result, "Use {var} for variables");
     }
 
     #[test]
     fn test_dollar_formatter_extract_arguments() {
-        let formatter = Formatter::Dollar;
+        let formatter = DollarFormatter;
         let args = formatter
             .extract_arguments("Hello $user from $project")
             .unwrap();
@@ -259,32 +2209,218 @@ mod tests {
 
     #[test]
     fn test_dollar_formatter_format() {
-        let formatter = Formatter::Dollar;
+        let formatter = DollarFormatter;
         let mut vars = HashMap::new();
         vars.insert("user".to_string(), "Alice".to_string());
-        let result = formatter.format("Hello $user!", &vars);
+        let result = formatter.format("Hello $user!", &vars).unwrap();
         assert_eq!(result, "Hello Alice!");
     }
 
+    #[test]
+    fn test_unicode_variable_names() {
+        let vars = HashMap::from([
+            ("名前".to_string(), "アリス".to_string()),
+            ("usuario".to_string(), r#"{"año": 2026}"#.to_string()),
+        ]);
+        let brace = "こんにちは、{名前}さん。{usuario.año}";
+        assert_eq!(
+            BraceFormatter.extract_arguments(brace).unwrap(),
+            HashSet::from(["名前".to_string(), "usuario".to_string()])
+        );
+        assert_eq!(
+            BraceFormatter.format(brace, &vars).unwrap(),
+            "こんにちは、アリスさん。2026"
+        );
+        assert_eq!(
+            DollarFormatter
+                .format("$名前。$usuario.año!", &vars)
+                .unwrap(),
+            "アリス。2026!"
+        );
+    }
+
     #[test]
     fn test_dollar_formatter_safe_substitute() {
-        let formatter = Formatter::Dollar;
+        let formatter = DollarFormatter;
         let mut vars = HashMap::new();
         vars.insert("user".to_string(), "Alice".to_string());
-        let result = formatter.format("Hello $user $missing", &vars);
+        let result = formatter.format("Hello $user $missing", &vars).unwrap();
         assert_eq!(result, "Hello Alice $missing");
     }
 
+    fn get_formatter(name: &str) -> Result<Arc<dyn Formatter>> {
+        FormatterRegistry::default().get(name)
+    }
+
     #[test]
     fn test_get_formatter_brace() {
         let formatter = get_formatter("brace").unwrap();
-        assert!(matches!(formatter, Formatter::Brace));
+        assert_eq!(formatter.format("{x}", &HashMap::new()).unwrap(), "{x}");
     }
 
     #[test]
     fn test_get_formatter_dollar() {
         let formatter = get_formatter("dollar").unwrap();
-        assert!(matches!(formatter, Formatter::Dollar));
+        assert_eq!(formatter.format("{x}", &HashMap::new()).unwrap(), "{x}");
+        assert!(formatter.extract_arguments("$x").unwrap().contains("x"));
+    }
+
+    #[test]
+    fn test_dollar_brace_formatter() {
+        let formatter = get_formatter("dollar-brace").unwrap();
+        let content = "Run ${cmd} in $HOME for ${user}, not $${literal} ${unknown} $${ ${";
+        let args = formatter.extract_arguments(content).unwrap();
+        assert_eq!(
+            args,
+            ["cmd", "user", "unknown"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+        let vars = HashMap::from([
+            ("cmd".to_string(), "ls".to_string()),
+            ("user".to_string(), "Alice".to_string()),
+        ]);
+        assert_eq!(
+            formatter.format(content, &vars).unwrap(),
+            "Run ls in $HOME for Alice, not ${literal} ${unknown} ${ ${"
+        );
+        assert!(formatter.extract_arguments("${my-var}").is_err());
+    }
+
+    #[test]
+    fn test_delimited_formatter() {
+        let formatter = DelimitedFormatter::new("<<", ">>").unwrap();
+        let content = "fn f() { <<body>> } cat << EOF >> out <<user>>; \\<<body>>";
+        let args = formatter.extract_arguments(content).unwrap();
+        assert_eq!(
+            args,
+            ["body", "user"].into_iter().map(String::from).collect()
+        );
+        let vars = HashMap::from([
+            ("body".to_string(), "x".to_string()),
+            ("user".to_string(), "Alice".to_string()),
+        ]);
+        assert_eq!(
+            formatter.format(content, &vars).unwrap(),
+            "fn f() { x } cat << EOF >> out Alice; <<body>>"
+        );
+        assert!(DelimitedFormatter::new("", ">>").is_err());
+    }
+
+    #[cfg(not(feature = "templating-jinja"))]
+    #[test]
+    fn test_jinja_disabled() {
+        let error = FormatterRegistry::default().get("jinja").unwrap_err();
+        assert!(error.to_string().contains("`templating-jinja` feature"));
+    }
+
+    #[cfg(feature = "templating-jinja")]
+    #[test]
+    fn test_jinja_formatter_extract_arguments() {
+        let formatter = get_formatter("jinja").unwrap();
+        let args = formatter
+            .extract_arguments(
+                "{% if lang == \"rust\" %}Use clippy.{% endif %}\n{% for i in range(3) %}{{ i }}{{ task | upper }}{% endfor %}",
+            )
+            .unwrap();
+        assert_eq!(
+            args,
+            HashSet::from(["lang".to_string(), "task".to_string()])
+        );
+
+        let result = formatter.extract_arguments("{% if %}");
+        assert!(result.unwrap_err().to_string().contains("Invalid template"));
+    }
+
+    #[cfg(feature = "templating-jinja")]
+    #[test]
+    fn test_jinja_formatter_format() {
+        let formatter = get_formatter("jinja").unwrap();
+        let template = "{% if lang == \"rust\" %}Run clippy on <{{ file }}>{% else %}Lint {{ file | upper }}{% endif %}";
+        let mut vars = HashMap::from([
+            ("lang".to_string(), "rust".to_string()),
+            ("file".to_string(), "main.rs".to_string()),
+        ]);
+        assert_eq!(
+            formatter.format(template, &vars).unwrap(),
+            "Run clippy on <main.rs>"
+        );
+        vars.insert("lang".to_string(), "go".to_string());
+        assert_eq!(formatter.format(template, &vars).unwrap(), "Lint MAIN.RS");
+    }
+
+    #[test]
+    fn test_handlebars_formatter() {
+        let partials = BTreeMap::from([(
+            "partials/greeting".to_string(),
+            "Hello {{user}}!".to_string(),
+        )]);
+        let formatter = get_formatter("handlebars")
+            .unwrap()
+            .with_partials(&partials)
+            .unwrap()
+            .unwrap();
+        let template = "{{> partials/greeting}} {{#if urgent}}Now: {{/if}}{{#each items}}{{name}}{{/each}}{{upper task}} <{{file}}>";
+        let args = formatter.extract_arguments(template).unwrap();
+        assert_eq!(
+            args,
+            ["user", "urgent", "items", "task", "file"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+
+        let vars = HashMap::from([
+            ("user".to_string(), "Alice".to_string()),
+            ("urgent".to_string(), "yes".to_string()),
+            ("file".to_string(), "a&b.rs".to_string()),
+        ]);
+        assert_eq!(
+            formatter
+                .format(
+                    "{{> partials/greeting}} {{#if urgent}}Now: {{/if}}<{{file}}>",
+                    &vars
+                )
+                .unwrap(),
+            "Hello Alice! Now: <a&b.rs>"
+        );
+    }
+
+    #[test]
+    fn test_tera_formatter() {
+        let formatter = get_formatter("tera").unwrap();
+        let template = "{% set n = count | int %}{% if lang == \"rust\" %}Run clippy on <{{ file }}>{% endif %}{% for item in items %}{{ loop.index }}{{ item.name }}{% endfor %}{{ n }}";
+        let args = formatter.extract_arguments(template).unwrap();
+        assert_eq!(
+            args,
+            ["count", "lang", "file", "items"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+
+        let vars = HashMap::from([
+            ("lang".to_string(), "rust".to_string()),
+            ("file".to_string(), "a&b.rs".to_string()),
+        ]);
+        assert_eq!(
+            formatter
+                .format(
+                    "{% if lang == \"rust\" %}Run clippy on <{{ file }}>{% endif %}",
+                    &vars
+                )
+                .unwrap(),
+            "Run clippy on <a&b.rs>"
+        );
+
+        let result = formatter.format("{{ get_env(name=\"HOME\") }}", &vars);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("get_env is disabled"));
+        let result = formatter.format("{% include \"secrets.txt\" %}", &vars);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -296,4 +2432,40 @@ mod tests {
             .to_string()
             .contains("Unknown formatter"));
     }
+
+    #[cfg(feature = "templating-jinja")]
+    #[test]
+    fn test_register_formatter() {
+        #[derive(Debug)]
+        struct UpperFormatter;
+        impl Formatter for UpperFormatter {
+            fn extract_placeholders(&self, _content: &str) -> Result<Placeholders> {
+                Ok(Placeholders::new())
+            }
+            fn compile(&self, content: &str) -> Result<Box<dyn Template>> {
+                Ok(Box::new(UpperTemplate(content.to_uppercase())))
+            }
+        }
+        #[derive(Debug)]
+        struct UpperTemplate(String);
+        impl Template for UpperTemplate {
+            fn render(
+                &self,
+                _variables: &HashMap<String, String>,
+                _unresolved: &mut Vec<String>,
+            ) -> Result<String> {
+                Ok(self.0.clone())
+            }
+        }
+
+        let mut registry = FormatterRegistry::default();
+        registry.register("upper", || Arc::new(UpperFormatter));
+        let formatter = registry.get("upper").unwrap();
+        assert_eq!(formatter.format("hi", &HashMap::new()).unwrap(), "HI");
+        let error = FormatterRegistry::default().get("upper").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown formatter: upper, expected one of: brace, dollar, dollar-brace, handlebars, jinja, mustache, tera"
+        );
+    }
 }
@@ -0,0 +1,652 @@
+use crate::model::{Argument, ArgumentType, Fallback};
+use serde::de::{DeserializeOwned, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Prompt metadata as written in one place: YAML frontmatter, a fenced JSON
+/// block or a sidecar file. The text is kept to locate problems by line.
+pub struct Metadata {
+    mapping: Mapping,
+    file: PathBuf,
+    text: String,
+    /// Line of `file` the text starts on, 1-based.
+    first_line: usize,
+}
+
+impl Metadata {
+    pub fn yaml(file: &Path, text: &str, first_line: usize) -> Result<Self, Diagnostic> {
+        let value = serde_yaml::from_str::<Value>(text).map_err(|e| Diagnostic {
+            file: file.to_path_buf(),
+            line: e.location().map(|l| l.line() + first_line - 1),
+            field: String::new(),
+            message: format!("invalid YAML: {}", e),
+            error: false,
+        })?;
+        Self::new(file, text, first_line, value)
+    }
+
+    pub fn json(file: &Path, text: &str, first_line: usize) -> Result<Self, Diagnostic> {
+        let value = serde_json::from_str::<Value>(text).map_err(|e| Diagnostic {
+            file: file.to_path_buf(),
+            line: Some(e.line() + first_line - 1),
+            field: String::new(),
+            message: format!("invalid JSON: {}", e),
+            error: false,
+        })?;
+        Self::new(file, text, first_line, value)
+    }
+
+    fn new(file: &Path, text: &str, first_line: usize, value: Value) -> Result<Self, Diagnostic> {
+        let mapping = match value {
+            Value::Mapping(mapping) => mapping,
+            Value::Null => Mapping::new(),
+            _ => {
+                return Err(Diagnostic {
+                    file: file.to_path_buf(),
+                    line: Some(first_line),
+                    field: String::new(),
+                    message: "metadata is not a mapping, ignoring".to_string(),
+                    error: false,
+                })
+            }
+        };
+        Ok(Self {
+            mapping,
+            file: file.to_path_buf(),
+            text: text.to_string(),
+            first_line,
+        })
+    }
+
+    /// Best-effort line of `path`: each key is searched for from the line of
+    /// its parent, and list items are counted by their `-` markers. Stops at
+    /// the deepest part found.
+    fn locate(&self, path: &[Field]) -> usize {
+        let lines: Vec<&str> = self.text.lines().collect();
+        let indent = |line: &str| line.len() - line.trim_start().len();
+        let is_item = |line: &str| line.trim_start().starts_with('-');
+        let mut found = None;
+        for field in path {
+            let start = found.unwrap_or(0);
+            let next = match field {
+                Field::Key(key) => (start..lines.len()).find(|&i| is_key(lines[i], key)),
+                Field::Index(index) => (start + 1..lines.len())
+                    .find(|&i| is_item(lines[i]))
+                    .and_then(|first| {
+                        (first..lines.len())
+                            .filter(|&i| {
+                                is_item(lines[i]) && indent(lines[i]) == indent(lines[first])
+                            })
+                            .nth(*index)
+                    }),
+            };
+            match next {
+                Some(line) => found = Some(line),
+                None => break,
+            }
+        }
+        found.unwrap_or(0) + self.first_line
+    }
+}
+
+fn is_key(line: &str, key: &str) -> bool {
+    let line = line.trim_start();
+    let line = line.strip_prefix('-').unwrap_or(line).trim_start();
+    [
+        format!("{}:", key),
+        format!("\"{}\"", key),
+        format!("'{}'", key),
+    ]
+    .iter()
+    .any(|prefix| line.starts_with(prefix.as_str()))
+}
+
+/// One step of the path to a field, e.g. `arguments`, `[0]`, `type`.
+#[derive(Clone, Debug, PartialEq)]
+enum Field {
+    Key(String),
+    Index(usize),
+}
+
+fn path_string(path: &[Field]) -> String {
+    let mut s = String::new();
+    for field in path {
+        match field {
+            Field::Key(key) if s.is_empty() => s.push_str(key),
+            Field::Key(key) => s.push_str(&format!(".{}", key)),
+            Field::Index(index) => s.push_str(&format!("[{}]", index)),
+        }
+    }
+    s
+}
+
+/// A problem with a metadata field. Errors fail the prompt; anything else is
+/// reported as a warning and the field is ignored.
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    /// Path of the field, e.g. `arguments[0].type`; empty for the whole metadata.
+    pub field: String,
+    pub message: String,
+    #[serde(rename = "severity", serialize_with = "serialize_severity")]
+    pub error: bool,
+}
+
+fn serialize_severity<S: Serializer>(error: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(if *error { "error" } else { "warning" })
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if !self.field.is_empty() {
+            write!(f, ": '{}'", self.field)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// A string, or a number or boolean written without quotes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scalar(pub String);
+
+impl<'de> Deserialize<'de> for Scalar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ScalarVisitor;
+
+        impl Visitor<'_> for ScalarVisitor {
+            type Value = Scalar;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string, number or boolean")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Scalar, E> {
+                Ok(Scalar(v.to_string()))
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, v: bool) -> Result<Scalar, E> {
+                Ok(Scalar(v.to_string()))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Scalar, E> {
+                Ok(Scalar(v.to_string()))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Scalar, E> {
+                Ok(Scalar(v.to_string()))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Scalar, E> {
+                Ok(Scalar(v.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(ScalarVisitor)
+    }
+}
+
+/// Typed frontmatter fields. Arguments are deserialized one at a time so a
+/// broken argument doesn't affect the others.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Frontmatter {
+    pub name: Option<Scalar>,
+    pub title: Option<Scalar>,
+    pub description: Option<Scalar>,
+    pub post_render_cmd: Option<String>,
+    pub tags: Vec<Scalar>,
+    pub aliases: Vec<Scalar>,
+    pub category: Option<Scalar>,
+    pub enabled: Option<bool>,
+    pub draft: Option<bool>,
+    pub variable_format: Option<String>,
+    pub version: Option<Scalar>,
+    pub min_shinkuro_version: Option<Scalar>,
+    pub author: Option<Scalar>,
+    pub maintainer: Option<Scalar>,
+    pub one_of: Vec<Vec<Scalar>>,
+    pub strict_render: Option<bool>,
+    pub max_render_bytes: Option<u64>,
+    pub max_render_tokens: Option<usize>,
+    #[serde(rename = "arguments")]
+    argument_values: Option<Vec<Value>>,
+    #[serde(skip)]
+    pub arguments: Vec<Argument>,
+}
+
+#[derive(Deserialize)]
+struct ArgumentSpec {
+    name: Scalar,
+    description: Option<Scalar>,
+    default: Option<Scalar>,
+    #[serde(default)]
+    fallback: Vec<Fallback>,
+    #[serde(default, rename = "enum")]
+    enum_values: Vec<Scalar>,
+    #[serde(default, rename = "type")]
+    arg_type: ArgumentType,
+    pattern: Option<String>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    required: Option<bool>,
+}
+
+impl Frontmatter {
+    /// Deserialize and validate metadata from `sources`, later ones overriding
+    /// earlier ones key by key. Every field that doesn't fit is reported and
+    /// dropped instead of failing the whole prompt.
+    ///
+    /// `env:` fallbacks are only kept for variables matched by `allow_env`,
+    /// see [`env_fallback_allowed`].
+    pub fn parse(sources: &[Metadata], allow_env: &[String]) -> (Self, Vec<Diagnostic>) {
+        let mut merged = Mapping::new();
+        for source in sources {
+            merged.extend(source.mapping.clone());
+        }
+        let mut problems = Vec::new();
+        let mut report = |path: Vec<Field>, message: String, error: bool| {
+            // The field was written in the last source that has its top-level key
+            let key = match path.first() {
+                Some(Field::Key(key)) => Some(key.as_str()),
+                _ => None,
+            };
+            let source = sources
+                .iter()
+                .rev()
+                .find(|s| key.is_some_and(|k| s.mapping.contains_key(k)))
+                .or(sources.last());
+            if let Some(source) = source {
+                problems.push(Diagnostic {
+                    file: source.file.clone(),
+                    line: Some(source.locate(&path)),
+                    field: path_string(&path),
+                    message,
+                    error,
+                });
+            }
+        };
+
+        let mut frontmatter: Frontmatter =
+            lenient(Value::Mapping(merged), &[], &mut report).unwrap_or_default();
+        if let Some(required) = &frontmatter.min_shinkuro_version {
+            if parse_version(&required.0).is_none() {
+                report(
+                    vec![Field::Key("min_shinkuro_version".to_string())],
+                    format!("'{}' is not a version like 1.2.3, ignoring", required.0),
+                    false,
+                );
+                frontmatter.min_shinkuro_version = None;
+            }
+        }
+        for (i, value) in frontmatter
+            .argument_values
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+        {
+            let path = vec![Field::Key("arguments".to_string()), Field::Index(i)];
+            if let Some(spec) = lenient::<ArgumentSpec>(value, &path, &mut report) {
+                if let Some(argument) = validate_argument(spec, &path, allow_env, &mut report) {
+                    frontmatter.arguments.push(argument);
+                }
+            }
+        }
+        let groups = std::mem::take(&mut frontmatter.one_of);
+        for (i, group) in groups.into_iter().enumerate() {
+            let path = vec![Field::Key("one_of".to_string()), Field::Index(i)];
+            let unknown: Vec<_> = group
+                .iter()
+                .filter(|name| !frontmatter.arguments.iter().any(|a| a.name == name.0))
+                .map(|name| name.0.as_str())
+                .collect();
+            if !unknown.is_empty() {
+                report(
+                    path,
+                    format!("unknown arguments {}, ignoring", unknown.join(", ")),
+                    false,
+                );
+            } else if group.len() < 2 {
+                report(
+                    path,
+                    "needs at least two arguments, ignoring".to_string(),
+                    false,
+                );
+            } else {
+                frontmatter.one_of.push(group);
+            }
+        }
+        (frontmatter, problems)
+    }
+
+    pub fn is_draft(&self) -> bool {
+        self.enabled == Some(false) || self.draft == Some(true)
+    }
+}
+
+/// `major.minor.patch` as numbers, with missing parts as 0 and any
+/// pre-release suffix ignored.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    parts.next().is_none().then_some((major, minor, patch))
+}
+
+/// Whether this server satisfies a prompt's `min_shinkuro_version`.
+pub fn is_supported(min_version: &str) -> bool {
+    match (
+        parse_version(env!("CARGO_PKG_VERSION")),
+        parse_version(min_version),
+    ) {
+        (Some(current), Some(required)) => current >= required,
+        _ => true,
+    }
+}
+
+/// Deserialize `value`, reporting and removing each field serde rejects until
+/// the rest fits. Returns `None` if `value` itself doesn't fit, e.g. an
+/// argument that isn't a mapping or has no name.
+fn lenient<T: DeserializeOwned>(
+    mut value: Value,
+    path: &[Field],
+    report: &mut impl FnMut(Vec<Field>, String, bool),
+) -> Option<T> {
+    loop {
+        let error = match serde_path_to_error::deserialize::<_, T>(value.clone()) {
+            Ok(parsed) => return Some(parsed),
+            Err(e) => e,
+        };
+        let mut full = path.to_vec();
+        full.extend(error.path().iter().filter_map(|segment| match segment {
+            serde_path_to_error::Segment::Map { key } => Some(Field::Key(key.clone())),
+            serde_path_to_error::Segment::Seq { index } => Some(Field::Index(*index)),
+            _ => None,
+        }));
+        let removed = match error.path().iter().next() {
+            Some(serde_path_to_error::Segment::Map { key }) => value
+                .as_mapping_mut()
+                .and_then(|mapping| mapping.remove(key.as_str())),
+            _ => None,
+        };
+        let message = error.into_inner().to_string();
+        if removed.is_none() {
+            report(full, format!("{}, skipping", message), false);
+            return None;
+        }
+        report(full, format!("{}, ignoring", message), false);
+    }
+}
+
+/// Whether an `env:` fallback may read `name`: it is listed in `allow_env`,
+/// or starts with an entry ending in `*`. Prompt libraries may come from
+/// untrusted repositories, so nothing is allowed by default.
+pub fn env_fallback_allowed(name: &str, allow_env: &[String]) -> bool {
+    allow_env
+        .iter()
+        .any(|allowed| match allowed.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == allowed,
+        })
+}
+
+/// Check what serde can't: names, patterns, defaults and allowed fallbacks.
+fn validate_argument(
+    spec: ArgumentSpec,
+    path: &[Field],
+    allow_env: &[String],
+    report: &mut impl FnMut(Vec<Field>, String, bool),
+) -> Option<Argument> {
+    let field = |name: &str| {
+        let mut field = path.to_vec();
+        field.push(Field::Key(name.to_string()));
+        field
+    };
+    let name = spec.name.0;
+    if name.is_empty() {
+        report(field("name"), "is empty, skipping".to_string(), false);
+        return None;
+    }
+    if !crate::formatter::validate_variable_name(&name) {
+        report(
+            field("name"),
+            format!(
+                "'{}' contains invalid characters, consider '{}'",
+                name,
+                crate::naming::suggest_argument_name(&name)
+            ),
+            true,
+        );
+        return None;
+    }
+
+    let mut pattern = spec.pattern;
+    if let Some(Err(e)) = pattern.as_deref().map(regex::Regex::new) {
+        report(
+            field("pattern"),
+            format!("is not a valid regex, ignoring: {}", e),
+            false,
+        );
+        pattern = None;
+    }
+
+    let mut fallback = spec.fallback;
+    fallback.retain(|f| match f {
+        Fallback::Env(var) if !env_fallback_allowed(var, allow_env) => {
+            report(
+                field("fallback"),
+                format!(
+                    "'env:{}' is not an allowed environment variable, ignoring",
+                    var
+                ),
+                false,
+            );
+            false
+        }
+        _ => true,
+    });
+
+    let enum_values: Vec<String> = spec.enum_values.into_iter().map(|v| v.0).collect();
+    let default = spec.default.map(|d| d.0);
+    if let Some(default) = &default {
+        if spec.arg_type.coerce(default).is_none() {
+            report(
+                field("default"),
+                format!("'{}' is not a valid {}", default, spec.arg_type.as_str()),
+                false,
+            );
+        }
+        if !enum_values.is_empty() && !enum_values.contains(default) {
+            report(
+                field("default"),
+                format!("'{}' is not one of the enum values", default),
+                false,
+            );
+        }
+    }
+
+    Some(Argument {
+        name,
+        description: spec.description.map(|d| d.0).unwrap_or_default(),
+        default,
+        fallback,
+        enum_values,
+        arg_type: spec.arg_type,
+        pattern,
+        min_length: spec.min_length,
+        max_length: spec.max_length,
+        required: spec.required,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> (Frontmatter, Vec<Diagnostic>) {
+        let metadata = Metadata::yaml(Path::new("a.md"), yaml, 2).unwrap();
+        Frontmatter::parse(&[metadata], &["LANG".to_string()])
+    }
+
+    #[test]
+    fn test_parse_frontmatter() {
+        let (frontmatter, problems) = parse(
+            "name: review\ntitle: 42\ntags: [rust, 1]\ndraft: true\narguments:\n  - name: lang\n    enum: [rust, go]\n    fallback: [session, \"env:LANG\"]\n    type: string",
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(frontmatter.name, Some(Scalar("review".to_string())));
+        assert_eq!(frontmatter.title, Some(Scalar("42".to_string())));
+        assert_eq!(
+            frontmatter.tags,
+            vec![Scalar("rust".to_string()), Scalar("1".to_string())]
+        );
+        assert!(frontmatter.is_draft());
+        let argument = &frontmatter.arguments[0];
+        assert_eq!(argument.name, "lang");
+        assert_eq!(argument.enum_values, vec!["rust", "go"]);
+        assert_eq!(
+            argument.fallback,
+            vec![Fallback::Session, Fallback::Env("LANG".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_env_fallback_needs_allowing() {
+        let metadata = Metadata::yaml(
+            Path::new("a.md"),
+            "arguments:\n  - name: key\n    fallback: [\"env:AWS_SECRET_ACCESS_KEY\", \"env:APP_LANG\", session]",
+            2,
+        )
+        .unwrap();
+        let (frontmatter, problems) = Frontmatter::parse(&[metadata], &["APP_*".to_string()]);
+        assert_eq!(
+            frontmatter.arguments[0].fallback,
+            vec![Fallback::Env("APP_LANG".to_string()), Fallback::Session]
+        );
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].field, "arguments[0].fallback");
+        assert!(!problems[0].error);
+
+        assert!(env_fallback_allowed("LANG", &["LANG".to_string()]));
+        assert!(!env_fallback_allowed("LANGUAGE", &["LANG".to_string()]));
+        assert!(!env_fallback_allowed("LANG", &[]));
+    }
+
+    #[test]
+    fn test_parse_frontmatter_reports_every_problem() {
+        let (frontmatter, problems) = parse(
+            "title: [a, b]\ndescription: Fine\narguments:\n  - name: ok\n    type: int\n  - description: no name\n  - name: lang\n    default: ruby\n    enum: [rust, go]\n    pattern: \"(\"",
+        );
+        let found: Vec<_> = problems
+            .iter()
+            .map(|p| (p.line, p.field.as_str(), p.error))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(2), "title", false),
+                (Some(6), "arguments[0].type", false),
+                (Some(7), "arguments[1]", false),
+                (Some(11), "arguments[2].pattern", false),
+                (Some(9), "arguments[2].default", false),
+            ]
+        );
+        assert!(problems[1].message.contains("unknown variant `int`"));
+        assert_eq!(frontmatter.title, None);
+        assert_eq!(frontmatter.description, Some(Scalar("Fine".to_string())));
+        let names: Vec<_> = frontmatter
+            .arguments
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["ok", "lang"]);
+        assert_eq!(frontmatter.arguments[0].arg_type, ArgumentType::String);
+    }
+
+    #[test]
+    fn test_parse_frontmatter_invalid_argument_name_is_error() {
+        let (_, problems) = parse("arguments:\n  - name: my-arg");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].error);
+        assert_eq!(
+            problems[0].to_string(),
+            "a.md:3: 'arguments[0].name': 'my-arg' contains invalid characters, consider 'my_arg'"
+        );
+    }
+
+    #[test]
+    fn test_later_source_overrides_and_locates() {
+        let sidecar = Metadata::json(
+            Path::new("a.json"),
+            "{\n  \"title\": [1],\n  \"draft\": false\n}",
+            1,
+        )
+        .unwrap();
+        let frontmatter = Metadata::yaml(Path::new("a.md"), "draft: yes", 2).unwrap();
+        let (parsed, problems) = Frontmatter::parse(&[sidecar, frontmatter], &[]);
+        let found: Vec<_> = problems
+            .iter()
+            .map(|p| (p.file.to_str().unwrap(), p.line, p.field.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("a.json", Some(2), "title"), ("a.md", Some(2), "draft")]
+        );
+        assert_eq!(parsed.draft, None);
+    }
+
+    #[test]
+    fn test_parse_one_of() {
+        let (frontmatter, problems) = parse(
+            "arguments:\n  - name: diff\n  - name: file_path\none_of:\n  - [diff, file_path]\n  - [diff, path]\n  - [diff]",
+        );
+        assert_eq!(
+            frontmatter.one_of,
+            vec![vec![
+                Scalar("diff".to_string()),
+                Scalar("file_path".to_string())
+            ]]
+        );
+        let found: Vec<_> = problems
+            .iter()
+            .map(|p| (p.line, p.field.as_str(), p.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(7), "one_of[1]", "unknown arguments path, ignoring"),
+                (
+                    Some(8),
+                    "one_of[2]",
+                    "needs at least two arguments, ignoring"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_min_shinkuro_version() {
+        assert_eq!(parse_version("1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("v2"), Some((2, 0, 0)));
+        assert_eq!(parse_version("0.4.0-beta.1"), Some((0, 4, 0)));
+        assert_eq!(parse_version("1.x"), None);
+        assert!(is_supported("0.0.1"));
+        assert!(is_supported(env!("CARGO_PKG_VERSION")));
+        assert!(!is_supported("999.0.0"));
+
+        let (frontmatter, problems) = parse("version: 2\nmin_shinkuro_version: soon");
+        assert_eq!(frontmatter.version, Some(Scalar("2".to_string())));
+        assert_eq!(frontmatter.min_shinkuro_version, None);
+        assert_eq!(problems[0].field, "min_shinkuro_version");
+    }
+}
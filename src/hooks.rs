@@ -0,0 +1,144 @@
+use anyhow::Result;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// What to do with the rendered content when the post-render command fails.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailurePolicy {
+    /// Fail the request.
+    Error,
+    /// Warn and return the content unchanged.
+    Passthrough,
+}
+
+pub fn get_failure_policy(policy: &str) -> Result<FailurePolicy> {
+    match policy {
+        "error" => Ok(FailurePolicy::Error),
+        "passthrough" => Ok(FailurePolicy::Passthrough),
+        _ => anyhow::bail!("Unknown post-render failure policy: {}", policy),
+    }
+}
+
+/// An external command the rendered content is piped through (stdin to stdout).
+#[derive(Debug, Clone)]
+pub struct PostRenderHook {
+    pub command: String,
+    pub timeout: Duration,
+    pub on_failure: FailurePolicy,
+}
+
+impl PostRenderHook {
+    pub fn apply(&self, prompt_name: &str, content: String) -> Result<String, String> {
+        match self.run(prompt_name, &content) {
+            Ok(output) => Ok(output),
+            Err(e) if self.on_failure == FailurePolicy::Passthrough => {
+                tracing::warn!(
+                    prompt = prompt_name,
+                    error = %e,
+                    "Post-render command failed, returning unprocessed content"
+                );
+                Ok(content)
+            }
+            Err(e) => Err(format!("Post-render command failed: {}", e)),
+        }
+    }
+
+    fn run(&self, prompt_name: &str, input: &str) -> Result<String> {
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let mut child = Command::new(shell)
+            .args([flag, &self.command])
+            .env("SHINKURO_PROMPT", prompt_name)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Feed and drain the pipes on separate threads so large content can't
+        // deadlock against the child while we wait with a timeout.
+        let mut stdin = child.stdin.take().unwrap();
+        let input = input.to_string();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let mut stdout = child.stdout.take().unwrap();
+        let reader = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            stdout.read_to_end(&mut buf).map(|_| buf)
+        });
+        let mut stderr = child.stderr.take().unwrap();
+        let error_reader = std::thread::spawn(move || {
+            let mut buf = String::new();
+            let _ = stderr.read_to_string(&mut buf);
+            buf
+        });
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                anyhow::bail!("timed out after {:?}", self.timeout);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+
+        // The child may exit without reading all of its input, which is fine.
+        let _ = writer.join();
+        let output = reader.join().unwrap()?;
+        let errors = error_reader.join().unwrap();
+        if !status.success() {
+            anyhow::bail!("exited with {}: {}", status, errors.trim());
+        }
+        Ok(String::from_utf8(output)?)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn hook(command: &str, on_failure: FailurePolicy) -> PostRenderHook {
+        PostRenderHook {
+            command: command.to_string(),
+            timeout: Duration::from_secs(5),
+            on_failure,
+        }
+    }
+
+    #[test]
+    fn test_post_render_hook_transforms_content() {
+        let hook = hook("tr a-z A-Z", FailurePolicy::Error);
+        assert_eq!(hook.apply("test", "hello".to_string()).unwrap(), "HELLO");
+    }
+
+    #[test]
+    fn test_post_render_hook_failure_policies() {
+        let failing = hook("exit 3", FailurePolicy::Error);
+        assert!(failing
+            .apply("test", "hello".to_string())
+            .unwrap_err()
+            .contains("Post-render command failed"));
+
+        let passthrough = hook("exit 3", FailurePolicy::Passthrough);
+        assert_eq!(
+            passthrough.apply("test", "hello".to_string()).unwrap(),
+            "hello"
+        );
+    }
+
+    #[test]
+    fn test_post_render_hook_timeout() {
+        let mut slow = hook("sleep 5", FailurePolicy::Error);
+        slow.timeout = Duration::from_millis(100);
+        assert!(slow
+            .apply("test", "hello".to_string())
+            .unwrap_err()
+            .contains("timed out"));
+    }
+}
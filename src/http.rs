@@ -0,0 +1,358 @@
+use crate::loader::SyncStatus;
+use crate::paths;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+/// Validators remembered per URL for conditional requests.
+const METADATA_FILE: &str = ".shinkuro-http.json";
+
+#[cfg(feature = "http-transport")]
+const USER_AGENT: &str = concat!("shinkuro/", env!("CARGO_PKG_VERSION"));
+
+#[cfg(all(feature = "http-transport", not(target_os = "wasi")))]
+pub use reqwest::blocking::Client;
+#[cfg(all(feature = "http-transport", target_os = "wasi"))]
+pub use waki::Client;
+
+/// Without the `http-transport` feature there is no client to build.
+#[cfg(not(feature = "http-transport"))]
+pub enum Client {}
+
+/// HTTP client honoring `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY`, or `proxy` if set
+/// (still bypassed for hosts listed in `NO_PROXY`).
+#[cfg(all(feature = "http-transport", not(target_os = "wasi")))]
+pub fn client(proxy: Option<&str>) -> Result<Client> {
+    use reqwest::{NoProxy, Proxy};
+    let mut builder = Client::builder().user_agent(USER_AGENT);
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(Proxy::all(proxy)?.no_proxy(NoProxy::from_env()));
+    }
+    Ok(builder.build()?)
+}
+
+/// The host's HTTP client. Proxies are configured on the host, not here.
+#[cfg(all(feature = "http-transport", target_os = "wasi"))]
+pub fn client(proxy: Option<&str>) -> Result<Client> {
+    if proxy.is_some() {
+        anyhow::bail!("--proxy is not supported in WASM builds");
+    }
+    Ok(Client::new())
+}
+
+#[cfg(not(feature = "http-transport"))]
+pub fn client(_proxy: Option<&str>) -> Result<Client> {
+    anyhow::bail!("Fetching over HTTP needs shinkuro built with the `http-transport` feature")
+}
+
+/// A response read in full, the same whichever client the build uses.
+pub struct Response {
+    pub status: u16,
+    /// Header names are lowercase.
+    headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+pub fn get(client: &Client, url: &str, headers: &[(&'static str, &str)]) -> Result<Response> {
+    send(client, "GET", url, headers)
+}
+
+pub fn head(client: &Client, url: &str) -> Result<Response> {
+    send(client, "HEAD", url, &[])
+}
+
+/// Send a request, failing on error statuses.
+#[cfg(all(feature = "http-transport", not(target_os = "wasi")))]
+fn send(
+    client: &Client,
+    method: &str,
+    url: &str,
+    headers: &[(&'static str, &str)],
+) -> Result<Response> {
+    let mut request = client.request(method.parse()?, url);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = request.send()?.error_for_status()?;
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    Ok(Response {
+        status: response.status().as_u16(),
+        headers,
+        body: response.bytes()?.to_vec(),
+    })
+}
+
+/// Send a request through the host's `wasi:http`, failing on error statuses.
+#[cfg(all(feature = "http-transport", target_os = "wasi"))]
+fn send(
+    client: &Client,
+    method: &str,
+    url: &str,
+    headers: &[(&'static str, &str)],
+) -> Result<Response> {
+    let request = match method {
+        "HEAD" => client.head(url),
+        _ => client.get(url),
+    };
+    let response = request
+        .header("user-agent", USER_AGENT)
+        .headers(headers.iter().copied())
+        .send()?;
+    let status = response.status_code();
+    if status >= 400 {
+        anyhow::bail!("HTTP status {} for url ({})", status, url);
+    }
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    Ok(Response {
+        status,
+        headers,
+        body: response.body()?,
+    })
+}
+
+#[cfg(not(feature = "http-transport"))]
+fn send(
+    client: &Client,
+    _method: &str,
+    _url: &str,
+    _headers: &[(&'static str, &str)],
+) -> Result<Response> {
+    match *client {}
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CacheMetadata {
+    #[serde(default)]
+    validators: HashMap<String, Validators>,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// A manifest is either a list of prompt file paths or `{"prompts": [...]}`,
+/// relative to the manifest URL.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Manifest {
+    List(Vec<String>),
+    Object { prompts: Vec<String> },
+}
+
+impl Manifest {
+    fn into_files(self) -> Vec<String> {
+        match self {
+            Manifest::List(files) | Manifest::Object { prompts: files } => files,
+        }
+    }
+}
+
+/// Cache directory for an HTTP source: `<cache_dir>/http/<host>/<path>` with the
+/// path flattened (`/` becomes `_`) so one source's folder never nests another's.
+/// A port is kept in the host, e.g. `localhost_8080`.
+pub fn get_cache_path(url: &str, cache_dir: &str) -> Result<PathBuf> {
+    let parsed = url::Url::parse(url)?;
+    let host = match (parsed.host_str(), parsed.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (host, None) => host.unwrap_or_default().to_string(),
+        (None, Some(_)) => String::new(),
+    };
+    let flattened = parsed.path().trim_start_matches('/').replace('/', "_");
+    Ok(paths::expand(cache_dir)
+        .join("http")
+        .join(paths::component(&host))
+        .join(paths::component(&flattened)))
+}
+
+/// Fetch a single markdown file or every file listed by a JSON manifest into `root`.
+/// URLs ending with `.json` are manifests, URLs ending with `/` use `index.json`.
+pub fn fetch_or_update(
+    url: &str,
+    root: &Path,
+    auto_pull: bool,
+    proxy: Option<&str>,
+) -> Result<SyncStatus> {
+    if root.exists() && !auto_pull {
+        return Ok(SyncStatus::Cached);
+    }
+    match fetch(url, root, proxy) {
+        Ok(()) => Ok(SyncStatus::Fresh),
+        Err(e) if root.exists() => {
+            tracing::warn!(source = %url, error = %e, "Failed to fetch, serving cached copy");
+            Ok(SyncStatus::Stale)
+        }
+        Err(e) => Err(anyhow::anyhow!("HTTP fetch failed: {}", e)),
+    }
+}
+
+fn fetch(url: &str, root: &Path, proxy: Option<&str>) -> Result<()> {
+    let client = client(proxy)?;
+    std::fs::create_dir_all(root)?;
+    let metadata_path = root.join(METADATA_FILE);
+    let mut metadata: CacheMetadata = std::fs::read(&metadata_path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default();
+
+    let mut parsed = url::Url::parse(url)?;
+    if parsed.path().ends_with('/') {
+        parsed = parsed.join("index.json")?;
+    }
+
+    if parsed.path().ends_with(".json") {
+        let manifest: Manifest = serde_json::from_slice(&get(&client, parsed.as_str(), &[])?.body)?;
+        let mut keep = HashSet::new();
+        for file in manifest.into_files() {
+            let Some(relative) = relative_path(&file) else {
+                tracing::warn!(source = %url, file = %file, "Skipping unsafe manifest entry");
+                continue;
+            };
+            let file_url = parsed.join(&file)?;
+            fetch_file(&client, &file_url, &root.join(&relative), &mut metadata)?;
+            keep.insert(relative);
+        }
+        remove_unlisted(root, root, &keep)?;
+    } else {
+        let name = parsed
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("index.md")
+            .to_string();
+        fetch_file(&client, &parsed, &root.join(name), &mut metadata)?;
+    }
+
+    std::fs::write(&metadata_path, serde_json::to_vec_pretty(&metadata)?)?;
+    Ok(())
+}
+
+/// Download `url` to `dest` unless the server reports it unchanged.
+fn fetch_file(
+    client: &Client,
+    url: &url::Url,
+    dest: &Path,
+    metadata: &mut CacheMetadata,
+) -> Result<()> {
+    let mut headers = Vec::new();
+    if dest.exists() {
+        if let Some(validators) = metadata.validators.get(url.as_str()) {
+            if let Some(etag) = &validators.etag {
+                headers.push(("if-none-match", etag.as_str()));
+            }
+            if let Some(last_modified) = &validators.last_modified {
+                headers.push(("if-modified-since", last_modified.as_str()));
+            }
+        }
+    }
+
+    let response = get(client, url.as_str(), &headers)?;
+    if response.status == 304 {
+        return Ok(());
+    }
+    let validators = Validators {
+        etag: response.header("etag").map(str::to_string),
+        last_modified: response.header("last-modified").map(str::to_string),
+    };
+    let body = response.body;
+
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(dest, &body)?;
+    metadata
+        .validators
+        .insert(url.as_str().to_string(), validators);
+    Ok(())
+}
+
+/// Keep manifest entries inside the cache directory.
+fn relative_path(entry: &str) -> Option<PathBuf> {
+    let path = Path::new(entry);
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!relative.as_os_str().is_empty()).then_some(relative)
+}
+
+/// Delete previously fetched files that the manifest no longer lists.
+fn remove_unlisted(root: &Path, dir: &Path, keep: &HashSet<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            remove_unlisted(root, &path, keep)?;
+        } else if path.file_name().and_then(|n| n.to_str()) != Some(METADATA_FILE) {
+            let relative = path.strip_prefix(root)?.to_path_buf();
+            if !keep.contains(&relative) {
+                std::fs::remove_file(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_cache_path() {
+        let path = get_cache_path("https://example.com/prompts/", "/cache").unwrap();
+        assert_eq!(path, PathBuf::from("/cache/http/example.com/prompts_"));
+        let path = get_cache_path("https://example.com/prompts/review.md", "/cache").unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("/cache/http/example.com/prompts_review.md")
+        );
+        let path = get_cache_path("https://example.com", "/cache").unwrap();
+        assert_eq!(path, PathBuf::from("/cache/http/example.com/_"));
+        let path = get_cache_path("http://localhost:8080/a:b.md", "/cache").unwrap();
+        assert_eq!(path, PathBuf::from("/cache/http/localhost_8080/a_b.md"));
+    }
+
+    #[cfg(feature = "http-transport")]
+    #[test]
+    fn test_client_proxy() {
+        assert!(client(Some("http://proxy.example.com:8080")).is_ok());
+        assert!(client(Some("not a proxy")).is_err());
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path("./dev/commit.md"),
+            Some(PathBuf::from("dev/commit.md"))
+        );
+        assert_eq!(relative_path("../secret.md"), None);
+        assert_eq!(relative_path("/etc/passwd"), None);
+    }
+
+    #[test]
+    fn test_manifest_formats() {
+        let list: Manifest = serde_json::from_str(r#"["a.md", "b.md"]"#).unwrap();
+        assert_eq!(list.into_files(), vec!["a.md", "b.md"]);
+        let object: Manifest = serde_json::from_str(r#"{"prompts": ["a.md"]}"#).unwrap();
+        assert_eq!(object.into_files(), vec!["a.md"]);
+    }
+}
@@ -0,0 +1,524 @@
+use crate::formatter::{validate_variable_name, Formatter};
+use crate::init::{title_case, yaml};
+use crate::naming;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Formats prompts can be imported from with `shinkuro import`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    /// Claude Code slash commands, one markdown file per command.
+    ClaudeCommands,
+    /// Fabric patterns, a folder per pattern with its prompt in `system.md`.
+    Fabric,
+    /// VS Code Copilot prompt files, named `<name>.prompt.md`.
+    Copilot,
+}
+
+pub fn get_import_format(format: &str) -> Result<ImportFormat> {
+    match format {
+        "claude-commands" => Ok(ImportFormat::ClaudeCommands),
+        "fabric" => Ok(ImportFormat::Fabric),
+        "copilot" => Ok(ImportFormat::Copilot),
+        _ => anyhow::bail!("Unknown import format: {}", format),
+    }
+}
+
+/// Convert the files in `dir` into prompts in `out`, skipping files that
+/// already exist. Returns the files written.
+pub fn import(
+    dir: &Path,
+    format: ImportFormat,
+    out: &Path,
+    formatter: &dyn Formatter,
+) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        anyhow::bail!("Folder not found: {}", dir.display());
+    }
+    let mut written = Vec::new();
+    for (file, relative) in source_files(dir, format)? {
+        let path = out.join(&relative);
+        if path.exists() {
+            tracing::warn!("Skipped {}, it already exists", path.display());
+            continue;
+        }
+        let name = relative
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        let text = std::fs::read_to_string(&file)?;
+        let prompt = match format {
+            ImportFormat::ClaudeCommands => claude_command(name, &text, formatter),
+            ImportFormat::Fabric => Ok(fabric_pattern(name, &text, formatter)),
+            ImportFormat::Copilot => Ok(copilot_prompt(name, &text, formatter)),
+        };
+        let prompt =
+            prompt.map_err(|e| anyhow::anyhow!("Failed to import {}: {}", file.display(), e))?;
+        if format == ImportFormat::ClaudeCommands && text.contains("!`") {
+            tracing::warn!(
+                "{} runs shell commands with !`...`, which are kept as written",
+                file.display()
+            );
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, prompt)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// The files to convert with the prompt file each becomes, relative to the
+/// output folder. Slash commands and Copilot prompt files keep their
+/// relative paths and Fabric patterns are named after their folder.
+fn source_files(dir: &Path, format: ImportFormat) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = Vec::new();
+    match format {
+        ImportFormat::ClaudeCommands => {
+            for entry in walkdir::WalkDir::new(dir).sort_by_file_name() {
+                let entry = entry?;
+                let file = entry.path();
+                if entry.file_type().is_file()
+                    && file.extension().and_then(|e| e.to_str()) == Some("md")
+                {
+                    files.push((file.to_path_buf(), file.strip_prefix(dir)?.to_path_buf()));
+                }
+            }
+        }
+        ImportFormat::Copilot => {
+            for entry in walkdir::WalkDir::new(dir).sort_by_file_name() {
+                let entry = entry?;
+                let file = entry.path();
+                let relative = file.strip_prefix(dir)?;
+                let Some(name) = relative
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_suffix(".prompt.md"))
+                else {
+                    continue;
+                };
+                if entry.file_type().is_file() {
+                    files.push((
+                        file.to_path_buf(),
+                        relative.with_file_name(format!("{}.md", name)),
+                    ));
+                }
+            }
+        }
+        ImportFormat::Fabric => {
+            let mut patterns: Vec<_> = std::fs::read_dir(dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.join("system.md").is_file())
+                .collect();
+            patterns.sort();
+            for pattern in patterns {
+                let name = pattern.file_name().unwrap_or_default().to_string_lossy();
+                files.push((
+                    pattern.join("system.md"),
+                    PathBuf::from(format!("{}.md", name)),
+                ));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// An argument of an imported prompt.
+struct ImportedArgument {
+    name: String,
+    description: String,
+    required: bool,
+}
+
+/// A prompt file with `content` already written in the output format.
+fn prompt_file(
+    name: &str,
+    description: &str,
+    tags: &[String],
+    arguments: &[ImportedArgument],
+    content: &str,
+) -> String {
+    let mut text = format!(
+        "---\ntitle: {}\ndescription: {}\n",
+        yaml(&title_case(name)),
+        yaml(description)
+    );
+    if !tags.is_empty() {
+        let tags: Vec<_> = tags.iter().map(|tag| yaml(tag)).collect();
+        text.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+    }
+    if !arguments.is_empty() {
+        text.push_str("arguments:\n");
+    }
+    for argument in arguments {
+        text.push_str(&format!(
+            "  - name: {}\n    description: {}\n",
+            argument.name,
+            yaml(&argument.description)
+        ));
+        if !argument.required {
+            text.push_str("    default: \"\"\n");
+        }
+    }
+    text.push_str("---\n\n");
+    text.push_str(content);
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// The first line of text that isn't a heading.
+fn first_line(body: &str) -> &str {
+    body.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or_default()
+}
+
+/// A placeholder in a slash command.
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// `$ARGUMENTS`, everything after the command.
+    All,
+    /// `$1`, `$2`, ...
+    Position(usize),
+}
+
+/// Split a slash command body into text and the placeholders between it.
+fn tokenize(body: &str) -> Vec<(&str, Option<Token>)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut rest = body;
+    let mut offset = 0;
+    while let Some(found) = rest.find('$') {
+        let at = offset + found;
+        let after = &body[at + 1..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let (token, len) = if after.starts_with("ARGUMENTS") {
+            (Some(Token::All), 1 + "ARGUMENTS".len())
+        } else if digits > 0 {
+            let position = after[..digits].parse().ok().filter(|&n| n > 0);
+            (position.map(Token::Position), 1 + digits)
+        } else {
+            (None, 1)
+        };
+        if let Some(token) = token {
+            parts.push((&body[start..at], Some(token)));
+            start = at + len;
+        }
+        offset = at + len;
+        rest = &body[offset..];
+    }
+    parts.push((&body[start..], None));
+    parts
+}
+
+/// A top-level frontmatter value. Slash commands often aren't valid YAML,
+/// e.g. `argument-hint: [pr-number] [priority]`, so values are read line by
+/// line and only unquoted when they are YAML strings.
+fn field(frontmatter: &str, key: &str) -> Option<String> {
+    frontmatter.lines().find_map(|line| {
+        let value = line.strip_prefix(key)?.strip_prefix(':')?.trim();
+        Some(serde_yaml::from_str::<String>(value).unwrap_or_else(|_| value.to_string()))
+    })
+}
+
+/// A prompt file for a slash command called `name`. `$ARGUMENTS` becomes
+/// an `arguments` argument and `$1`, `$2`, ... are named after the bracketed
+/// words of `argument-hint`, or `arg1`, `arg2`, ... All are optional, since
+/// slash commands can be run without them.
+fn claude_command(name: &str, text: &str, formatter: &dyn Formatter) -> Result<String> {
+    let (frontmatter, body) = match text
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n"))
+    {
+        Some((frontmatter, body)) => (frontmatter, body),
+        None => ("", text),
+    };
+    let field = |key: &str| field(frontmatter, key);
+    let hint = field("argument-hint").filter(|hint| !hint.is_empty());
+    let hint = hint.as_deref();
+    let hint_names: Vec<String> = hint
+        .map(|hint| {
+            hint.split_whitespace()
+                .map(|word| {
+                    word.strip_prefix(['[', '<'])
+                        .and_then(|word| word.strip_suffix([']', '>']))
+                })
+                .collect::<Option<Vec<_>>>()
+                .unwrap_or_default()
+                .into_iter()
+                .map(naming::suggest_argument_name)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut arguments: Vec<(String, String)> = Vec::new();
+    let mut positions: Vec<(usize, String)> = Vec::new();
+    let mut content = String::new();
+    for (text, token) in tokenize(body.trim_start()) {
+        if !text.is_empty() {
+            content.push_str(&formatter.escape(text));
+        }
+        let (argument, description) = match token {
+            None => continue,
+            Some(Token::All) => (
+                "arguments".to_string(),
+                hint.unwrap_or("Everything after the command").to_string(),
+            ),
+            Some(Token::Position(n)) => {
+                let name = match positions.iter().find(|(position, _)| *position == n) {
+                    Some((_, name)) => name.clone(),
+                    None => {
+                        let name = hint_names
+                            .get(n - 1)
+                            .filter(|name| !arguments.iter().any(|(a, _)| a == *name))
+                            .cloned()
+                            .unwrap_or_else(|| format!("arg{}", n));
+                        positions.push((n, name.clone()));
+                        name
+                    }
+                };
+                (name, format!("Argument {}", n))
+            }
+        };
+        content.push_str(&formatter.placeholder(&argument));
+        if !arguments.iter().any(|(a, _)| *a == argument) {
+            arguments.push((argument, description));
+        }
+    }
+
+    let description = match field("description").filter(|d| !d.is_empty()) {
+        Some(description) => description,
+        // Like slash commands, fall back to the first line
+        None => body
+            .lines()
+            .map(|line| line.trim_start_matches('#').trim())
+            .find(|line| !line.is_empty())
+            .unwrap_or_default()
+            .to_string(),
+    };
+    let arguments: Vec<_> = arguments
+        .into_iter()
+        .map(|(name, description)| ImportedArgument {
+            name,
+            description,
+            required: false,
+        })
+        .collect();
+    Ok(prompt_file(name, &description, &[], &arguments, &content))
+}
+
+/// A prompt file for the Fabric pattern `name`. Fabric sends the input after
+/// the pattern unless it has an `{{input}}` placeholder, so the required
+/// `input` argument is appended when there is none. Other `{{variables}}`
+/// become optional arguments.
+fn fabric_pattern(name: &str, text: &str, formatter: &dyn Formatter) -> String {
+    let body = text.trim();
+    let mut arguments: Vec<ImportedArgument> = Vec::new();
+    let mut content = String::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        let variable = rest[start + 2..]
+            .split_once("}}")
+            .map(|(inside, _)| inside.trim())
+            .filter(|inside| validate_variable_name(inside));
+        let Some(variable) = variable else {
+            content.push_str(&formatter.escape(&rest[..start + 2]));
+            rest = &rest[start + 2..];
+            continue;
+        };
+        content.push_str(&formatter.escape(&rest[..start]));
+        content.push_str(&formatter.placeholder(variable));
+        if !arguments.iter().any(|a| a.name == variable) {
+            arguments.push(fabric_argument(variable));
+        }
+        rest = &rest[start + 2..];
+        rest = &rest[rest.find("}}").unwrap_or_default() + 2..];
+    }
+    content.push_str(&formatter.escape(rest));
+    if !arguments.iter().any(|a| a.name == "input") {
+        content.push_str("\n\n");
+        content.push_str(&formatter.placeholder("input"));
+        arguments.push(fabric_argument("input"));
+    }
+
+    let description = first_line(body);
+    // Patterns open with a paragraph; keep its first sentence
+    let description = match description.find(". ") {
+        Some(end) => &description[..end + 1],
+        None => description,
+    };
+    prompt_file(name, description, &[], &arguments, &content)
+}
+
+fn fabric_argument(name: &str) -> ImportedArgument {
+    if name == "input" {
+        ImportedArgument {
+            name: name.to_string(),
+            description: "The text to process".to_string(),
+            required: true,
+        }
+    } else {
+        ImportedArgument {
+            name: name.to_string(),
+            description: String::new(),
+            required: false,
+        }
+    }
+}
+
+/// A prompt file for the Copilot prompt file `name`. `${input:name}` and
+/// `${input:name:placeholder}` become required arguments described by their
+/// placeholder, and the chat `mode` becomes a tag. Other variables like
+/// `${selection}` only exist in the editor, so they are kept as written.
+fn copilot_prompt(name: &str, text: &str, formatter: &dyn Formatter) -> String {
+    let (frontmatter, body) = match text
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n"))
+    {
+        Some((frontmatter, body)) => (frontmatter, body),
+        None => ("", text),
+    };
+    let mut arguments: Vec<ImportedArgument> = Vec::new();
+    let mut content = String::new();
+    let mut rest = body.trim_start();
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let inside = &rest[start + 2..start + len];
+        let Some(input) = inside.strip_prefix("input:") else {
+            tracing::warn!(
+                "'${{{}}}' in {} only exists in the editor and is kept as written",
+                inside,
+                name
+            );
+            content.push_str(&formatter.escape(&rest[..start + len + 1]));
+            rest = &rest[start + len + 1..];
+            continue;
+        };
+        let (variable, placeholder) = input.split_once(':').unwrap_or((input, ""));
+        let variable = naming::suggest_argument_name(variable);
+        content.push_str(&formatter.escape(&rest[..start]));
+        content.push_str(&formatter.placeholder(&variable));
+        if !arguments.iter().any(|a| a.name == variable) {
+            arguments.push(ImportedArgument {
+                name: variable,
+                description: placeholder.trim().to_string(),
+                required: true,
+            });
+        }
+        rest = &rest[start + len + 1..];
+    }
+    content.push_str(&formatter.escape(rest));
+
+    let description = field(frontmatter, "description")
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| first_line(body).to_string());
+    let tags: Vec<String> = field(frontmatter, "mode").into_iter().collect();
+    prompt_file(name, &description, &tags, &arguments, &content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter::{BraceFormatter, DollarFormatter};
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Fix $ARGUMENTS in $1, not $0 or $x, cost $ 12"),
+            [
+                ("Fix ", Some(Token::All)),
+                (" in ", Some(Token::Position(1))),
+                (", not $0 or $x, cost $ 12", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_claude_command() {
+        let text = "---\ndescription: Fix an issue\nargument-hint: [issue-number] [priority]\n---\n\nFix issue #$1 with priority $2 in {repo}.\n";
+        assert_eq!(
+            claude_command("fix-issue", text, &BraceFormatter).unwrap(),
+            "---\ntitle: Fix Issue\ndescription: Fix an issue\narguments:\n  - name: issue_number\n    description: Argument 1\n    default: \"\"\n  - name: priority\n    description: Argument 2\n    default: \"\"\n---\n\nFix issue #{issue_number} with priority {priority} in {{repo}}.\n"
+        );
+        assert_eq!(
+            claude_command("review", "# Review\n\nReview $ARGUMENTS for $5\n", &DollarFormatter)
+                .unwrap(),
+            "---\ntitle: Review\ndescription: Review\narguments:\n  - name: arguments\n    description: Everything after the command\n    default: \"\"\n  - name: arg5\n    description: Argument 5\n    default: \"\"\n---\n\n# Review\n\nReview $arguments for $arg5\n"
+        );
+    }
+
+    #[test]
+    fn test_fabric_pattern() {
+        let text = "# IDENTITY and PURPOSE\n\nYou extract wisdom. You are thorough.\n\n# OUTPUT\n\nUse {braces}.\n";
+        assert_eq!(
+            fabric_pattern("extract_wisdom", text, &BraceFormatter),
+            "---\ntitle: Extract Wisdom\ndescription: You extract wisdom.\narguments:\n  - name: input\n    description: The text to process\n---\n\n# IDENTITY and PURPOSE\n\nYou extract wisdom. You are thorough.\n\n# OUTPUT\n\nUse {{braces}}.\n\n{input}\n"
+        );
+        assert_eq!(
+            fabric_pattern("translate", "Translate to {{lang}}:\n\n{{ input }} {{ not a name }}", &BraceFormatter),
+            "---\ntitle: Translate\ndescription: 'Translate to {{lang}}:'\narguments:\n  - name: lang\n    description: ''\n    default: \"\"\n  - name: input\n    description: The text to process\n---\n\nTranslate to {lang}:\n\n{input} {{{{ not a name }}}}\n"
+        );
+    }
+
+    #[test]
+    fn test_copilot_prompt() {
+        let text = "---\nmode: 'agent'\ndescription: 'Generate a form'\ntools: ['codebase']\n---\nCreate ${input:formName:The form name} in ${file}, like ${input:formName}.\n";
+        assert_eq!(
+            copilot_prompt("react-form", text, &BraceFormatter),
+            "---\ntitle: React Form\ndescription: Generate a form\ntags: [agent]\narguments:\n  - name: formName\n    description: The form name\n---\n\nCreate {formName} in ${{file}}, like {formName}.\n"
+        );
+        assert_eq!(
+            copilot_prompt("plain", "# Heading\n\nExplain ${input:topic}", &BraceFormatter),
+            "---\ntitle: Plain\ndescription: Explain ${input:topic}\narguments:\n  - name: topic\n    description: ''\n---\n\n# Heading\n\nExplain {topic}\n"
+        );
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = TempDir::new("import");
+        let commands = dir.join("commands");
+        std::fs::create_dir_all(commands.join("git")).unwrap();
+        std::fs::write(commands.join("git/commit.md"), "Commit $ARGUMENTS\n").unwrap();
+        std::fs::write(commands.join("notes.txt"), "not a command").unwrap();
+        let out = dir.join("prompts");
+        let written = import(
+            &commands,
+            ImportFormat::ClaudeCommands,
+            &out,
+            &BraceFormatter,
+        )
+        .unwrap();
+        assert_eq!(written, [out.join("git/commit.md")]);
+        // Existing files are kept
+        let written = import(
+            &commands,
+            ImportFormat::ClaudeCommands,
+            &out,
+            &BraceFormatter,
+        )
+        .unwrap();
+        assert!(written.is_empty());
+
+        let patterns = dir.join("patterns");
+        std::fs::create_dir_all(patterns.join("summarize")).unwrap();
+        std::fs::create_dir_all(patterns.join("empty")).unwrap();
+        std::fs::write(patterns.join("summarize/system.md"), "Summarize.").unwrap();
+        std::fs::write(patterns.join("summarize/user.md"), "").unwrap();
+        let written = import(&patterns, ImportFormat::Fabric, &out, &BraceFormatter).unwrap();
+        assert_eq!(written, [out.join("summarize.md")]);
+
+        let prompts = dir.join("github-prompts");
+        std::fs::create_dir_all(&prompts).unwrap();
+        std::fs::write(prompts.join("explain.prompt.md"), "Explain").unwrap();
+        std::fs::write(prompts.join("README.md"), "Not a prompt").unwrap();
+        let written = import(&prompts, ImportFormat::Copilot, &out, &BraceFormatter).unwrap();
+        assert_eq!(written, [out.join("explain.md")]);
+    }
+}
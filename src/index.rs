@@ -0,0 +1,137 @@
+use crate::model::PromptData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Bumped whenever the index layout or prompt parsing changes; older indexes are discarded.
+const VERSION: u32 = 11;
+
+/// Parsed prompts from the previous scan of a folder, keyed by file path, so
+/// unchanged files are neither read nor parsed again on refresh.
+#[derive(Default, Serialize, Deserialize)]
+pub struct ScanIndex {
+    version: u32,
+    /// Parsing depends on these, so the index is only valid for the same values.
+    skip_frontmatter: bool,
+    allow_env: Vec<String>,
+    files: HashMap<PathBuf, IndexedFile>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IndexedFile {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+    prompts: Vec<PromptData>,
+}
+
+/// Where the index of `folder` is stored: `<index_dir>/<hash of the path>.bin`.
+pub fn get_index_path(index_dir: &Path, folder: &Path) -> PathBuf {
+    let hash = crate::loader::stable_hash(folder.as_os_str().as_encoded_bytes());
+    index_dir.join(format!("{:016x}.bin", hash))
+}
+
+/// Hash of a prompt file and its sidecar metadata, if any. The two are joined
+/// by a byte that can't occur in UTF-8, so moving text between them changes it.
+pub fn content_hash(content: &str, sidecar: Option<&str>) -> u64 {
+    let mut bytes = content.as_bytes().to_vec();
+    if let Some(sidecar) = sidecar {
+        bytes.push(0xff);
+        bytes.extend_from_slice(sidecar.as_bytes());
+    }
+    crate::loader::stable_hash(&bytes)
+}
+
+impl ScanIndex {
+    /// Load the index at `path`, or an empty one if it is missing, unreadable or stale.
+    pub fn load(path: &Path, skip_frontmatter: bool, allow_env: &[String]) -> Self {
+        std::fs::read(path)
+            .ok()
+            .and_then(|bytes| bincode::deserialize::<ScanIndex>(&bytes).ok())
+            .filter(|index| {
+                index.version == VERSION
+                    && index.skip_frontmatter == skip_frontmatter
+                    && index.allow_env == allow_env
+            })
+            .unwrap_or(ScanIndex {
+                version: VERSION,
+                skip_frontmatter,
+                allow_env: allow_env.to_vec(),
+                files: HashMap::new(),
+            })
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, bincode::serialize(self)?)?;
+        Ok(())
+    }
+
+    /// The cached prompt if the file's modification time and size are unchanged.
+    pub fn unchanged(&self, file: &Path, metadata: &std::fs::Metadata) -> Option<&IndexedFile> {
+        let entry = self.files.get(file)?;
+        (entry.modified.is_some()
+            && entry.modified == metadata.modified().ok()
+            && entry.len == metadata.len())
+        .then_some(entry)
+    }
+
+    /// The cached prompt if the file's content is unchanged (e.g. after a checkout
+    /// touched it).
+    pub fn same_content(&self, file: &Path, hash: u64) -> Option<&IndexedFile> {
+        self.files.get(file).filter(|entry| entry.hash == hash)
+    }
+
+    /// Replace all entries, returning whether anything changed.
+    pub fn replace(&mut self, files: HashMap<PathBuf, IndexedFile>) -> bool {
+        let changed = files.len() != self.files.len()
+            || files.iter().any(|(path, entry)| {
+                self.files.get(path).is_none_or(|old| {
+                    old.modified != entry.modified || old.len != entry.len || old.hash != entry.hash
+                })
+            });
+        self.files = files;
+        changed
+    }
+}
+
+impl IndexedFile {
+    pub fn new(metadata: &std::fs::Metadata, hash: u64, prompts: Vec<PromptData>) -> Self {
+        Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash,
+            prompts,
+        }
+    }
+
+    /// The same entry with fresh file metadata.
+    pub fn touched(&self, metadata: &std::fs::Metadata) -> Self {
+        Self::new(metadata, self.hash, self.prompts.clone())
+    }
+
+    /// The prompts defined in the file, in order.
+    pub fn prompts(&self) -> &[PromptData] {
+        &self.prompts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashes_are_stable() {
+        // Pinned, since a changed hash would discard every index
+        assert_eq!(
+            get_index_path(Path::new("/cache/index"), Path::new("/srv/prompts")),
+            PathBuf::from("/cache/index/3d68df326fb88a0b.bin")
+        );
+        assert_eq!(content_hash("a", None), content_hash("a", None));
+        assert_ne!(content_hash("a", None), content_hash("a", Some("")));
+        assert_ne!(content_hash("ab", Some("")), content_hash("a", Some("b")));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::formatter::{validate_variable_name, Formatter};
+use crate::naming;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+const EXAMPLE_PROMPT: &str = r#"---
+# Every key is optional. The name defaults to the file name and is what
+# MCP clients call the prompt by.
+name: code-review
+title: Code Review
+description: Review code for bugs, readability and security issues
+tags: [example]
+# Arguments are passed by the client and substituted for {name} placeholders.
+# Without a default they are required.
+arguments:
+  - name: code
+    description: The code to review
+  - name: language
+    description: Programming language of the code
+    default: the language it is written in
+    # Other checks: enum, type (string, number, boolean or list), pattern,
+    # min_length and max_length
+---
+
+# Code Review
+
+Review the following code, written in {language}. Point out bugs first, then
+readability and security issues, each with a suggested fix.
+
+{code}
+
+Write `{{` and `}}` for literal braces.
+"#;
+
+const IGNORE_FILE: &str = "# Files and folders skipped when loading prompts, in .gitignore syntax
+README.md
+drafts/
+";
+
+const CONFIG_FILE: &str = r#"# Default options for shinkuro run from this folder. Every command-line
+# option can be set here by its long name; flags and environment variables
+# take precedence.
+folder = "."
+# variable_format = "brace"
+# extensions = ["md"]
+# filter_tag = ["example"]
+# extra_sources = ["git@github.com:owner/prompts.git#shared"]
+# auto_pull = true
+# refresh_interval = "15m"
+"#;
+
+/// Create a starter prompt library in `dir`, keeping any file that already
+/// exists. Returns the files created.
+pub fn scaffold(dir: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let mut created = Vec::new();
+    for (name, content) in [
+        ("code-review.md", EXAMPLE_PROMPT),
+        (".shinkuroignore", IGNORE_FILE),
+        ("shinkuro.toml", CONFIG_FILE),
+    ] {
+        let path = dir.join(name);
+        if path.exists() {
+            tracing::warn!("{} already exists, skipping", path.display());
+            continue;
+        }
+        std::fs::write(&path, content)?;
+        created.push(path);
+    }
+    Ok(created)
+}
+
+/// Parse an argument for `shinkuro new`: a name, optionally followed by `=`
+/// and a default.
+pub fn parse_argument(spec: &str) -> Result<(String, Option<String>), String> {
+    let (name, default) = match spec.split_once('=') {
+        Some((name, default)) => (name, Some(default.to_string())),
+        None => (spec, None),
+    };
+    if !validate_variable_name(name) {
+        return Err(format!(
+            "'{}' is not a valid argument name, consider '{}'",
+            name,
+            naming::suggest_argument_name(name)
+        ));
+    }
+    Ok((name.to_string(), default))
+}
+
+/// A new prompt file named `name`, with a placeholder for each argument
+/// written the way `formatter` expects.
+pub fn new_prompt(
+    name: &str,
+    arguments: &[(String, Option<String>)],
+    formatter: &dyn Formatter,
+) -> Result<String> {
+    if !naming::validate_prompt_name(name) {
+        anyhow::bail!(
+            "'{}' is not a valid prompt name, consider '{}'",
+            name,
+            naming::suggest_prompt_name(name)
+        );
+    }
+    let title = title_case(name);
+    let mut text = format!(
+        "---\nname: {}\ntitle: {}\n# Shown to users when picking a prompt\ndescription: \"\"\n",
+        yaml(name),
+        yaml(&title)
+    );
+    if !arguments.is_empty() {
+        text.push_str("arguments:\n");
+    }
+    for (argument, default) in arguments {
+        text.push_str(&format!("  - name: {}\n    description: \"\"\n", argument));
+        if let Some(default) = default {
+            text.push_str(&format!("    default: {}\n", yaml(default)));
+        }
+    }
+    text.push_str(&format!("---\n\n# {}\n", title));
+    for (argument, _) in arguments {
+        text.push_str(&format!("\n{}\n", formatter.placeholder(argument)));
+    }
+    Ok(text)
+}
+
+/// A title for a prompt name, e.g. `Code Review` for `code-review`.
+pub fn title_case(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(['-', '_', '.'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+    words.join(" ")
+}
+
+/// `value` as a YAML scalar, quoted if needed.
+pub fn yaml(value: &str) -> String {
+    serde_yaml::to_string(value)
+        .map(|s| s.trim_end().to_string())
+        .unwrap_or_else(|_| format!("{:?}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter::BraceFormatter;
+    use crate::loader::{scan_markdown_files, ScanOptions};
+    use crate::prompt::MarkdownPrompt;
+    use crate::test_util::TempDir;
+    use clap::CommandFactory;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_scaffold() {
+        let dir = TempDir::new("init");
+        assert_eq!(scaffold(&dir).unwrap().len(), 3);
+        assert!(scaffold(&dir).unwrap().is_empty());
+        let config = crate::config::load(&dir.join("shinkuro.toml")).unwrap();
+        let matches = crate::Args::command().get_matches_from(["shinkuro"]);
+        crate::config::to_args(&config, &crate::Args::command(), &matches).unwrap();
+
+        let data = scan_markdown_files(&dir, &ScanOptions::default()).unwrap();
+        assert_eq!(data.len(), 1);
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data[0].clone(), Arc::new(BraceFormatter), false)
+                .unwrap();
+        assert_eq!(prompt.name, "code-review");
+        let args = HashMap::from([("code".to_string(), "fn main() {}".to_string())]);
+        let text = prompt.render(Some(args), &HashMap::new()).unwrap().text;
+        assert!(text.contains("written in the language it is written in"));
+        assert!(text.ends_with("Write `{` and `}` for literal braces."));
+    }
+
+    #[cfg(feature = "templating-jinja")]
+    #[test]
+    fn test_new_prompt() {
+        let arguments = vec![
+            parse_argument("diff").unwrap(),
+            parse_argument("style=concise: short").unwrap(),
+        ];
+        let text = new_prompt("code-review", &arguments, &BraceFormatter).unwrap();
+        assert_eq!(
+            text,
+            "---\nname: code-review\ntitle: Code Review\n# Shown to users when picking a prompt\ndescription: \"\"\narguments:\n  - name: diff\n    description: \"\"\n  - name: style\n    description: \"\"\n    default: 'concise: short'\n---\n\n# Code Review\n\n{diff}\n\n{style}\n"
+        );
+
+        let dir = TempDir::new("new-prompt");
+        let formatter = crate::formatter::FormatterRegistry::default()
+            .get("jinja")
+            .unwrap();
+        let text = new_prompt("review", &arguments, formatter.as_ref()).unwrap();
+        std::fs::write(dir.join("review.md"), text).unwrap();
+        let data = scan_markdown_files(&dir, &ScanOptions::default()).unwrap();
+        let prompt = MarkdownPrompt::from_prompt_data(data[0].clone(), formatter, false).unwrap();
+        let args = HashMap::from([("diff".to_string(), "+x".to_string())]);
+        let text = prompt.render(Some(args), &HashMap::new()).unwrap().text;
+        assert_eq!(text, "# Review\n\n+x\n\nconcise: short");
+
+        assert!(parse_argument("my-arg").is_err());
+        assert!(new_prompt("code review", &[], &BraceFormatter).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! Load prompts from local folders, git repositories, HTTP(S) and S3, and
+//! serve them over MCP.
+//!
+//! ```no_run
+//! use shinkuro::formatter::FormatterRegistry;
+//! use shinkuro::PromptLibrary;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let library = PromptLibrary::builder()
+//!     .folder("./prompts")
+//!     .git("https://github.com/owner/shared-prompts.git")
+//!     .formatter(FormatterRegistry::default().get("jinja")?)
+//!     .load()
+//!     .await?;
+//! for prompt in library.prompts() {
+//!     println!("{}: {}", prompt.name, prompt.description);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod archive;
+pub mod audit;
+pub mod budget;
+pub mod error;
+pub mod formatter;
+pub mod frontmatter;
+pub mod hooks;
+pub mod http;
+pub mod index;
+pub mod library;
+pub mod loader;
+pub mod mcp;
+pub mod model;
+pub mod mustache;
+pub mod naming;
+pub mod pack;
+pub mod paths;
+pub mod platform;
+pub mod prompt;
+pub mod registry;
+#[cfg(feature = "rmcp")]
+pub mod rmcp;
+pub mod s3;
+pub mod source;
+#[cfg(test)]
+mod test_util;
+
+pub use error::{Error, Result};
+pub use library::{PromptLibrary, PromptLibraryBuilder};
+pub use mcp::{McpServer, PromptProvider};
+pub use model::Argument;
+pub use prompt::MarkdownPrompt;
+pub use registry::PromptRegistry;
+pub use source::PromptSource;
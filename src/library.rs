@@ -0,0 +1,346 @@
+use crate::budget::RenderBudget;
+use crate::error::{Error, Result};
+use crate::formatter::{BraceFormatter, Formatter, FormatterRegistry};
+use crate::loader::{self, GitOptions, ScanOptions, Source, SourceStatus};
+use crate::mcp::McpServer;
+use crate::model::PromptData;
+use crate::naming::{self, DuplicatePolicy};
+use crate::prompt::MarkdownPrompt;
+use crate::registry::PromptRegistry;
+use crate::source::{BuiltinSource, PromptSource};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Prompts loaded from one or more sources, for embedding in other programs.
+/// Build one with [`PromptLibrary::builder`].
+pub struct PromptLibrary {
+    prompts: PromptRegistry,
+    sources: Vec<SourceStatus>,
+}
+
+impl PromptLibrary {
+    pub fn builder() -> PromptLibraryBuilder {
+        PromptLibraryBuilder::default()
+    }
+
+    /// The prompts ordered by name.
+    pub fn prompts(&self) -> impl Iterator<Item = &MarkdownPrompt> {
+        self.prompts.iter_sorted().map(|prompt| prompt.as_ref())
+    }
+
+    /// Look a prompt up by name or alias.
+    pub fn get(&self, name: &str) -> Option<&MarkdownPrompt> {
+        self.prompts.get(name).map(|prompt| prompt.as_ref())
+    }
+
+    /// The prompts for queries like [`PromptRegistry::find_by_tag`].
+    pub fn registry(&self) -> &PromptRegistry {
+        &self.prompts
+    }
+
+    /// How each source loaded, in the order they were added.
+    pub fn sources(&self) -> &[SourceStatus] {
+        &self.sources
+    }
+
+    /// An MCP server for the prompts, served on stdio with [`McpServer::run`].
+    pub fn into_server(self) -> McpServer {
+        let server = McpServer::new();
+        server.set_source_statuses(self.sources);
+        server.set_registry(self.prompts);
+        server
+    }
+}
+
+/// Sources and options for [`PromptLibrary`], with the same defaults as the
+/// command line.
+#[derive(Clone)]
+pub struct PromptLibraryBuilder {
+    /// Built-in sources get the options set on the builder when loading, so
+    /// they can be set in any order.
+    sources: Vec<BuilderSource>,
+    cache_dir: String,
+    git_options: GitOptions,
+    scan_options: ScanOptions,
+    formatter: Arc<dyn Formatter>,
+    auto_discover_args: bool,
+    on_duplicate: DuplicatePolicy,
+    render_budget: RenderBudget,
+}
+
+#[derive(Clone)]
+enum BuilderSource {
+    Builtin(Source),
+    Custom(Arc<dyn PromptSource>),
+}
+
+impl Default for PromptLibraryBuilder {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            cache_dir: "~/.shinkuro/remote".to_string(),
+            git_options: GitOptions {
+                clone_depth: Some(1),
+                single_branch: true,
+                ..Default::default()
+            },
+            scan_options: ScanOptions::default(),
+            formatter: Arc::new(BraceFormatter),
+            auto_discover_args: false,
+            on_duplicate: DuplicatePolicy::Overwrite,
+            render_budget: RenderBudget::default(),
+        }
+    }
+}
+
+impl PromptLibraryBuilder {
+    /// A local folder, prompt pack or archive.
+    pub fn folder(mut self, path: impl AsRef<Path>) -> Self {
+        self.sources.push(BuilderSource::Builtin(Source::local(
+            &path.as_ref().to_string_lossy(),
+        )));
+        self
+    }
+
+    /// A git repository, cloned into the cache directory.
+    pub fn git(mut self, url: impl Into<String>) -> Self {
+        self.sources.push(BuilderSource::Builtin(Source::Git {
+            url: url.into(),
+            folder: None,
+        }));
+        self
+    }
+
+    /// A source spec as accepted by `shinkuro serve`, like
+    /// `git@github.com:owner/repo.git#prompts` or `s3://bucket/prefix`.
+    pub fn source(mut self, spec: &str) -> Self {
+        self.sources
+            .push(BuilderSource::Builtin(Source::parse(spec)));
+        self
+    }
+
+    /// A custom backend, like a database or wiki.
+    pub fn add_source(mut self, source: impl PromptSource + 'static) -> Self {
+        self.sources.push(BuilderSource::Custom(Arc::new(source)));
+        self
+    }
+
+    /// How `{placeholders}` are written in prompts without a
+    /// `variable_format`, by default `brace`.
+    pub fn formatter(mut self, formatter: Arc<dyn Formatter>) -> Self {
+        self.formatter = formatter;
+        self
+    }
+
+    /// Where remote sources are cached, by default `~/.shinkuro/remote`.
+    pub fn cache_dir(mut self, dir: impl Into<String>) -> Self {
+        self.cache_dir = dir.into();
+        self
+    }
+
+    pub fn git_options(mut self, options: GitOptions) -> Self {
+        self.git_options = options;
+        self
+    }
+
+    pub fn scan_options(mut self, options: ScanOptions) -> Self {
+        self.scan_options = options;
+        self
+    }
+
+    /// Take arguments from the placeholders in the content instead of the
+    /// frontmatter.
+    pub fn auto_discover_args(mut self, enabled: bool) -> Self {
+        self.auto_discover_args = enabled;
+        self
+    }
+
+    /// What to do with prompts of the same name within a source, by default
+    /// overwrite. Later sources always shadow earlier ones.
+    pub fn on_duplicate(mut self, policy: DuplicatePolicy) -> Self {
+        self.on_duplicate = policy;
+        self
+    }
+
+    /// Size limits for rendered prompts, by default none. Prompts can set
+    /// their own with `max_render_bytes` and `max_render_tokens`.
+    pub fn render_budget(mut self, budget: RenderBudget) -> Self {
+        self.render_budget = budget;
+        self
+    }
+
+    /// Load every source, in order. Failing sources are skipped with a
+    /// warning unless all of them fail.
+    pub async fn load(self) -> Result<PromptLibrary> {
+        let mut loaded = Vec::new();
+        let mut sources = Vec::new();
+        let mut last_error = None;
+        for source in self.sources() {
+            match self.load_source(source.as_ref()).await {
+                Ok(data) => {
+                    sources.push(SourceStatus {
+                        source: source.to_string(),
+                        sync: None,
+                        prompts: data.len(),
+                        error: None,
+                    });
+                    loaded.push((source.to_string(), data));
+                }
+                Err(e) => {
+                    tracing::warn!(%source, error = %e, "Failed to load");
+                    sources.push(SourceStatus {
+                        source: source.to_string(),
+                        sync: None,
+                        prompts: 0,
+                        error: Some(e.to_string()),
+                    });
+                    last_error = Some(Error::Source {
+                        name: source.to_string(),
+                        error: e,
+                    });
+                }
+            }
+        }
+        if let Some(e) = last_error.filter(|_| loaded.is_empty()) {
+            return Err(e);
+        }
+
+        let formatters = FormatterRegistry::default();
+        let prompts = loader::merge_sources(loaded)
+            .into_iter()
+            .map(|data| self.build_prompt(data, &formatters))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(PromptLibrary {
+            prompts: PromptRegistry::new(prompts),
+            sources,
+        })
+    }
+
+    fn sources(&self) -> Vec<Arc<dyn PromptSource>> {
+        self.sources
+            .iter()
+            .map(|source| match source {
+                BuilderSource::Builtin(source) => Arc::new(BuiltinSource {
+                    source: source.clone(),
+                    cache_dir: self.cache_dir.clone(),
+                    git_options: self.git_options.clone(),
+                    scan_options: self.scan_options.clone(),
+                }) as Arc<dyn PromptSource>,
+                BuilderSource::Custom(source) => source.clone(),
+            })
+            .collect()
+    }
+
+    async fn load_source(&self, source: &dyn PromptSource) -> anyhow::Result<Vec<PromptData>> {
+        let mut prompts = source.load().await?;
+        naming::check_prompt_names(&mut prompts, false);
+        naming::resolve_duplicates(prompts, self.on_duplicate)
+    }
+
+    fn build_prompt(
+        &self,
+        data: PromptData,
+        formatters: &FormatterRegistry,
+    ) -> Result<MarkdownPrompt> {
+        let origin = data.origin();
+        let parse_error = |e: anyhow::Error| Error::Parse(format!("{} in {}", e, origin));
+        let formatter = match &data.variable_format {
+            Some(format) => formatters.get(format).map_err(parse_error)?,
+            None => self.formatter.clone(),
+        };
+        let formatter = formatter
+            .with_partials(&data.partials)
+            .map_err(parse_error)?
+            .unwrap_or(formatter);
+        let strict = data.strict_render.unwrap_or_default();
+        let mut prompt = MarkdownPrompt::from_prompt_data(data, formatter, self.auto_discover_args)
+            .map_err(parse_error)?;
+        prompt.strict = strict;
+        prompt.budget = RenderBudget {
+            max_bytes: prompt.budget.max_bytes.or(self.render_budget.max_bytes),
+            max_tokens: prompt.budget.max_tokens.or(self.render_budget.max_tokens),
+            on_exceed: self.render_budget.on_exceed,
+        };
+        Ok(prompt)
+    }
+}
+
+#[cfg(all(test, feature = "templating-jinja"))]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use async_trait::async_trait;
+    use std::fmt;
+
+    struct Database;
+
+    impl fmt::Display for Database {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "database")
+        }
+    }
+
+    #[async_trait]
+    impl PromptSource for Database {
+        async fn load(&self) -> anyhow::Result<Vec<PromptData>> {
+            Ok(vec![PromptData {
+                name: "greet".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let dir = TempDir::new("library");
+        let shared = dir.join("shared");
+        let personal = dir.join("personal");
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::create_dir_all(&personal).unwrap();
+        std::fs::write(
+            shared.join("review.md"),
+            "---\narguments:\n  - name: code\n---\nReview {{ code }}",
+        )
+        .unwrap();
+        std::fs::write(shared.join("commit.md"), "Write a commit message").unwrap();
+        std::fs::write(personal.join("commit.md"), "Write a short commit message").unwrap();
+
+        let library = PromptLibrary::builder()
+            .folder(&shared)
+            .folder(&personal)
+            .folder(dir.join("missing"))
+            .add_source(Database)
+            .formatter(FormatterRegistry::default().get("jinja").unwrap())
+            .load()
+            .await
+            .unwrap();
+        let names: Vec<_> = library.prompts().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["commit", "greet", "review"]);
+        assert_eq!(library.sources().len(), 4);
+        assert_eq!(library.sources()[3].source, "database");
+        assert_eq!(
+            library
+                .get("commit")
+                .unwrap()
+                .render(None, &Default::default())
+                .unwrap()
+                .text,
+            "Write a short commit message"
+        );
+        let args = [("code".to_string(), "fn main() {}".to_string())].into();
+        assert_eq!(
+            library
+                .get("review")
+                .unwrap()
+                .render(Some(args), &Default::default())
+                .unwrap()
+                .text,
+            "Review fn main() {}"
+        );
+
+        // Prompts can also come from the server alone
+        let library = PromptLibrary::builder().load().await.unwrap();
+        assert_eq!(library.prompts().count(), 0);
+    }
+}
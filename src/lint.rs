@@ -0,0 +1,302 @@
+use crate::formatter::{validate_variable_path, Formatter};
+use crate::model::PromptData;
+use crate::prompt::{estimate_tokens, BUILTIN_VARIABLES};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Prompts over this many estimated tokens are reported as overly long.
+const LONG_PROMPT_TOKENS: usize = 8000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// The lowest severity that fails `shinkuro lint`, for `--deny`.
+pub fn get_severity(level: &str) -> Result<Severity> {
+    match level {
+        "info" => Ok(Severity::Info),
+        "warning" | "warnings" => Ok(Severity::Warning),
+        "error" | "errors" => Ok(Severity::Error),
+        _ => anyhow::bail!("Unknown severity: {}", level),
+    }
+}
+
+/// A problem with a prompt, found by the check named `rule`.
+#[derive(Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    pub rule: &'static str,
+    pub prompt: String,
+    pub file: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {} ({}): {}",
+            self.severity, self.rule, self.file, self.prompt, self.message
+        )
+    }
+}
+
+/// Check prompts for likely mistakes. `formatter` gives each prompt's
+/// formatter and `format` the name of its variable format. Undeclared
+/// placeholders are allowed when arguments are discovered automatically.
+pub fn lint(
+    prompts: &[PromptData],
+    formatter: impl Fn(&PromptData) -> Result<Arc<dyn Formatter>>,
+    format: impl Fn(&PromptData) -> String,
+    auto_discover: bool,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut titles: HashMap<&str, &str> = HashMap::new();
+    for prompt in prompts {
+        let mut report = |severity, rule, message| {
+            findings.push(Finding {
+                severity,
+                rule,
+                prompt: prompt.name.clone(),
+                file: prompt.path.clone(),
+                message,
+            })
+        };
+
+        let placeholders = formatter(prompt).and_then(|formatter| {
+            Ok((
+                formatter.extract_arguments(&prompt.content)?,
+                formatter.inline_defaults(&prompt.content)?,
+            ))
+        });
+        match placeholders {
+            Ok((used, inline_defaults)) => {
+                for argument in &prompt.arguments {
+                    if !used.contains(&argument.name) {
+                        report(
+                            Severity::Warning,
+                            "unused-argument",
+                            format!("argument '{}' is never used", argument.name),
+                        );
+                    }
+                }
+                let mut undeclared: Vec<_> = used
+                    .iter()
+                    .filter(|name| {
+                        !auto_discover
+                            && !inline_defaults.contains_key(*name)
+                            && !BUILTIN_VARIABLES.contains(&name.as_str())
+                            && !prompt.arguments.iter().any(|a| a.name == **name)
+                    })
+                    .collect();
+                undeclared.sort();
+                for name in undeclared {
+                    report(
+                        Severity::Error,
+                        "undeclared-placeholder",
+                        format!("placeholder '{}' is not declared in arguments", name),
+                    );
+                }
+            }
+            Err(e) => report(Severity::Error, "invalid-template", e.to_string()),
+        }
+
+        if is_missing_description(prompt) {
+            report(
+                Severity::Warning,
+                "missing-description",
+                "no description, clients will show a generic one".to_string(),
+            );
+        }
+        if let Some(other) = titles.insert(&prompt.title, &prompt.name) {
+            report(
+                Severity::Warning,
+                "duplicate-title",
+                format!("title '{}' is also used by '{}'", prompt.title, other),
+            );
+        }
+        let tokens = estimate_tokens(&prompt.content);
+        if tokens > LONG_PROMPT_TOKENS {
+            report(
+                Severity::Info,
+                "long-prompt",
+                format!("about {} tokens, over {}", tokens, LONG_PROMPT_TOKENS),
+            );
+        }
+        for message in suspicious_escapes(&prompt.content, &format(prompt)) {
+            report(Severity::Warning, "suspicious-escape", message);
+        }
+    }
+    findings
+}
+
+/// Empty, or the description the loader makes up from the file's path.
+fn is_missing_description(prompt: &PromptData) -> bool {
+    let description = prompt.description.trim();
+    description.is_empty()
+        || description
+            .strip_prefix("Prompt from ")
+            .is_some_and(|file| prompt.path.ends_with(file))
+}
+
+/// Backslashes before placeholders in formats where a backslash doesn't
+/// escape them, so the placeholder is substituted after the backslash.
+fn suspicious_escapes(content: &str, format: &str) -> Vec<String> {
+    let (escape, literal) = match format {
+        "brace" => ("\\{", '{'),
+        "dollar" | "dollar-brace" => ("\\$", '$'),
+        _ => return Vec::new(),
+    };
+    let mut messages = Vec::new();
+    for (start, _) in content.match_indices(escape) {
+        let rest = &content[start + escape.len()..];
+        let name = match format {
+            "brace" => rest.split_once('}').map(|(name, _)| name),
+            _ => {
+                let rest = rest.strip_prefix('{').unwrap_or(rest);
+                let end = rest
+                    .find(|c: char| !unicode_ident::is_xid_continue(c))
+                    .unwrap_or(rest.len());
+                Some(&rest[..end])
+            }
+        };
+        if let Some(name) = name.filter(|name| validate_variable_path(name)) {
+            messages.push(format!(
+                "a backslash doesn't escape '{}', write '{}{}' for a literal '{}'",
+                name, literal, literal, literal
+            ));
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter::BraceFormatter;
+    use crate::model::Argument;
+
+    fn prompt(name: &str, content: &str, arguments: &[&str]) -> PromptData {
+        PromptData {
+            name: name.to_string(),
+            title: name.to_string(),
+            description: "Does things".to_string(),
+            path: format!("prompts/{}.md", name),
+            content: content.to_string(),
+            arguments: arguments
+                .iter()
+                .map(|name| Argument {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn rules(prompts: &[PromptData], auto_discover: bool) -> Vec<(String, &'static str)> {
+        lint(
+            prompts,
+            |_| Ok(Arc::new(BraceFormatter) as Arc<dyn Formatter>),
+            |_| "brace".to_string(),
+            auto_discover,
+        )
+        .into_iter()
+        .map(|f| (f.prompt, f.rule))
+        .collect()
+    }
+
+    #[test]
+    fn test_lint_arguments() {
+        let prompts = [prompt(
+            "review",
+            "Review {code} on {today} in {lang:-rust} for {user}",
+            &["code", "style"],
+        )];
+        assert_eq!(
+            rules(&prompts, false),
+            [
+                ("review".to_string(), "unused-argument"),
+                ("review".to_string(), "undeclared-placeholder"),
+            ]
+        );
+        assert_eq!(
+            rules(&prompts, true),
+            [("review".to_string(), "unused-argument")]
+        );
+        let broken = [prompt("broken", "{?open} never closed", &[])];
+        assert_eq!(
+            rules(&broken, false),
+            [("broken".to_string(), "invalid-template")]
+        );
+    }
+
+    #[test]
+    fn test_lint_metadata() {
+        let mut missing = prompt("a", "Hi", &[]);
+        missing.description = "Prompt from a.md".to_string();
+        let mut duplicate = prompt("b", "Hi", &[]);
+        duplicate.title = "a".to_string();
+        let long = prompt("c", &"word ".repeat(8000), &[]);
+        let findings = lint(
+            &[missing, duplicate, long],
+            |_| Ok(Arc::new(BraceFormatter) as Arc<dyn Formatter>),
+            |_| "brace".to_string(),
+            false,
+        );
+        let found: Vec<_> = findings
+            .iter()
+            .map(|f| (f.prompt.as_str(), f.rule, f.severity))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("a", "missing-description", Severity::Warning),
+                ("b", "duplicate-title", Severity::Warning),
+                ("c", "long-prompt", Severity::Info),
+            ]
+        );
+        assert_eq!(
+            findings[1].to_string(),
+            "warning[duplicate-title]: prompts/b.md (b): title 'a' is also used by 'a'"
+        );
+    }
+
+    #[test]
+    fn test_suspicious_escapes() {
+        assert_eq!(
+            suspicious_escapes("Use \\{name} and \\{ not this }", "brace"),
+            ["a backslash doesn't escape 'name', write '{{' for a literal '{'"]
+        );
+        assert_eq!(
+            suspicious_escapes("Costs \\$5 or \\$price, \\${total}", "dollar"),
+            [
+                "a backslash doesn't escape 'price', write '$$' for a literal '$'",
+                "a backslash doesn't escape 'total', write '$$' for a literal '$'",
+            ]
+        );
+        assert!(suspicious_escapes("\\{{name}}", "jinja").is_empty());
+    }
+
+    #[test]
+    fn test_get_severity() {
+        assert_eq!(get_severity("warnings").unwrap(), Severity::Warning);
+        assert_eq!(get_severity("info").unwrap(), Severity::Info);
+        assert!(get_severity("loud").is_err());
+    }
+}
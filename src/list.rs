@@ -0,0 +1,133 @@
+use crate::PromptRegistry;
+use serde_json::{json, Value};
+
+/// The prompts as a table of name, title, file and arguments, with optional
+/// arguments marked with `?`.
+pub fn table(prompts: &PromptRegistry) -> String {
+    let mut rows = vec![[
+        "NAME".to_string(),
+        "TITLE".to_string(),
+        "FILE".to_string(),
+        "ARGUMENTS".to_string(),
+    ]];
+    for prompt in prompts.iter_sorted() {
+        let arguments: Vec<_> = prompt
+            .arguments
+            .iter()
+            .map(|a| {
+                if a.required {
+                    a.name.clone()
+                } else {
+                    format!("{}?", a.name)
+                }
+            })
+            .collect();
+        rows.push([
+            prompt.name.clone(),
+            prompt.title.clone(),
+            prompt.path.clone(),
+            arguments.join(", "),
+        ]);
+    }
+    format_table(&rows)
+}
+
+/// Align rows into columns two spaces apart. The last column isn't padded.
+pub fn format_table<const N: usize>(rows: &[[String; N]]) -> String {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (width, cell) in widths.iter().zip(row) {
+            line.push_str(cell);
+            line.push_str(&" ".repeat(width - cell.chars().count() + 2));
+        }
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+pub fn json(prompts: &PromptRegistry) -> Value {
+    prompts
+        .iter_sorted()
+        .map(|prompt| {
+            json!({
+                "name": prompt.name,
+                "title": prompt.title,
+                "file": prompt.path,
+                "arguments": prompt.arguments.iter().map(|a| json!({
+                    "name": a.name,
+                    "description": a.description,
+                    "required": a.required,
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter::BraceFormatter;
+    use crate::model::{Argument, PromptData};
+    use crate::prompt::MarkdownPrompt;
+    use std::sync::Arc;
+
+    fn prompts() -> PromptRegistry {
+        let data = PromptData {
+            name: "code-review".to_string(),
+            title: "Code Review".to_string(),
+            path: "prompts/review.md".to_string(),
+            content: "Review {code} in {lang}".to_string(),
+            arguments: vec![
+                Argument {
+                    name: "code".to_string(),
+                    ..Default::default()
+                },
+                Argument {
+                    name: "lang".to_string(),
+                    default: Some("rust".to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let simple = PromptData {
+            name: "hi".to_string(),
+            title: "hi".to_string(),
+            path: "hi.md".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        };
+        PromptRegistry::new(
+            [data, simple].into_iter().map(|d| {
+                MarkdownPrompt::from_prompt_data(d, Arc::new(BraceFormatter), false).unwrap()
+            }),
+        )
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            table(&prompts()),
+            "NAME         TITLE        FILE               ARGUMENTS\n\
+             code-review  Code Review  prompts/review.md  code, lang?\n\
+             hi           hi           hi.md\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let json = json(&prompts());
+        assert_eq!(json[0]["file"], "prompts/review.md");
+        assert_eq!(json[0]["arguments"][1]["name"], "lang");
+        assert_eq!(json[0]["arguments"][1]["required"], false);
+        assert_eq!(json[1]["arguments"], serde_json::json!([]));
+    }
+}
@@ -1,58 +1,1131 @@
-pub mod formatter;
-mod loader;
-mod mcp;
-mod model;
-mod prompt;
+mod cache;
+mod config;
+mod doctor;
+mod export;
+mod import;
+mod init;
+mod lint;
+mod list;
+#[cfg(test)]
+mod test_util;
+mod validate;
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use formatter::{Formatter, FormatterRegistry};
+use model::PromptData;
+use shinkuro::{
+    audit, budget, formatter, frontmatter, hooks, http, loader, mcp, model, naming, pack, paths,
+    platform, prompt, source, PromptRegistry,
+};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "watch")]
+use tokio::sync::Mutex;
 
-#[derive(Parser)]
+/// The most recently loaded prompts, shared by every reload trigger so
+/// reloads never overlap and only changes are announced.
+#[cfg(feature = "watch")]
+type LastLoaded = Arc<Mutex<Vec<PromptData>>>;
+
+#[derive(Parser, Clone)]
 #[command(
     name = "shinkuro",
     about = "Universal prompt loader MCP server",
     version
 )]
 struct Args {
-    #[arg(long, env = "FOLDER")]
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// TOML or YAML file with default options, by default `shinkuro.toml` or
+    /// `shinkuro.yaml` in the current directory or `config.toml` or
+    /// `config.yaml` in `~/.config/shinkuro/`
+    #[arg(long, global = true, env = "SHINKURO_CONFIG")]
+    config: Option<PathBuf>,
+    /// Which logs to print to stderr, as a level like `debug` or `RUST_LOG`
+    /// directives like `shinkuro=trace`, by default `RUST_LOG` or `info`
+    #[arg(long, global = true, env = "LOG_LEVEL")]
+    log_level: Option<String>,
+    /// Log format: text or json (one object per line)
+    #[arg(long, global = true, env = "LOG_FORMAT", default_value = "text")]
+    log_format: String,
+    /// Profile from the config file whose options override its top-level ones
+    #[arg(long, global = true, env = "SHINKURO_PROFILE")]
+    profile: Option<String>,
+    #[arg(long, global = true, env = "FOLDER")]
     folder: Option<String>,
-    #[arg(long, env = "GIT_URL")]
+    #[arg(long, global = true, env = "GIT_URL")]
     git_url: Option<String>,
-    #[arg(long, env = "CACHE_DIR", default_value = "~/.shinkuro/remote")]
+    #[arg(long, global = true, env = "HTTP_URL")]
+    http_url: Option<String>,
+    #[arg(long, global = true, env = "S3_URL")]
+    s3_url: Option<String>,
+    /// Token for HTTPS git sources that have no credentials in their URL
+    #[arg(long, global = true, env = "GIT_TOKEN", hide_env_values = true)]
+    git_token: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "CACHE_DIR",
+        default_value = "~/.shinkuro/remote"
+    )]
     cache_dir: String,
-    #[arg(long, env = "AUTO_PULL")]
+    #[arg(long, global = true, env = "CACHE_MAX_AGE", value_parser = parse_duration)]
+    cache_max_age: Option<Duration>,
+    #[arg(long, global = true, env = "CACHE_MAX_SIZE", value_parser = parse_size)]
+    cache_max_size: Option<u64>,
+    #[arg(long, global = true, env = "AUTO_PULL")]
     auto_pull: bool,
-    #[arg(long, env = "VARIABLE_FORMAT", default_value = "brace")]
+    #[arg(long, global = true, env = "SOURCE", default_value = "git")]
+    source: String,
+    #[arg(long, global = true, env = "GIT_REF")]
+    git_ref: Option<String>,
+    #[arg(long, global = true, env = "VARIABLE_FORMAT", default_value = "brace")]
     variable_format: String,
-    #[arg(long, env = "AUTO_DISCOVER_ARGS")]
+    #[arg(long, global = true, env = "VAR_OPEN", requires = "var_close")]
+    var_open: Option<String>,
+    #[arg(long, global = true, env = "VAR_CLOSE", requires = "var_open")]
+    var_close: Option<String>,
+    #[arg(long, global = true, env = "STRICT_RENDER")]
+    strict_render: bool,
+    #[arg(long, global = true, env = "FILE_PLACEHOLDERS")]
+    file_placeholders: bool,
+    #[arg(long, global = true, env = "SKIP_CODE")]
+    skip_code: bool,
+    #[arg(long, global = true, env = "AUTO_DISCOVER_ARGS")]
     auto_discover_args: bool,
-    #[arg(long, env = "SKIP_FRONTMATTER")]
+    /// Fail to start, or keep the previous prompts on refresh, when a folder
+    /// is missing, a source fails to load or a file can't be read or parsed
+    #[arg(long, global = true, env = "STRICT_LOAD")]
+    strict_load: bool,
+    #[arg(long, global = true, env = "SKIP_FRONTMATTER")]
     skip_frontmatter: bool,
+    #[arg(
+        long,
+        global = true,
+        env = "EXTENSIONS",
+        value_delimiter = ',',
+        default_value = "md"
+    )]
+    extensions: Vec<String>,
+    #[arg(long, global = true, env = "ON_DUPLICATE", default_value = "overwrite")]
+    on_duplicate: String,
+    #[arg(long, global = true, env = "NAMESPACE_BY_DIR")]
+    namespace_by_dir: bool,
+    #[arg(long, global = true, env = "NAMESPACE_SEPARATOR", default_value = ".")]
+    namespace_separator: String,
+    #[arg(long, global = true, env = "FOLLOW_SYMLINKS")]
+    follow_symlinks: bool,
+    #[arg(long, global = true, env = "MAX_DEPTH")]
+    max_depth: Option<usize>,
+    #[arg(long, global = true, env = "MAX_FILE_SIZE", default_value = "4MB", value_parser = parse_size)]
+    max_file_size: u64,
+    #[arg(long, global = true, env = "INCLUDE", value_delimiter = ',')]
+    include: Vec<String>,
+    #[arg(long, global = true, env = "EXCLUDE", value_delimiter = ',')]
+    exclude: Vec<String>,
+    #[arg(long, global = true, env = "REFRESH_INTERVAL", value_parser = parse_duration)]
+    refresh_interval: Option<Duration>,
+    #[arg(long, global = true, env = "AUTOFIX_NAMES")]
+    autofix_names: bool,
+    #[arg(long, global = true, env = "CLONE_DEPTH", default_value_t = 1)]
+    clone_depth: u32,
+    #[arg(long, global = true, env = "SINGLE_BRANCH", default_value_t = true, action = clap::ArgAction::Set)]
+    single_branch: bool,
+    #[arg(long, global = true, env = "SPARSE_CHECKOUT")]
+    sparse_checkout: bool,
+    #[arg(long, global = true, env = "RECURSE_SUBMODULES")]
+    recurse_submodules: bool,
+    /// JSONL file to append a line to for every `prompts/get` request
+    #[arg(long, global = true, env = "AUDIT_LOG")]
+    audit_log: Option<PathBuf>,
+    #[arg(long, global = true, env = "PROXY")]
+    proxy: Option<String>,
+    #[arg(long, global = true, env = "POST_RENDER_CMD")]
+    post_render_cmd: Option<String>,
+    /// Run `post_render_cmd` from prompt frontmatter, which otherwise is ignored
+    #[arg(long, global = true, env = "ALLOW_FRONTMATTER_HOOKS")]
+    allow_frontmatter_hooks: bool,
+    /// Environment variables `env:` fallbacks may read, e.g. `LANG` or `APP_*`; others are ignored
+    #[arg(long, global = true, env = "ALLOW_ENV_FALLBACK", value_delimiter = ',')]
+    allow_env_fallback: Vec<String>,
+    #[arg(long, global = true, env = "POST_RENDER_TIMEOUT", default_value = "10s", value_parser = parse_duration)]
+    post_render_timeout: Duration,
+    #[arg(
+        long,
+        global = true,
+        env = "POST_RENDER_ON_FAILURE",
+        default_value = "error"
+    )]
+    post_render_on_failure: String,
+    #[arg(long, global = true, env = "MAX_RENDER_BYTES", value_parser = parse_size)]
+    max_render_bytes: Option<u64>,
+    #[arg(long, global = true, env = "MAX_RENDER_TOKENS")]
+    max_render_tokens: Option<usize>,
+    #[arg(long, global = true, env = "ON_OVER_BUDGET", default_value = "reject")]
+    on_over_budget: String,
+    #[arg(
+        long = "extra-source",
+        global = true,
+        env = "EXTRA_SOURCES",
+        value_delimiter = ','
+    )]
+    extra_sources: Vec<String>,
+    #[arg(long, global = true, env = "FILTER_TAG", value_delimiter = ',')]
+    filter_tag: Vec<String>,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
+#[derive(Subcommand, Clone)]
+enum Command {
+    /// Load all sources and write them into a single prompt pack
+    Bundle {
+        /// Output file, conventionally with the `.skpack` extension
+        output: PathBuf,
+    },
+    /// Inspect, clear or update cached remote sources
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Check git, SSH, the cache, remote sources and the configured prompts,
+    /// suggesting fixes and exiting with status 1 if a check fails
+    Doctor,
+    /// Convert the loaded prompts into another tool's prompt files
+    Export {
+        /// `claude-commands` for Claude Code slash commands, `cursor` for
+        /// Cursor rules or `openai-jsonl` for a JSONL file of chat templates
+        #[arg(long, default_value = "claude-commands")]
+        format: String,
+        /// Folder to write the files to, by default `.claude/commands` or
+        /// `.cursor/rules`, or the file for `openai-jsonl` (`prompts.jsonl`)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Convert another tool's prompt files into prompts, in `--out`, `--folder`
+    /// or the current directory
+    Import {
+        /// `claude-commands` for Claude Code slash commands, `fabric` for
+        /// Fabric patterns or `copilot` for VS Code Copilot prompt files
+        #[arg(long, default_value = "claude-commands")]
+        from: String,
+        /// Folder with the files to convert, e.g. `~/.claude/commands`,
+        /// Fabric's `patterns` folder or `.github/prompts`
+        dir: PathBuf,
+        /// Folder to write the prompts to
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Create a starter prompt library with an example prompt
+    Init {
+        /// Folder to create it in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Create a prompt file with frontmatter and placeholders for its arguments,
+    /// in `--folder` or the current directory
+    New {
+        /// Name of the prompt, also used for the file name
+        name: String,
+        /// An argument, with an optional default as `NAME=DEFAULT`
+        #[arg(long = "arg", value_name = "NAME[=DEFAULT]", value_parser = init::parse_argument)]
+        args: Vec<(String, Option<String>)>,
+    },
+    /// Check prompts for likely mistakes, exiting with status 1 if any is at
+    /// least as severe as `--deny`
+    Lint {
+        /// `error`, `warnings` or `info`
+        #[arg(long, default_value = "error")]
+        deny: String,
+    },
+    /// Print the prompts that would be served, with their arguments
+    List {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Render a prompt and print it, to test substitutions without a client
+    Render {
+        /// Name of the prompt
+        name: String,
+        /// An argument value, repeated for each argument
+        #[arg(long = "arg", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        args: Vec<(String, String)>,
+    },
+    /// Serve prompts over MCP, the default without a subcommand
+    Serve {
+        /// Sources to load after the options' sources, as for `--extra-source`
+        #[arg(value_name = "SOURCE")]
+        sources: Vec<String>,
+    },
+    /// Check every prompt without serving them, printing a JSON report and
+    /// exiting with status 1 if there are errors
+    Validate,
+}
+
+#[derive(Subcommand, Clone)]
+enum CacheCommand {
+    /// List cached sources with their size, last use and last update
+    List,
+    /// Print the cache folder, or where a source is cached
+    Path {
+        /// A source URL or archive, as for `--extra-source`
+        url: Option<String>,
+    },
+    /// Remove a cached source, or every cached source
+    Clear {
+        /// A source URL or archive, or an entry path from `shinkuro cache list`
+        url: Option<String>,
+    },
+    /// Fetch the latest version of a source, or of every configured source
+    Update {
+        /// A source URL, as for `--extra-source`
+        url: Option<String>,
+    },
+}
+
+/// Parse the command line, filling options it and the environment don't set
+/// from the config file.
+fn parse_args() -> Result<Args> {
+    let argv: Vec<String> = std::env::args().collect();
+    let matches = Args::command().get_matches_from(&argv);
+    let path = match matches.get_one::<PathBuf>("config") {
+        Some(path) => Some(path.clone()),
+        None => config::discover(&std::env::current_dir()?),
+    };
+    let Some(path) = path else {
+        if let Some(profile) = matches.get_one::<String>("profile") {
+            anyhow::bail!("Profile {} needs a config file", profile);
+        }
+        return Ok(Args::from_arg_matches(&matches)?);
+    };
+    let options = config::load(&path)?;
+    let options = config::select_profile(options, matches.get_one::<String>("profile"))
+        .map_err(|e| anyhow::anyhow!("{} ({})", e, path.display()))?;
+    let extra = config::to_args(&options, &Args::command(), &matches)
+        .map_err(|e| anyhow::anyhow!("{} ({})", e, path.display()))?;
+    let argv: Vec<String> = argv[..1]
+        .iter()
+        .cloned()
+        .chain(extra)
+        .chain(argv[1..].iter().cloned())
+        .collect();
+    Ok(Args::parse_from(argv))
+}
+
+/// Log to stderr, keeping stdout for the MCP protocol and command output.
+/// The json format writes one object per event with its timestamp, level,
+/// module and fields, like the prompt file and error.
+fn init_logging(level: Option<&str>, format: &str) -> Result<()> {
+    use std::io::IsTerminal;
+    use tracing_subscriber::EnvFilter;
+
+    let filter = match level {
+        Some(level) => EnvFilter::try_new(level)
+            .map_err(|e| anyhow::anyhow!("Invalid log level {}: {}", level, e))?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match format {
+        "text" => builder
+            .with_ansi(std::io::stderr().is_terminal())
+            .with_target(false)
+            .without_time()
+            .init(),
+        "json" => builder.json().flatten_event(true).init(),
+        _ => anyhow::bail!("Unknown log format: {}", format),
+    }
+    Ok(())
+}
+
+/// Parse durations like `30s`, `15m`, `2h` or `1d`. A bare number means seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", s))?;
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => return Err(format!("Invalid duration unit in: {}", s)),
+    };
+    let secs = value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Duration too large: {}", s))?;
+    if secs == 0 {
+        return Err("Duration must be greater than zero".to_string());
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// Parse `key=value` pairs, splitting at the first `=`.
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", s))
+}
 
-    let folder_path = loader::get_folder_path(
-        args.folder.as_deref(),
-        args.git_url.as_deref(),
+/// Parse sizes like `512KB`, `4MB` or `1GB` (powers of 1024). A bare number means bytes.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value.parse().map_err(|_| format!("Invalid size: {}", s))?;
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("Invalid size unit in: {}", s)),
+    };
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Size too large: {}", s))
+}
+
+/// The primary source from `--folder`/`--git-url`, then `--http-url` and `--s3-url`, followed by
+/// any `--extra-source`s and the sources given to `shinkuro serve`.
+fn sources(args: &Args) -> Result<Vec<loader::Source>> {
+    let mut sources = Vec::new();
+    if let Some(url) = &args.git_url {
+        sources.push(loader::Source::Git {
+            url: url.clone(),
+            folder: args.folder.clone(),
+        });
+    } else if let Some(folder) = &args.folder {
+        sources.push(loader::Source::local(folder));
+    }
+    if let Some(url) = &args.http_url {
+        sources.push(loader::Source::Http(url.clone()));
+    }
+    if let Some(url) = &args.s3_url {
+        sources.push(loader::Source::S3(url.clone()));
+    }
+    sources.extend(args.extra_sources.iter().map(|s| loader::Source::parse(s)));
+    if let Some(Command::Serve { sources: extra }) = &args.command {
+        sources.extend(extra.iter().map(|s| loader::Source::parse(s)));
+    }
+    if sources.is_empty() {
+        anyhow::bail!("Either folder, git-url, http-url, s3-url or a source must be provided");
+    }
+    Ok(sources)
+}
+
+/// Resolve a single source (cloning or pulling if needed) and scan it.
+fn load_source(
+    source: &loader::Source,
+    args: &Args,
+    auto_pull: bool,
+) -> Result<(Vec<PromptData>, loader::SyncStatus)> {
+    let _span = tracing::debug_span!("source", %source).entered();
+    let (mut prompts, status) = builtin_source(source, args, auto_pull)?.load_blocking()?;
+    let mode = loader::get_fetch_mode(&args.source)?;
+    if let Some(path) = loader::cache_path(source, &args.cache_dir, mode, args.git_ref.as_deref())?
+    {
+        cache::touch(&args.cache_dir, &path);
+    }
+    loader::filter_by_tags(&mut prompts, &args.filter_tag);
+    naming::check_prompt_names(&mut prompts, args.autofix_names);
+    let policy = naming::get_duplicate_policy(&args.on_duplicate)?;
+    let prompts = naming::resolve_duplicates(prompts, policy)?;
+    Ok((prompts, status))
+}
+
+/// Clone, pull or download a non-pack source as needed.
+fn resolve_folder(
+    source: &loader::Source,
+    args: &Args,
+    auto_pull: bool,
+) -> Result<loader::ResolvedFolder> {
+    let folder = builtin_source(source, args, auto_pull)?.resolve()?;
+    cache::touch(&args.cache_dir, &folder.path);
+    Ok(folder)
+}
+
+fn builtin_source(
+    source: &loader::Source,
+    args: &Args,
+    auto_pull: bool,
+) -> Result<source::BuiltinSource> {
+    Ok(source::BuiltinSource {
+        source: source.clone(),
+        cache_dir: args.cache_dir.clone(),
+        git_options: loader::GitOptions {
+            mode: loader::get_fetch_mode(&args.source)?,
+            git_ref: args.git_ref.clone(),
+            auto_pull,
+            sparse_checkout: args.sparse_checkout,
+            recurse_submodules: args.recurse_submodules,
+            proxy: args.proxy.clone(),
+            // 0 means the full history
+            clone_depth: Some(args.clone_depth).filter(|&d| d > 0),
+            single_branch: args.single_branch,
+            token: args.git_token.clone(),
+        },
+        scan_options: scan_options(args),
+    })
+}
+
+fn scan_options(args: &Args) -> loader::ScanOptions {
+    loader::ScanOptions {
+        skip_frontmatter: args.skip_frontmatter,
+        extensions: args.extensions.clone(),
+        include: args.include.clone(),
+        exclude: args.exclude.clone(),
+        namespace_separator: args
+            .namespace_by_dir
+            .then(|| args.namespace_separator.clone()),
+        follow_symlinks: args.follow_symlinks,
+        max_depth: args.max_depth,
+        max_file_size: args.max_file_size,
+        strict: args.strict_load,
+        index_dir: Some(paths::expand(&args.cache_dir).join("index")),
+        allow_env: args.allow_env_fallback.clone(),
+    }
+}
+
+/// Check every source for `shinkuro validate`. Prompt packs were checked
+/// when they were bundled, so only their names are checked again.
+fn validate_sources(args: &Args) -> Result<validate::Report> {
+    let mut report = validate::Report::default();
+    for source in sources(args)? {
+        let check = match &source {
+            loader::Source::Pack(path) => {
+                let prompts = pack::read_pack(&paths::expand(path))?;
+                loader::FolderCheck {
+                    files: 1,
+                    prompts,
+                    diagnostics: Vec::new(),
+                }
+            }
+            _ => {
+                let folder = resolve_folder(&source, args, args.auto_pull)?;
+                loader::check_folder(&folder.path, &scan_options(args))?
+            }
+        };
+        report.add(check, |prompt| build_prompts(vec![prompt], args).map(drop));
+    }
+    Ok(report)
+}
+
+/// Where `source` is cached, failing for sources that aren't cached.
+fn source_cache_path(source: &str, args: &Args) -> Result<PathBuf> {
+    let mode = loader::get_fetch_mode(&args.source)?;
+    loader::cache_path(
+        &loader::Source::parse(source),
         &args.cache_dir,
-        args.auto_pull,
-    )?;
+        mode,
+        args.git_ref.as_deref(),
+    )?
+    .ok_or_else(|| anyhow::anyhow!("{} is read in place, not cached", source))
+}
 
-    let formatter = formatter::get_formatter(&args.variable_format)?;
-    let prompts = loader::scan_markdown_files(&folder_path, args.skip_frontmatter)?;
+fn run_cache_command(command: &CacheCommand, args: &Args) -> Result<()> {
+    match command {
+        CacheCommand::List => print!("{}", cache::table(&cache::list(&args.cache_dir))),
+        CacheCommand::Path { url: None } => {
+            println!("{}", cache::root(&args.cache_dir).display())
+        }
+        CacheCommand::Path { url: Some(url) } => {
+            println!("{}", source_cache_path(url, args)?.display())
+        }
+        CacheCommand::Clear { url } => {
+            let path = match url {
+                // Entry paths from `cache list` are relative to the cache root
+                Some(url) if cache::root(&args.cache_dir).join(url).exists() => {
+                    Some(PathBuf::from(url))
+                }
+                Some(url) => Some(source_cache_path(url, args)?),
+                None => None,
+            };
+            let removed = cache::clear(&args.cache_dir, path.as_deref())?;
+            eprintln!("Removed {} cache entries", removed);
+        }
+        CacheCommand::Update { url } => {
+            let sources = match url {
+                Some(url) => vec![loader::Source::parse(url)],
+                None => sources(args)?,
+            };
+            for source in sources {
+                if matches!(source, loader::Source::Folder(_) | loader::Source::Pack(_)) {
+                    continue;
+                }
+                let folder = resolve_folder(&source, args, true)?;
+                if folder.status == loader::SyncStatus::Stale {
+                    anyhow::bail!("Failed to update {}", source);
+                }
+                eprintln!("Updated {} in {}", source, folder.path.display());
+            }
+        }
+    }
+    Ok(())
+}
 
-    let mut server = mcp::McpServer::new();
-    for prompt_data in prompts {
-        let prompt = prompt::MarkdownPrompt::from_prompt_data(
-            prompt_data,
-            formatter.clone(),
+/// Run the checks for `shinkuro doctor`. Git, SSH and the clock are only
+/// checked when a source needs them.
+fn doctor_checks(args: &Args) -> Vec<doctor::Check> {
+    let sources = sources(args).unwrap_or_default();
+    let git_urls: Vec<&str> = sources
+        .iter()
+        .filter_map(|source| match source {
+            loader::Source::Git { url, .. } => Some(url.as_str()),
+            _ => None,
+        })
+        .collect();
+    let proxy = args.proxy.as_deref();
+    let mut checks = Vec::new();
+    if !git_urls.is_empty() {
+        checks.push(doctor::check_git());
+    }
+    if git_urls.iter().any(|url| doctor::is_ssh_url(url)) {
+        checks.push(doctor::check_ssh_agent());
+    }
+    checks.push(doctor::check_cache_dir(&paths::expand(&args.cache_dir)));
+    checks.extend(
+        sources
+            .iter()
+            .filter_map(|s| doctor::check_remote(s, proxy, args.git_token.as_deref())),
+    );
+    if args.auto_pull || args.refresh_interval.is_some() {
+        let host = sources.iter().find_map(|source| match source {
+            loader::Source::Git { url, .. } => loader::git_host(url).ok(),
+            loader::Source::Http(url) => url::Url::parse(url).ok()?.host_str().map(str::to_string),
+            _ => None,
+        });
+        if let Some(host) = host {
+            checks.push(doctor::check_clock(&host, proxy));
+        }
+    }
+    checks.push(doctor::check_prompts(validate_sources(args)));
+    checks
+}
+
+/// Load all sources concurrently. A failing source is reported in its status
+/// instead of preventing the others from loading, unless every source fails.
+/// Sources are merged with `loader::merge_sources`, then prompts are sorted by
+/// name so consecutive loads can be compared.
+async fn load_prompt_data(
+    args: &Args,
+    auto_pull: bool,
+) -> Result<(Vec<PromptData>, Vec<loader::SourceStatus>)> {
+    let tasks: Vec<_> = sources(args)?
+        .into_iter()
+        .map(|source| {
+            let args = args.clone();
+            platform::spawn_blocking(move || {
+                let result = load_source(&source, &args, auto_pull);
+                (source, result)
+            })
+        })
+        .collect();
+
+    let mut loaded = Vec::new();
+    let mut statuses = Vec::new();
+    let mut last_error = None;
+    for task in tasks {
+        let (source, result) = task.await?;
+        match result {
+            Ok((data, sync)) => {
+                statuses.push(loader::SourceStatus {
+                    source: source.to_string(),
+                    sync: Some(sync),
+                    prompts: data.len(),
+                    error: None,
+                });
+                loaded.push((source.to_string(), data));
+            }
+            Err(e) => {
+                tracing::warn!(%source, error = %e, "Failed to load");
+                statuses.push(loader::SourceStatus {
+                    source: source.to_string(),
+                    sync: None,
+                    prompts: 0,
+                    error: Some(e.to_string()),
+                });
+                last_error = Some(e);
+            }
+        }
+    }
+    if let Some(e) = last_error {
+        if args.strict_load || statuses.iter().all(|s| s.error.is_some()) {
+            return Err(e);
+        }
+    }
+
+    let mut prompts = loader::merge_sources(loaded);
+    prompts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok((prompts, statuses))
+}
+
+/// The formatter for `--variable-format`, or for `--var-open` and `--var-close`.
+fn default_formatter(args: &Args, formatters: &FormatterRegistry) -> Result<Arc<dyn Formatter>> {
+    let formatter: Arc<dyn Formatter> = match (&args.var_open, &args.var_close) {
+        (Some(open), Some(close)) => Arc::new(formatter::DelimitedFormatter::new(open, close)?),
+        _ => formatters.get(&args.variable_format)?,
+    };
+    Ok(if args.skip_code {
+        Arc::new(formatter::SkipCodeFormatter(formatter))
+    } else {
+        formatter
+    })
+}
+
+/// The formatter for a prompt's `variable_format`, falling back to `default`,
+/// with the prompt's partials.
+fn prompt_formatter(
+    prompt_data: &PromptData,
+    args: &Args,
+    formatters: &FormatterRegistry,
+    default: &Arc<dyn Formatter>,
+) -> Result<Arc<dyn Formatter>> {
+    let formatter = match &prompt_data.variable_format {
+        Some(format) => match formatters.get(format) {
+            Ok(formatter) if args.skip_code => Arc::new(formatter::SkipCodeFormatter(formatter)),
+            Ok(formatter) => formatter,
+            Err(e) if args.strict_load => return Err(e),
+            Err(_) => {
+                tracing::warn!(
+                    file = %prompt_data.origin(),
+                    "Unknown variable format '{}', using the default",
+                    format
+                );
+                default.clone()
+            }
+        },
+        None => default.clone(),
+    };
+    Ok(formatter
+        .with_partials(&prompt_data.partials)?
+        .unwrap_or(formatter))
+}
+
+fn build_prompts(data: Vec<PromptData>, args: &Args) -> Result<Vec<prompt::MarkdownPrompt>> {
+    let formatters = FormatterRegistry::default();
+    let default = default_formatter(args, &formatters)?;
+    let on_failure = hooks::get_failure_policy(&args.post_render_on_failure)?;
+    let on_exceed = budget::get_budget_policy(&args.on_over_budget)?;
+    data.into_iter()
+        .map(|prompt_data| {
+            let origin = prompt_data.origin();
+            let frontmatter_cmd = prompt_data.post_render_cmd.clone();
+            if frontmatter_cmd.is_some() && !args.allow_frontmatter_hooks {
+                tracing::warn!(
+                    file = %origin,
+                    "Ignoring post_render_cmd in frontmatter without --allow-frontmatter-hooks"
+                );
+            }
+            let post_render_cmd = frontmatter_cmd
+                .filter(|_| args.allow_frontmatter_hooks)
+                .or_else(|| args.post_render_cmd.clone());
+            let formatter = prompt_formatter(&prompt_data, args, &formatters, &default)?;
+            let strict = prompt_data.strict_render.unwrap_or(args.strict_render);
+            let file_root = prompt_data.root.clone().filter(|_| args.file_placeholders);
+            let mut prompt = prompt::MarkdownPrompt::from_prompt_data(
+                prompt_data,
+                formatter,
+                args.auto_discover_args,
+            )
+            .map_err(|e| anyhow::anyhow!("{} in {}", e, origin))?;
+            prompt.strict = strict;
+            prompt.file_root = file_root;
+            prompt.post_render = post_render_cmd.map(|command| hooks::PostRenderHook {
+                command,
+                timeout: args.post_render_timeout,
+                on_failure,
+            });
+            prompt.budget = budget::RenderBudget {
+                max_bytes: prompt.budget.max_bytes.or(args.max_render_bytes),
+                max_tokens: prompt.budget.max_tokens.or(args.max_render_tokens),
+                on_exceed,
+            };
+            Ok(prompt)
+        })
+        .collect()
+}
+
+/// Re-run the loader pipeline and swap the served prompts if anything changed.
+#[cfg(feature = "watch")]
+async fn reload(server: &mcp::McpServer, args: &Args, last: &LastLoaded) -> Result<()> {
+    let mut last = last.lock().await;
+    let (data, statuses) = load_prompt_data(args, true).await?;
+    server.set_source_statuses(statuses);
+    if data == *last {
+        return Ok(());
+    }
+
+    let prompts = build_prompts(data.clone(), args)?;
+    server.set_prompts(prompts);
+    *last = data;
+    server.notify_prompts_list_changed().await
+}
+
+#[cfg(feature = "watch")]
+async fn refresh_periodically(
+    server: mcp::McpServer,
+    args: Args,
+    interval: Duration,
+    last: LastLoaded,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    // The first tick completes immediately, but prompts were just loaded.
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if let Err(e) = reload(&server, &args, &last).await {
+            tracing::warn!(error = %e, "Failed to refresh prompts");
+        }
+    }
+}
+
+#[cfg(all(feature = "watch", unix))]
+async fn reload_on_sighup(server: mcp::McpServer, args: Args, last: LastLoaded) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        tracing::info!("Received SIGHUP, reloading prompts");
+        if let Err(e) = reload(&server, &args, &last).await {
+            tracing::warn!(error = %e, "Failed to reload prompts");
+        }
+    }
+    Ok(())
+}
+
+// WASI has no threads to run a multi-threaded runtime on
+#[cfg_attr(not(target_os = "wasi"), tokio::main)]
+#[cfg_attr(target_os = "wasi", tokio::main(flavor = "current_thread"))]
+async fn main() -> Result<()> {
+    let args = parse_args()?;
+    init_logging(args.log_level.as_deref(), &args.log_format)?;
+
+    if let Some(Command::Init { dir }) = &args.command {
+        for path in init::scaffold(dir)? {
+            eprintln!("Created {}", path.display());
+        }
+        eprintln!("Serve it with: shinkuro serve {}", dir.display());
+        return Ok(());
+    }
+    if let Some(Command::New {
+        name,
+        args: arguments,
+    }) = &args.command
+    {
+        let formatter = default_formatter(&args, &FormatterRegistry::default())?;
+        let text = init::new_prompt(name, arguments, formatter.as_ref())?;
+        let folder = args.folder.as_deref().unwrap_or(".");
+        let folder = paths::expand(folder);
+        let path = folder.join(format!("{}.md", name));
+        if path.exists() {
+            anyhow::bail!("{} already exists", path.display());
+        }
+        std::fs::create_dir_all(&folder)?;
+        std::fs::write(&path, text)?;
+        eprintln!("Created {}", path.display());
+        return Ok(());
+    }
+
+    if let Some(Command::Cache { command }) = &args.command {
+        let cache_args = args.clone();
+        let command = command.clone();
+        return platform::spawn_blocking(move || run_cache_command(&command, &cache_args)).await?;
+    }
+    if let Some(Command::Doctor) = &args.command {
+        let doctor_args = args.clone();
+        let checks = platform::spawn_blocking(move || doctor_checks(&doctor_args)).await?;
+        for check in &checks {
+            println!("{}", check);
+        }
+        let failed = checks
+            .iter()
+            .filter(|c| c.status == doctor::Status::Fail)
+            .count();
+        if failed > 0 {
+            println!("{} checks failed", failed);
+            std::process::exit(1);
+        }
+        println!("All checks passed");
+        return Ok(());
+    }
+
+    if let Some(Command::Import { from, dir, out }) = &args.command {
+        let format = import::get_import_format(from)?;
+        let formatter = default_formatter(&args, &FormatterRegistry::default())?;
+        let out = match out {
+            Some(out) => out.clone(),
+            None => paths::expand(args.folder.as_deref().unwrap_or(".")),
+        };
+        let dir = paths::expand(&dir.to_string_lossy());
+        let written = import::import(&dir, format, &out, formatter.as_ref())?;
+        eprintln!("Imported {} prompts into {}", written.len(), out.display());
+        return Ok(());
+    }
+    if args.cache_max_age.is_some() || args.cache_max_size.is_some() {
+        if let Err(e) = cache::prune(&args.cache_dir, args.cache_max_age, args.cache_max_size) {
+            tracing::warn!(error = %e, "Failed to prune cache");
+        }
+    }
+
+    if let Some(Command::Validate) = &args.command {
+        let report = validate_sources(&args)?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        if !report.valid {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    #[cfg(not(feature = "watch"))]
+    if args.refresh_interval.is_some() && matches!(args.command, None | Some(Command::Serve { .. }))
+    {
+        anyhow::bail!("--refresh-interval needs shinkuro built with the `watch` feature");
+    }
+
+    let started = std::time::Instant::now();
+    let (data, statuses) = load_prompt_data(&args, args.auto_pull).await?;
+    let elapsed = started.elapsed();
+
+    if let Some(Command::Bundle { output }) = &args.command {
+        let count = pack::write_pack(output, &data)?;
+        eprintln!("Bundled {} prompts into {}", count, output.display());
+        return Ok(());
+    }
+    if let Some(Command::Render { name, args: values }) = &args.command {
+        let prompts = PromptRegistry::new(build_prompts(data, &args)?);
+        let prompt = prompts
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown prompt: {}", name))?;
+        let values = values.iter().cloned().collect();
+        let rendered = prompt.render(Some(values), &HashMap::new())?;
+        println!("{}", rendered.text);
+        return Ok(());
+    }
+    if let Some(Command::Lint { deny }) = &args.command {
+        let deny = lint::get_severity(deny)?;
+        let formatters = FormatterRegistry::default();
+        let default = default_formatter(&args, &formatters)?;
+        let findings = lint::lint(
+            &data,
+            |prompt| prompt_formatter(prompt, &args, &formatters, &default),
+            |prompt| match (&args.var_open, &prompt.variable_format) {
+                (Some(_), None) => "custom".to_string(),
+                (_, Some(format)) => format.clone(),
+                (None, None) => args.variable_format.clone(),
+            },
             args.auto_discover_args,
-        )?;
-        server.add_prompt(prompt);
+        );
+        for finding in &findings {
+            println!("{}", finding);
+        }
+        let count = |severity| findings.iter().filter(|f| f.severity == severity).count();
+        eprintln!(
+            "{} errors, {} warnings, {} info",
+            count(lint::Severity::Error),
+            count(lint::Severity::Warning),
+            count(lint::Severity::Info)
+        );
+        if findings.iter().any(|f| f.severity >= deny) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Export { format, out }) = &args.command {
+        let format = export::get_export_format(format)?;
+        let out = out
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format.default_out()));
+        let prompts = build_prompts(data, &args)?;
+        let written = export::export(&prompts, format, &out)?;
+        eprintln!("Exported {} prompts to {}", written.len(), out.display());
+        return Ok(());
+    }
+    if let Some(Command::List { json }) = &args.command {
+        let prompts = PromptRegistry::new(build_prompts(data, &args)?);
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&list::json(&prompts))?);
+        } else {
+            print!("{}", list::table(&prompts));
+        }
+        return Ok(());
+    }
+
+    for status in &statuses {
+        if let Some(sync) = status.sync {
+            tracing::info!(
+                "Loaded {} prompts from {} ({:?})",
+                status.prompts,
+                status.source,
+                sync
+            );
+        }
+    }
+
+    tracing::info!(
+        "Loaded {} prompts from {} sources in {:.2?}",
+        data.len(),
+        statuses.len(),
+        elapsed
+    );
+
+    let mut server = mcp::McpServer::new();
+    server.set_source_statuses(statuses);
+    server.set_prompts(build_prompts(data.clone(), &args)?);
+    if let Some(path) = &args.audit_log {
+        server.enable_audit_log(audit::AuditLog::open(path)?);
     }
 
+    #[cfg(feature = "watch")]
+    watch(&mut server, &args, data);
+
     server.run().await
 }
+
+/// Reload prompts every `--refresh-interval` and on SIGHUP, telling clients
+/// when the list changed.
+#[cfg(feature = "watch")]
+fn watch(server: &mut mcp::McpServer, args: &Args, data: Vec<PromptData>) {
+    let last: LastLoaded = Arc::new(Mutex::new(data));
+
+    if let Some(interval) = args.refresh_interval {
+        server.enable_list_changed();
+        tokio::spawn(refresh_periodically(
+            server.clone(),
+            args.clone(),
+            interval,
+            last.clone(),
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        server.enable_list_changed();
+        let (server, args) = (server.clone(), args.clone());
+        tokio::spawn(async move {
+            if let Err(e) = reload_on_sighup(server, args, last).await {
+                tracing::warn!(error = %e, "Failed to install SIGHUP handler");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration_units() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+    }
+
+    #[test]
+    fn test_parse_duration_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("15x").is_err());
+        assert!(parse_duration("m").is_err());
+        assert_eq!(
+            parse_duration("999999999999999999d").unwrap_err(),
+            "Duration too large: 999999999999999999d"
+        );
+    }
+
+    #[test]
+    fn test_parse_key_value() {
+        assert_eq!(
+            parse_key_value("code=a = b").unwrap(),
+            ("code".to_string(), "a = b".to_string())
+        );
+        assert_eq!(
+            parse_key_value("lang=").unwrap(),
+            ("lang".to_string(), String::new())
+        );
+        assert!(parse_key_value("code").is_err());
+    }
+
+    #[test]
+    fn test_frontmatter_hooks_need_opt_in() {
+        let data = PromptData {
+            name: "review".to_string(),
+            content: "Review".to_string(),
+            post_render_cmd: Some("frontmatter".to_string()),
+            ..Default::default()
+        };
+        let command = |argv: &[&str]| {
+            let args = Args::try_parse_from(argv).unwrap();
+            let prompt = build_prompts(vec![data.clone()], &args).unwrap().remove(0);
+            prompt.post_render.map(|hook| hook.command)
+        };
+        assert_eq!(command(&["shinkuro"]), None);
+        assert_eq!(
+            command(&["shinkuro", "--post-render-cmd", "global"]).as_deref(),
+            Some("global")
+        );
+        assert_eq!(
+            command(&[
+                "shinkuro",
+                "--post-render-cmd",
+                "global",
+                "--allow-frontmatter-hooks"
+            ])
+            .as_deref(),
+            Some("frontmatter")
+        );
+    }
+
+    #[test]
+    fn test_sources() {
+        let args = Args::try_parse_from(["shinkuro", "--folder", "./prompts"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(
+            sources(&args).unwrap(),
+            [loader::Source::local("./prompts")]
+        );
+
+        let args = Args::try_parse_from([
+            "shinkuro",
+            "serve",
+            "--folder",
+            "./prompts",
+            "./team",
+            "git@github.com:owner/repo.git#prompts",
+        ])
+        .unwrap();
+        assert_eq!(
+            sources(&args).unwrap(),
+            [
+                loader::Source::local("./prompts"),
+                loader::Source::local("./team"),
+                loader::Source::Git {
+                    url: "git@github.com:owner/repo.git".to_string(),
+                    folder: Some("prompts".to_string()),
+                },
+            ]
+        );
+        let args = Args::try_parse_from(["shinkuro", "serve"]).unwrap();
+        assert!(sources(&args).is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("512KB").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("4MB").unwrap(), 4 * 1024 * 1024);
+        assert_eq!(parse_size("1g").unwrap(), 1024 * 1024 * 1024);
+        assert!(parse_size("").is_err());
+        assert!(parse_size("4XB").is_err());
+        assert_eq!(
+            parse_size("99999999999G").unwrap_err(),
+            "Size too large: 99999999999G"
+        );
+    }
+}
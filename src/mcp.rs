@@ -1,9 +1,25 @@
-use crate::prompt::MarkdownPrompt;
+use crate::audit::{self, AuditLog};
+use crate::error::Error;
+use crate::formatter::BraceFormatter;
+use crate::loader::SourceStatus;
+use crate::model::{Argument, PromptData};
+use crate::naming;
+use crate::platform::{self, Stdout};
+use crate::prompt::{estimate_tokens, MarkdownPrompt, Rendered};
+use crate::registry::PromptRegistry;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+/// Requests handled at once before `McpServer::run` stops reading more.
+const MAX_CONCURRENT_REQUESTS: usize = 64;
 
 #[derive(Deserialize)]
 struct Request {
@@ -29,61 +45,247 @@ struct ErrorObject {
     message: String,
 }
 
+impl Response {
+    fn error(id: Option<Value>, error: Error) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(ErrorObject {
+                code: error.code(),
+                message: error.to_string(),
+            }),
+        }
+    }
+}
+
+/// Prompts generated at runtime, e.g. from a database, served alongside the
+/// loaded ones. Called on every request that looks prompts up.
+pub trait PromptProvider: Send + Sync {
+    fn prompts(&self) -> Result<Vec<MarkdownPrompt>>;
+}
+
+#[derive(Serialize)]
+struct Notification {
+    jsonrpc: String,
+    method: String,
+}
+
+/// Cheap to clone: clones share the prompt map and stdout, so background tasks
+/// can swap prompts and send notifications while `run` serves requests.
+#[derive(Clone)]
 pub struct McpServer {
-    prompts: HashMap<String, MarkdownPrompt>,
+    /// Requests hold a snapshot while reloads swap in a new registry, so
+    /// neither waits for the other.
+    prompts: Arc<RwLock<Arc<PromptRegistry>>>,
+    /// Prompts added with `add_prompt_from_parts`, kept when prompts are replaced.
+    registered: Arc<RwLock<Arc<PromptRegistry>>>,
+    providers: Arc<RwLock<Vec<Box<dyn PromptProvider>>>>,
+    stdout: Arc<Mutex<Stdout>>,
+    list_changed: bool,
+    /// Argument values provided by the client during this session, by prompt
+    /// and argument name, so one prompt's values don't fill in another's.
+    session: Arc<RwLock<HashMap<String, HashMap<String, String>>>>,
+    sources: Arc<RwLock<Vec<SourceStatus>>>,
+    audit_log: Option<Arc<AuditLog>>,
+    /// The `clientInfo` from `initialize`, recorded in the audit log.
+    client_info: Arc<RwLock<Option<Value>>>,
+}
+
+impl Default for McpServer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl McpServer {
     pub fn new() -> Self {
         Self {
-            prompts: HashMap::new(),
+            prompts: Arc::default(),
+            registered: Arc::default(),
+            providers: Arc::new(RwLock::new(Vec::new())),
+            stdout: Arc::new(Mutex::new(platform::stdout())),
+            list_changed: false,
+            session: Arc::new(RwLock::new(HashMap::new())),
+            sources: Arc::new(RwLock::new(Vec::new())),
+            audit_log: None,
+            client_info: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// Record every `prompts/get` request in `log`.
+    pub fn enable_audit_log(&mut self, log: AuditLog) {
+        self.audit_log = Some(Arc::new(log));
+    }
+
+    /// Advertise `prompts.listChanged` so clients expect `list_changed` notifications.
+    pub fn enable_list_changed(&mut self) {
+        self.list_changed = true;
+    }
+
     pub fn add_prompt(&mut self, prompt: MarkdownPrompt) {
-        self.prompts.insert(prompt.name.clone(), prompt);
+        Arc::make_mut(&mut self.prompts.write().unwrap()).insert(prompt);
     }
 
+    /// Serve a prompt built in code, with `{name}` placeholders for its
+    /// arguments. It stays when loaded prompts are replaced.
+    pub fn add_prompt_from_parts(
+        &mut self,
+        name: &str,
+        template: &str,
+        arguments: Vec<Argument>,
+    ) -> Result<()> {
+        if !naming::validate_prompt_name(name) {
+            anyhow::bail!("Invalid prompt name: {}", name);
+        }
+        let data = PromptData {
+            name: name.to_string(),
+            title: name.to_string(),
+            content: template.to_string(),
+            arguments,
+            ..Default::default()
+        };
+        let prompt = MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false)?;
+        Arc::make_mut(&mut self.registered.write().unwrap()).insert(prompt);
+        Ok(())
+    }
+
+    /// Serve the prompts of `provider` too, asking it again on every request.
+    pub fn add_dynamic_prompt(&mut self, provider: Box<dyn PromptProvider>) {
+        self.providers.write().unwrap().push(provider);
+    }
+
+    /// Replace all prompts at once, e.g. after a refresh. Requests already
+    /// in flight finish with the prompts they started with.
+    pub fn set_prompts(&self, prompts: Vec<MarkdownPrompt>) {
+        self.set_registry(PromptRegistry::new(prompts));
+    }
+
+    pub fn set_registry(&self, registry: PromptRegistry) {
+        *self.prompts.write().unwrap() = Arc::new(registry);
+    }
+
+    /// The loaded prompts as of now, unaffected by later `set_prompts` calls.
+    pub fn prompts(&self) -> Arc<PromptRegistry> {
+        self.prompts.read().unwrap().clone()
+    }
+
+    pub fn set_source_statuses(&self, statuses: Vec<SourceStatus>) {
+        *self.sources.write().unwrap() = statuses;
+    }
+
+    pub async fn notify_prompts_list_changed(&self) -> Result<()> {
+        self.send(&Notification {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/prompts/list_changed".to_string(),
+        })
+        .await
+    }
+
+    async fn send<T: Serialize>(&self, message: &T) -> Result<()> {
+        let json = serde_json::to_string(message)?;
+        let mut stdout = self.stdout.lock().await;
+        stdout.write_all(json.as_bytes()).await?;
+        stdout.write_all(b"\n").await?;
+        stdout.flush().await?;
+        Ok(())
+    }
+
+    /// Serve requests from stdin until it closes. Each request is handled in
+    /// its own task, so a slow render doesn't hold up the ones after it;
+    /// responses are written as they complete.
     pub async fn run(&self) -> Result<()> {
-        let stdin = tokio::io::stdin();
-        let mut stdout = tokio::io::stdout();
+        let stdin = platform::stdin();
         let mut reader = BufReader::new(stdin);
         let mut line = String::new();
+        let mut tasks = JoinSet::new();
 
         while reader.read_line(&mut line).await? > 0 {
-            if let Ok(req) = serde_json::from_str::<Request>(&line) {
-                if let Some(resp) = self.handle_request(req) {
-                    let json = serde_json::to_string(&resp)?;
-                    stdout.write_all(json.as_bytes()).await?;
-                    stdout.write_all(b"\n").await?;
-                    stdout.flush().await?;
+            if line.trim().is_empty() {
+                line.clear();
+                continue;
+            }
+            match serde_json::from_str::<Request>(&line) {
+                Ok(req) => {
+                    // Stop reading while too many requests are in flight
+                    while tasks.len() >= MAX_CONCURRENT_REQUESTS {
+                        if let Some(result) = tasks.join_next().await {
+                            result??;
+                        }
+                    }
+                    let server = self.clone();
+                    tasks.spawn(async move {
+                        match server.handle_request(req).await {
+                            Some(resp) => server.send(&resp).await,
+                            None => Ok(()),
+                        }
+                    });
                 }
+                Err(e) => tracing::warn!("Ignoring invalid message: {}", e),
+            }
+            while let Some(result) = tasks.try_join_next() {
+                result??;
             }
             line.clear();
         }
+        while let Some(result) = tasks.join_next().await {
+            result??;
+        }
         Ok(())
     }
 
-    fn handle_request(&self, req: Request) -> Option<Response> {
+    async fn handle_request(&self, req: Request) -> Option<Response> {
+        let span = tracing::debug_span!("request", method = %req.method);
+        span.in_scope(|| tracing::debug!(params = ?req.params, "Handling request"));
+        let audited = match (&self.audit_log, req.method.as_str()) {
+            (Some(log), "prompts/get") => Some((log.clone(), req.params.clone(), Instant::now())),
+            _ => None,
+        };
+        let response = self.dispatch(req).instrument(span).await;
+        if let Some((log, params, start)) = audited {
+            let params = params.as_ref();
+            log.record(&audit::Entry::new(
+                params.and_then(|p| p.get("name")).and_then(Value::as_str),
+                params.and_then(|p| p.get("arguments")),
+                self.client_info.read().unwrap().clone(),
+                start.elapsed(),
+                response
+                    .as_ref()
+                    .and_then(|r| r.error.as_ref())
+                    .map(|e| e.message.as_str()),
+            ));
+        }
+        response
+    }
+
+    async fn dispatch(&self, req: Request) -> Option<Response> {
         match req.method.as_str() {
-            "initialize" => Some(Response {
-                jsonrpc: "2.0".to_string(),
-                id: req.id,
-                result: Some(json!({
-                    "protocolVersion": "2025-06-18",
-                    "capabilities": {
-                        "prompts": {
-                            "listChanged": false
+            "initialize" => {
+                *self.client_info.write().unwrap() = req
+                    .params
+                    .as_ref()
+                    .and_then(|p| p.get("clientInfo"))
+                    .cloned();
+                Some(Response {
+                    jsonrpc: "2.0".to_string(),
+                    id: req.id,
+                    result: Some(json!({
+                        "protocolVersion": "2025-06-18",
+                        "capabilities": {
+                            "prompts": {
+                                "listChanged": self.list_changed
+                            },
+                            "tools": {
+                                "listChanged": false
+                            },
+                            "completions": {}
                         },
-                        "tools": {
-                            "listChanged": false
-                        }
-                    },
-                    "serverInfo": { "name": "shinkuro", "version": env!("CARGO_PKG_VERSION") },
-                    "instructions": ""
-                })),
-                error: None,
-            }),
+                        "serverInfo": { "name": "shinkuro", "version": env!("CARGO_PKG_VERSION") },
+                        "instructions": ""
+                    })),
+                    error: None,
+                })
+            }
             "notifications/initialized" => None,
             "ping" => Some(Response {
                 jsonrpc: "2.0".to_string(),
@@ -95,16 +297,7 @@ impl McpServer {
                 jsonrpc: "2.0".to_string(),
                 id: req.id,
                 result: Some(json!({
-                    "prompts": self.prompts.values().map(|p| json!({
-                        "name": p.name,
-                        "title": p.title,
-                        "description": p.description,
-                        "arguments": p.arguments.iter().map(|a| json!({
-                            "name": a.name,
-                            "description": a.description,
-                            "required": a.required
-                        })).collect::<Vec<_>>()
-                    })).collect::<Vec<_>>()
+                    "prompts": self.served_prompts().iter_sorted().map(|p| prompt_json(p)).collect::<Vec<_>>()
                 })),
                 error: None,
             }),
@@ -116,81 +309,668 @@ impl McpServer {
                     .and_then(|n| n.as_str());
 
                 if let Some(name) = name {
-                    if let Some(prompt) = self.prompts.get(name) {
+                    if let Some(prompt) = self.served_prompts().get(name).cloned() {
                         let args = req
                             .params
                             .as_ref()
                             .and_then(|p| p.get("arguments"))
-                            .and_then(|a| {
-                                serde_json::from_value::<HashMap<String, String>>(a.clone()).ok()
-                            });
+                            .and_then(argument_values);
 
-                        match prompt.render(args) {
-                            Ok(content) => Some(Response {
+                        match self.render(prompt, args).await {
+                            Ok(rendered) => Some(Response {
                                 jsonrpc: "2.0".to_string(),
                                 id: req.id,
                                 result: Some(json!({
-                                    "messages": [{ "role": "user", "content": { "type": "text", "text": content } }]
+                                    "messages": [{ "role": "user", "content": { "type": "text", "text": rendered.text } }],
+                                    "_meta": { "argumentSources": rendered.sources }
                                 })),
                                 error: None,
                             }),
-                            Err(e) => Some(Response {
-                                jsonrpc: "2.0".to_string(),
-                                id: req.id,
-                                result: None,
-                                error: Some(ErrorObject {
-                                    code: -32602,
-                                    message: e,
-                                }),
-                            }),
+                            Err(e) => Some(Response::error(req.id, e)),
                         }
                     } else {
-                        Some(Response {
-                            jsonrpc: "2.0".to_string(),
-                            id: req.id,
-                            result: None,
-                            error: Some(ErrorObject {
-                                code: -32602,
-                                message: "Prompt not found".to_string(),
-                            }),
-                        })
+                        Some(Response::error(
+                            req.id,
+                            Error::PromptNotFound(name.to_string()),
+                        ))
                     }
                 } else {
-                    Some(Response {
-                        jsonrpc: "2.0".to_string(),
-                        id: req.id,
-                        result: None,
-                        error: Some(ErrorObject {
-                            code: -32602,
-                            message: "Missing name parameter".to_string(),
-                        }),
-                    })
+                    Some(Response::error(
+                        req.id,
+                        Error::InvalidParams("Missing name parameter".to_string()),
+                    ))
                 }
             }
+            "completion/complete" => {
+                let params = req.params.as_ref();
+                let str_param = |pointer: &str| {
+                    params
+                        .and_then(|p| p.pointer(pointer))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                };
+                let prompts = self.served_prompts();
+                let Some(prompt) = prompts.get(str_param("/ref/name")) else {
+                    return Some(Response::error(
+                        req.id,
+                        Error::PromptNotFound(str_param("/ref/name").to_string()),
+                    ));
+                };
+                let mut values =
+                    prompt.complete(str_param("/argument/name"), str_param("/argument/value"));
+                let total = values.len();
+                // The spec caps a completion response at 100 values
+                values.truncate(100);
+                Some(Response {
+                    jsonrpc: "2.0".to_string(),
+                    id: req.id,
+                    result: Some(json!({
+                        "completion": {
+                            "values": values,
+                            "total": total,
+                            "hasMore": total > 100
+                        }
+                    })),
+                    error: None,
+                })
+            }
             "tools/list" => Some(Response {
                 jsonrpc: "2.0".to_string(),
                 id: req.id,
-                result: Some(json!({ "tools": [] })),
+                result: Some(json!({ "tools": tools() })),
                 error: None,
             }),
-            "tools/call" => Some(Response {
-                jsonrpc: "2.0".to_string(),
-                id: req.id,
-                result: None,
-                error: Some(ErrorObject {
-                    code: -32602,
-                    message: "No tools available".to_string(),
-                }),
-            }),
-            _ => Some(Response {
-                jsonrpc: "2.0".to_string(),
-                id: req.id,
-                result: None,
-                error: Some(ErrorObject {
-                    code: -32601,
-                    message: "Method not found".to_string(),
-                }),
-            }),
+            "tools/call" => {
+                let name = req
+                    .params
+                    .as_ref()
+                    .and_then(|p| p.get("name"))
+                    .and_then(|n| n.as_str())
+                    .unwrap_or_default();
+                let arguments = req.params.as_ref().and_then(|p| p.get("arguments"));
+                match self.call_tool(name, arguments).await {
+                    Some(result) => Some(Response {
+                        jsonrpc: "2.0".to_string(),
+                        id: req.id,
+                        result: Some(result),
+                        error: None,
+                    }),
+                    None => Some(Response::error(
+                        req.id,
+                        Error::InvalidParams(format!("Unknown tool: {}", name)),
+                    )),
+                }
+            }
+            method => Some(Response::error(
+                req.id,
+                Error::MethodNotFound(method.to_string()),
+            )),
         }
     }
+
+    /// Render a prompt, resolving session fallbacks and remembering the
+    /// client-provided values for later calls. Renders on a blocking thread,
+    /// since post-render commands block.
+    async fn render(
+        &self,
+        prompt: Arc<MarkdownPrompt>,
+        args: Option<HashMap<String, String>>,
+    ) -> crate::Result<Rendered> {
+        let name = prompt.name.clone();
+        let session = self
+            .session
+            .read()
+            .unwrap()
+            .get(&name)
+            .cloned()
+            .unwrap_or_default();
+        let values = args.clone();
+        let rendered = platform::spawn_blocking(move || prompt.render(values, &session))
+            .await
+            .map_err(|e| Error::Render(e.to_string()))??;
+        if let Some(args) = args {
+            self.session
+                .write()
+                .unwrap()
+                .entry(name)
+                .or_default()
+                .extend(args);
+        }
+        Ok(rendered)
+    }
+
+    /// The prompts to serve, where registered and generated prompts shadow
+    /// loaded ones of the same name. Only the loaded snapshot is shared when
+    /// there's nothing to merge into it.
+    fn served_prompts(&self) -> Arc<PromptRegistry> {
+        let loaded = self.prompts();
+        let registered = self.registered.read().unwrap().clone();
+        let generated = self.generated_prompts();
+        if registered.is_empty() && generated.is_empty() {
+            return loaded;
+        }
+        let mut prompts = PromptRegistry::clone(&loaded);
+        prompts.extend(&registered);
+        for prompt in generated {
+            prompts.insert(prompt);
+        }
+        Arc::new(prompts)
+    }
+
+    fn generated_prompts(&self) -> Vec<MarkdownPrompt> {
+        let mut prompts = Vec::new();
+        for provider in self.providers.read().unwrap().iter() {
+            match provider.prompts() {
+                Ok(generated) => prompts.extend(generated),
+                Err(e) => tracing::warn!(error = %e, "Failed to generate prompts"),
+            }
+        }
+        prompts
+    }
+
+    /// Returns `None` for unknown tools. Tool failures are reported in the result with `isError`.
+    async fn call_tool(&self, name: &str, arguments: Option<&Value>) -> Option<Value> {
+        let string_arg = |key: &str| {
+            arguments
+                .and_then(|a| a.get(key))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+        };
+        match name {
+            "sources_status" => {
+                let sources = self.sources.read().unwrap();
+                Some(tool_result(json!({ "sources": *sources })))
+            }
+            "render_prompt" => {
+                let Some(prompt) = self.served_prompts().get(string_arg("name")).cloned() else {
+                    return Some(tool_error(&Error::PromptNotFound(
+                        string_arg("name").to_string(),
+                    )));
+                };
+                let args = arguments
+                    .and_then(|a| a.get("arguments"))
+                    .and_then(argument_values);
+                Some(match self.render(prompt.clone(), args).await {
+                    Ok(rendered) => tool_result(json!({
+                        "prompt": prompt_json(&prompt),
+                        "text": rendered.text,
+                        "tokenEstimate": estimate_tokens(&rendered.text),
+                        "argumentSources": rendered.sources,
+                    })),
+                    Err(e) => tool_error(&e),
+                })
+            }
+            "search_prompts" => {
+                let query = string_arg("query").to_lowercase();
+                let prompts = self.served_prompts();
+                let matches: Vec<_> = prompts
+                    .iter_sorted()
+                    .filter(|p| {
+                        [&p.name, &p.title, &p.description]
+                            .into_iter()
+                            .chain(&p.tags)
+                            .chain(&p.category)
+                            .any(|field| field.to_lowercase().contains(&query))
+                    })
+                    .collect();
+                Some(tool_result(json!({
+                    "prompts": matches.into_iter().map(|p| prompt_json(p)).collect::<Vec<_>>()
+                })))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Argument values as text. Clients may pass objects and other JSON values,
+/// which are kept as JSON text so `{user.name}` can look into them.
+pub(crate) fn argument_values(arguments: &Value) -> Option<HashMap<String, String>> {
+    let arguments = arguments.as_object()?;
+    Some(
+        arguments
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                };
+                (name.clone(), value)
+            })
+            .collect(),
+    )
+}
+
+pub(crate) fn prompt_json(p: &MarkdownPrompt) -> Value {
+    let mut prompt = json!({
+        "name": p.name,
+        "title": p.title,
+        "description": p.description,
+        "arguments": p.arguments.iter().map(|a| json!({
+            "name": a.name,
+            "description": a.describe(),
+            "required": a.required
+        })).collect::<Vec<_>>()
+    });
+    if !p.tags.is_empty() {
+        prompt["_meta"]["tags"] = json!(p.tags);
+    }
+    if !p.aliases.is_empty() {
+        prompt["_meta"]["aliases"] = json!(p.aliases);
+    }
+    if let Some(category) = &p.category {
+        prompt["_meta"]["category"] = json!(category);
+    }
+    for (key, value) in [
+        ("version", &p.version),
+        ("author", &p.author),
+        ("maintainer", &p.maintainer),
+    ] {
+        if let Some(value) = value {
+            prompt["_meta"][key] = json!(value);
+        }
+    }
+    if !p.one_of.is_empty() {
+        prompt["_meta"]["oneOf"] = json!(p.one_of);
+    }
+    prompt
+}
+
+/// Tool results carry typed `structuredContent`, serialized into a text block too
+/// for clients that only read `content`.
+fn tool_result(structured: Value) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": structured.to_string() }],
+        "structuredContent": structured
+    })
+}
+
+fn tool_error(error: &Error) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": error.to_string() }],
+        "isError": true
+    })
+}
+
+fn tools() -> Value {
+    let prompt_schema = json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "title": { "type": "string" },
+            "description": { "type": "string" },
+            "arguments": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "description": { "type": "string" },
+                        "required": { "type": "boolean" }
+                    }
+                }
+            },
+            "_meta": {
+                "type": "object",
+                "properties": {
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "category": { "type": "string" },
+                    "version": { "type": "string" },
+                    "author": { "type": "string" },
+                    "maintainer": { "type": "string" },
+                    "oneOf": {
+                        "type": "array",
+                        "items": { "type": "array", "items": { "type": "string" } }
+                    }
+                }
+            }
+        }
+    });
+    json!([
+        {
+            "name": "sources_status",
+            "description": "Report the load status of each configured prompt source",
+            "inputSchema": { "type": "object", "properties": {} },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "sources": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "source": { "type": "string" },
+                                "sync": { "type": "string" },
+                                "prompts": { "type": "integer" },
+                                "error": { "type": "string" }
+                            }
+                        }
+                    }
+                },
+                "required": ["sources"]
+            }
+        },
+        {
+            "name": "render_prompt",
+            "description": "Render a prompt with the given arguments",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "arguments": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" }
+                    }
+                },
+                "required": ["name"]
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "prompt": prompt_schema,
+                    "text": { "type": "string" },
+                    "tokenEstimate": { "type": "integer" },
+                    "argumentSources": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" }
+                    }
+                },
+                "required": ["prompt", "text", "tokenEstimate"]
+            }
+        },
+        {
+            "name": "search_prompts",
+            "description": "Search prompts by name, title, description, tags or category",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "query": { "type": "string" } },
+                "required": ["query"]
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "prompts": { "type": "array", "items": prompt_schema }
+                },
+                "required": ["prompts"]
+            }
+        }
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter::BraceFormatter;
+    use crate::model::{Argument, PromptData};
+    use crate::test_util::TempDir;
+
+    fn prompt(
+        name: &str,
+        content: &str,
+        arguments: &[&str],
+        auto_discover: bool,
+    ) -> MarkdownPrompt {
+        let data = PromptData {
+            name: name.to_string(),
+            arguments: arguments
+                .iter()
+                .map(|name| Argument {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            content: content.to_string(),
+            ..Default::default()
+        };
+        MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), auto_discover).unwrap()
+    }
+
+    async fn request(server: &McpServer, method: &str, params: Value) -> Option<Response> {
+        server
+            .handle_request(Request {
+                id: Some(json!(1)),
+                method: method.to_string(),
+                params: Some(params),
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_prompts_list_order() {
+        let server = McpServer::new();
+        server.set_prompts(vec![
+            prompt(
+                "zeta",
+                "{zoo} {apple} {mid}",
+                &["zoo", "apple", "mid"],
+                false,
+            ),
+            prompt("alpha", "{zoo} {apple} {mid}", &[], true),
+        ]);
+        let response = request(&server, "prompts/list", json!({})).await.unwrap();
+        let prompts = &response.result.unwrap()["prompts"];
+        let order = |i: usize| -> Vec<String> {
+            prompts[i]["arguments"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|a| a["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(prompts[0]["name"], "alpha");
+        assert_eq!(prompts[1]["name"], "zeta");
+        // Auto-discovered arguments are sorted; declared ones keep the author's order
+        assert_eq!(order(0), vec!["apple", "mid", "zoo"]);
+        assert_eq!(order(1), vec!["zoo", "apple", "mid"]);
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let dir = TempDir::new("audit");
+        let path = dir.join("audit.jsonl");
+        let mut server = McpServer::new();
+        server.enable_audit_log(AuditLog::open(&path).unwrap());
+        server.set_prompts(vec![prompt("review", "Review {code}", &["code"], false)]);
+        request(
+            &server,
+            "initialize",
+            json!({ "clientInfo": { "name": "editor", "version": "1.0" } }),
+        )
+        .await;
+        request(&server, "prompts/list", json!({})).await;
+        request(
+            &server,
+            "prompts/get",
+            json!({ "name": "review", "arguments": { "code": "secret" } }),
+        )
+        .await;
+        request(&server, "prompts/get", json!({ "name": "missing" })).await;
+
+        let lines: Vec<Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["prompt"], "review");
+        assert_eq!(lines[0]["arguments"], json!(["code"]));
+        assert_eq!(
+            lines[0]["client"],
+            json!({ "name": "editor", "version": "1.0" })
+        );
+        assert_eq!(lines[0]["outcome"], "ok");
+        assert!(lines[0].get("error").is_none());
+        assert!(!lines[0].to_string().contains("secret"));
+        assert_eq!(lines[1]["prompt"], "missing");
+        assert_eq!(lines[1]["outcome"], "error");
+        assert_eq!(lines[1]["error"], "Prompt not found");
+    }
+
+    #[tokio::test]
+    async fn test_programmatic_prompts() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Tickets(Arc<AtomicUsize>);
+
+        impl PromptProvider for Tickets {
+            fn prompts(&self) -> Result<Vec<MarkdownPrompt>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(vec![prompt("ticket-42", "Fix the login bug", &[], false)])
+            }
+        }
+
+        let mut server = McpServer::new();
+        server
+            .add_prompt_from_parts(
+                "greet",
+                "Hello {user}",
+                vec![Argument {
+                    name: "user".to_string(),
+                    ..Default::default()
+                }],
+            )
+            .unwrap();
+        assert!(server
+            .add_prompt_from_parts("bad name", "", vec![])
+            .is_err());
+        let calls = Arc::new(AtomicUsize::new(0));
+        server.add_dynamic_prompt(Box::new(Tickets(calls.clone())));
+        // Loaded prompts are replaced, registered ones stay
+        server.set_prompts(vec![prompt("review", "Review", &[], false)]);
+        // Requests that don't look prompts up don't generate them
+        request(&server, "ping", json!({})).await;
+        request(&server, "tools/list", json!({})).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        let list = request(&server, "prompts/list", json!({}))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        let names: Vec<_> = list["prompts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["greet", "review", "ticket-42"]);
+
+        let get = |name: &str, arguments: Value| {
+            let params = json!({ "name": name, "arguments": arguments });
+            let server = &server;
+            async move {
+                let result = request(server, "prompts/get", params).await.unwrap().result;
+                result.unwrap()["messages"][0]["content"]["text"].clone()
+            }
+        };
+        assert_eq!(
+            get("greet", json!({ "user": "Alice" })).await,
+            "Hello Alice"
+        );
+        assert_eq!(get("ticket-42", json!({})).await, "Fix the login bug");
+    }
+
+    #[tokio::test]
+    async fn test_session_fallback_is_per_prompt() {
+        let lang = Argument {
+            name: "lang".to_string(),
+            default: Some("none".to_string()),
+            fallback: vec![crate::model::Fallback::Session],
+            ..Default::default()
+        };
+        let mut server = McpServer::new();
+        for name in ["review", "commit"] {
+            server
+                .add_prompt_from_parts(name, "{lang}", vec![lang.clone()])
+                .unwrap();
+        }
+        let get = |name: &str, arguments: Value| {
+            let params = json!({ "name": name, "arguments": arguments });
+            let server = &server;
+            async move {
+                let result = request(server, "prompts/get", params).await.unwrap().result;
+                result.unwrap()["messages"][0]["content"]["text"].clone()
+            }
+        };
+        assert_eq!(get("review", json!({ "lang": "rust" })).await, "rust");
+        assert_eq!(get("review", json!({})).await, "rust");
+        assert_eq!(get("commit", json!({})).await, "none");
+    }
+
+    #[tokio::test]
+    async fn test_set_prompts_snapshot() {
+        let server = McpServer::new();
+        server.set_prompts(vec![prompt("review", "Review", &[], false)]);
+        let before = server.prompts();
+
+        // Clones share the registry, so a reload task can swap it
+        let reloader = server.clone();
+        std::thread::spawn(move || {
+            let mut commit = prompt("commit", "Commit", &[], false);
+            commit.aliases = vec!["ci".to_string()];
+            reloader.set_prompts(vec![commit]);
+        })
+        .join()
+        .unwrap();
+
+        let names = |registry: &PromptRegistry| -> Vec<String> {
+            registry.iter_sorted().map(|p| p.name.clone()).collect()
+        };
+        assert_eq!(names(&before), vec!["review"]);
+        assert_eq!(names(&server.prompts()), vec!["commit"]);
+        // Without registered or generated prompts, requests share the snapshot
+        assert!(Arc::ptr_eq(&server.served_prompts(), &server.prompts()));
+        let get = |name: &str| request(&server, "prompts/get", json!({ "name": name }));
+        assert_eq!(
+            get("review").await.unwrap().error.unwrap().message,
+            "Prompt not found"
+        );
+        // Aliases resolve to the prompt
+        assert_eq!(
+            get("ci").await.unwrap().result.unwrap()["messages"][0]["content"]["text"],
+            "Commit"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_render_does_not_block_other_tasks() {
+        use crate::hooks::{FailurePolicy, PostRenderHook};
+        use std::time::Duration;
+
+        let server = McpServer::new();
+        let mut slow = prompt("slow", "Slow", &[], false);
+        slow.post_render = Some(PostRenderHook {
+            command: "sleep 0.5; cat".to_string(),
+            timeout: Duration::from_secs(10),
+            on_failure: FailurePolicy::Error,
+        });
+        server.set_prompts(vec![slow]);
+        let start = Instant::now();
+        let (response, ticked) = tokio::join!(
+            request(&server, "prompts/get", json!({ "name": "slow" })),
+            async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                start.elapsed()
+            }
+        );
+        assert_eq!(
+            response.unwrap().result.unwrap()["messages"][0]["content"]["text"],
+            "Slow"
+        );
+        assert!(ticked < Duration::from_millis(400), "{:?}", ticked);
+    }
+
+    #[test]
+    fn test_argument_values() {
+        let args = argument_values(&json!({
+            "user": { "name": "Alice" },
+            "count": 3,
+            "note": "plain"
+        }))
+        .unwrap();
+        assert_eq!(args["user"], r#"{"name":"Alice"}"#);
+        assert_eq!(args["count"], "3");
+        assert_eq!(args["note"], "plain");
+        assert_eq!(argument_values(&json!("not an object")), None);
+    }
 }
@@ -1,18 +1,187 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Where to look for an argument value the client didn't provide, tried in order
+/// before falling back to the `default` literal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Fallback {
+    /// The last value the client provided for an argument with the same name.
+    Session,
+    /// The value of an environment variable.
+    Env(String),
+}
+
+impl Fallback {
+    /// Parse `session` or `env:NAME`.
+    pub fn parse(s: &str) -> Option<Self> {
+        if s == "session" {
+            Some(Fallback::Session)
+        } else {
+            s.strip_prefix("env:")
+                .filter(|name| !name.is_empty())
+                .map(|name| Fallback::Env(name.to_string()))
+        }
+    }
+}
+
+impl TryFrom<String> for Fallback {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Fallback::parse(&s).ok_or_else(|| format!("'{}' is not 'session' or 'env:NAME'", s))
+    }
+}
+
+impl From<Fallback> for String {
+    fn from(fallback: Fallback) -> Self {
+        match fallback {
+            Fallback::Session => "session".to_string(),
+            Fallback::Env(name) => format!("env:{}", name),
+        }
+    }
+}
+
+/// The type of an argument value. Values are always substituted as text, but
+/// are checked and normalized against the type first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgumentType {
+    #[default]
+    String,
+    Number,
+    Boolean,
+    /// A JSON array, or one item per line. Normalized to a JSON array of strings.
+    List,
+}
+
+impl ArgumentType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArgumentType::String => "string",
+            ArgumentType::Number => "number",
+            ArgumentType::Boolean => "boolean",
+            ArgumentType::List => "list",
+        }
+    }
+
+    /// Normalize `value` to this type, e.g. ` 42 ` to `42` or `Yes` to `true`.
+    pub fn coerce(&self, value: &str) -> Option<String> {
+        match self {
+            ArgumentType::String => Some(value.to_string()),
+            ArgumentType::Number => {
+                let value = value.trim();
+                if let Ok(n) = value.parse::<i64>() {
+                    Some(n.to_string())
+                } else {
+                    value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|n| n.is_finite())
+                        .map(|_| value.to_string())
+                }
+            }
+            ArgumentType::Boolean => match value.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Some("true".to_string()),
+                "false" | "no" | "off" | "0" => Some("false".to_string()),
+                _ => None,
+            },
+            ArgumentType::List => {
+                let items: Vec<String> = match serde_json::from_str(value.trim()) {
+                    Ok(serde_json::Value::Array(items)) => items
+                        .into_iter()
+                        .map(|item| match item {
+                            serde_json::Value::String(s) => s,
+                            item => item.to_string(),
+                        })
+                        .collect(),
+                    Ok(_) | Err(_) => value
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(String::from)
+                        .collect(),
+                };
+                Some(serde_json::Value::from(items).to_string())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Argument {
     pub name: String,
     pub description: String,
     #[serde(default)]
     pub default: Option<String>,
+    #[serde(default)]
+    pub fallback: Vec<Fallback>,
+    /// Allowed values; any value is accepted if empty.
+    #[serde(default, rename = "enum")]
+    pub enum_values: Vec<String>,
+    #[serde(default, rename = "type")]
+    pub arg_type: ArgumentType,
+    /// Regex values must match.
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Bounds on the value length in characters.
+    #[serde(default)]
+    pub min_length: Option<usize>,
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    /// Overrides whether the argument is required, which is otherwise inferred
+    /// from the absence of `default` and `fallback`.
+    #[serde(default)]
+    pub required: Option<bool>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct PromptData {
     pub name: String,
     pub title: String,
     pub description: String,
     pub arguments: Vec<Argument>,
     pub content: String,
+    /// Command the rendered content is piped through, overriding `--post-render-cmd`
+    /// when `--allow-frontmatter-hooks` is set.
+    pub post_render_cmd: Option<String>,
+    /// The file the prompt was loaded from, for diagnostics.
+    pub path: String,
+    pub tags: Vec<String>,
+    /// Other names the prompt can be looked up by.
+    pub aliases: Vec<String>,
+    pub category: Option<String>,
+    /// Set by `enabled: false` or `draft: true`; drafts are skipped when scanning.
+    pub draft: bool,
+    /// Overrides `--variable-format` for this prompt.
+    pub variable_format: Option<String>,
+    /// The prompt's own version, for clients tracking rollouts.
+    pub version: Option<String>,
+    pub author: Option<String>,
+    pub maintainer: Option<String>,
+    /// Groups of argument names of which exactly one must be provided.
+    pub one_of: Vec<Vec<String>>,
+    /// Overrides `--strict-render` for this prompt.
+    pub strict_render: Option<bool>,
+    /// Overrides `--max-render-bytes` for this prompt.
+    pub max_render_bytes: Option<u64>,
+    /// Overrides `--max-render-tokens` for this prompt.
+    pub max_render_tokens: Option<usize>,
+    /// Handlebars partials from the prompt's folder, by name.
+    pub partials: BTreeMap<String, String>,
+    /// The folder the prompt was scanned from, which `{file:...}` placeholders
+    /// must stay inside. Not known for prompts from packs.
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+}
+
+impl PromptData {
+    /// Where the prompt came from and who to contact about it, for error messages.
+    pub fn origin(&self) -> String {
+        match self.maintainer.as_ref().or(self.author.as_ref()) {
+            Some(owner) => format!("{}, maintained by {}", self.path, owner),
+            None => self.path.clone(),
+        }
+    }
 }
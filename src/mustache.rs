@@ -0,0 +1,317 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+/// A parsed Mustache template. Values are strings, so sections render once for
+/// a non-empty value and inverted sections for an empty or missing one.
+#[derive(Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Variable {
+        name: String,
+        escape: bool,
+    },
+    Section {
+        name: String,
+        inverted: bool,
+        children: Vec<Node>,
+    },
+}
+
+/// Tags that are removed along with their line when they stand alone on it.
+const STANDALONE_TAGS: &[char] = &['#', '^', '/', '!', '='];
+
+fn parse(template: &str) -> Result<Vec<Node>> {
+    let mut open = "{{".to_string();
+    let mut close = "}}".to_string();
+    let mut sections: Vec<(String, bool, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = template[pos..].find(&open) {
+        let start = pos + found;
+        let after_open = start + open.len();
+        let sigil = template[after_open..]
+            .chars()
+            .next()
+            .filter(|c| "{&#^/!=>".contains(*c));
+        let end_marker = match sigil {
+            Some('{') => format!("}}{}", close),
+            Some('=') => format!("={}", close),
+            _ => close.clone(),
+        };
+        let inner_start = after_open + sigil.map_or(0, |c| c.len_utf8());
+        let Some(inner_len) = template[inner_start..].find(&end_marker) else {
+            anyhow::bail!("Unclosed tag on line {}", line_of(template, start));
+        };
+        let inner = template[inner_start..inner_start + inner_len].trim();
+        let mut text_end = start;
+        let mut next = inner_start + inner_len + end_marker.len();
+
+        if sigil.is_some_and(|c| STANDALONE_TAGS.contains(&c)) {
+            let line_start = template[..start].rfind('\n').map_or(0, |i| i + 1);
+            let rest = &template[next..];
+            let line_end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+            if line_start >= pos
+                && template[line_start..start].trim().is_empty()
+                && rest[..line_end].trim().is_empty()
+            {
+                text_end = line_start;
+                next += line_end;
+            }
+        }
+        if text_end > pos {
+            nodes.push(Node::Text(template[pos..text_end].to_string()));
+        }
+
+        match sigil {
+            Some(c @ ('#' | '^')) => {
+                sections.push((inner.to_string(), c == '^', std::mem::take(&mut nodes)));
+            }
+            Some('/') => {
+                let Some((name, inverted, parent)) = sections.pop() else {
+                    anyhow::bail!(
+                        "Unexpected section end '{}' on line {}",
+                        inner,
+                        line_of(template, start)
+                    );
+                };
+                if name != inner {
+                    anyhow::bail!(
+                        "Section '{}' closed by '{}' on line {}",
+                        name,
+                        inner,
+                        line_of(template, start)
+                    );
+                }
+                let children = std::mem::replace(&mut nodes, parent);
+                nodes.push(Node::Section {
+                    name,
+                    inverted,
+                    children,
+                });
+            }
+            Some('!') => {}
+            Some('=') => {
+                let delimiters: Vec<_> = inner.split_whitespace().collect();
+                let [new_open, new_close] = delimiters[..] else {
+                    anyhow::bail!(
+                        "Invalid delimiters '{}' on line {}",
+                        inner,
+                        line_of(template, start)
+                    );
+                };
+                open = new_open.to_string();
+                close = new_close.to_string();
+            }
+            Some('>') => anyhow::bail!(
+                "Partial '{}' on line {}: partials are not supported",
+                inner,
+                line_of(template, start)
+            ),
+            sigil => nodes.push(Node::Variable {
+                name: inner.to_string(),
+                escape: !matches!(sigil, Some('{' | '&')),
+            }),
+        }
+        pos = next;
+    }
+    if let Some((name, _, _)) = sections.last() {
+        anyhow::bail!("Unclosed section '{}'", name);
+    }
+    if pos < template.len() {
+        nodes.push(Node::Text(template[pos..].to_string()));
+    }
+    Ok(nodes)
+}
+
+fn line_of(template: &str, offset: usize) -> usize {
+    template[..offset].matches('\n').count() + 1
+}
+
+/// Names read from the context, e.g. `user` for `{{user.name}}`. `.` refers
+/// to the current section's value and isn't an argument.
+pub fn extract_arguments(template: &str) -> Result<HashSet<String>> {
+    fn collect(nodes: &[Node], args: &mut HashSet<String>) {
+        for node in nodes {
+            match node {
+                Node::Text(_) => {}
+                Node::Variable { name, .. } => {
+                    args.extend(root_name(name));
+                }
+                Node::Section { name, children, .. } => {
+                    args.extend(root_name(name));
+                    collect(children, args);
+                }
+            }
+        }
+    }
+    let mut args = HashSet::new();
+    collect(&parse(template)?, &mut args);
+    Ok(args)
+}
+
+fn root_name(name: &str) -> Option<String> {
+    (name != ".").then(|| name.split('.').next().unwrap_or(name).to_string())
+}
+
+/// A parsed template, for rendering many times.
+#[derive(Debug)]
+pub struct Template(Vec<Node>);
+
+pub fn compile(template: &str) -> Result<Template> {
+    Ok(Template(parse(template)?))
+}
+
+impl Template {
+    pub fn render(&self, variables: &HashMap<String, String>) -> String {
+        let mut out = String::new();
+        render_nodes(&self.0, variables, None, &mut out);
+        out
+    }
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    variables: &HashMap<String, String>,
+    context: Option<&str>,
+    out: &mut String,
+) {
+    fn lookup<'a>(
+        name: &str,
+        variables: &'a HashMap<String, String>,
+        context: Option<&'a str>,
+    ) -> &'a str {
+        if name == "." {
+            return context.unwrap_or_default();
+        }
+        // Strings have no fields, so dotted names are always empty
+        if name.contains('.') {
+            return "";
+        }
+        variables.get(name).map_or("", String::as_str)
+    }
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Variable { name, escape } => {
+                let value = lookup(name, variables, context);
+                if *escape {
+                    out.push_str(&escape_html(value));
+                } else {
+                    out.push_str(value);
+                }
+            }
+            Node::Section {
+                name,
+                inverted,
+                children,
+            } => {
+                let value = lookup(name, variables, context);
+                if value.is_empty() == *inverted {
+                    let context = if *inverted { context } else { Some(value) };
+                    render_nodes(children, variables, context, out);
+                }
+            }
+        }
+    }
+}
+
+/// The same characters mustache.js escapes.
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '/' => escaped.push_str("&#x2F;"),
+            '`' => escaped.push_str("&#x60;"),
+            '=' => escaped.push_str("&#x3D;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, variables: &HashMap<String, String>) -> Result<String> {
+        Ok(compile(template)?.render(variables))
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_variables() {
+        let variables = vars(&[("code", "a < b && c")]);
+        assert_eq!(
+            render(
+                "{{code}} | {{{code}}} | {{& code}} | {{missing}}",
+                &variables
+            )
+            .unwrap(),
+            "a &lt; b &amp;&amp; c | a < b && c | a < b && c | "
+        );
+    }
+
+    #[test]
+    fn test_render_sections() {
+        let template = "Review:\n{{#lang}}\n  Language: {{.}}\n{{/lang}}\n{{^lang}}\n  Any language\n{{/lang}}\n{{! comment }}\nDone";
+        assert_eq!(
+            render(template, &vars(&[("lang", "rust")])).unwrap(),
+            "Review:\n  Language: rust\nDone"
+        );
+        assert_eq!(
+            render(template, &vars(&[("lang", "")])).unwrap(),
+            "Review:\n  Any language\nDone"
+        );
+        assert_eq!(
+            render("[{{#a}}x{{/a}}]", &vars(&[("a", "false")])).unwrap(),
+            "[x]"
+        );
+    }
+
+    #[test]
+    fn test_set_delimiters() {
+        let template = "{{=<% %>=}}\nfn main() { <% body %> }";
+        assert_eq!(
+            render(template, &vars(&[("body", "x")])).unwrap(),
+            "fn main() { x }"
+        );
+    }
+
+    #[test]
+    fn test_extract_arguments() {
+        let args = extract_arguments(
+            "{{#items}}{{.}}{{user.name}}{{/items}}{{^empty}}{{{raw}}}{{/empty}}",
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            ["items", "user", "empty", "raw"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |template| parse(template).unwrap_err().to_string();
+        assert_eq!(
+            error("{{#a}}\n{{/b}}"),
+            "Section 'a' closed by 'b' on line 2"
+        );
+        assert_eq!(error("{{#a}}"), "Unclosed section 'a'");
+        assert_eq!(error("Hi {{name"), "Unclosed tag on line 1");
+        assert!(error("{{> header}}").contains("partials are not supported"));
+    }
+}
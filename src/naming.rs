@@ -0,0 +1,264 @@
+use crate::model::PromptData;
+use anyhow::Result;
+use std::collections::HashSet;
+
+/// Longest prompt name accepted, following the MCP naming guidance for tools.
+const MAX_NAME_LEN: usize = 128;
+
+/// Prompt names must be 1-128 characters of ASCII letters, digits, `_`, `-` or `.`,
+/// which is what MCP recommends and what strict clients enforce.
+pub fn validate_prompt_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Suggest a valid prompt name by replacing runs of invalid characters with `-`.
+pub fn suggest_prompt_name(name: &str) -> String {
+    let mut suggestion = String::new();
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            suggestion.push(c);
+        } else if !suggestion.ends_with('-') {
+            suggestion.push('-');
+        }
+    }
+    let suggestion = suggestion.trim_matches('-');
+    if suggestion.is_empty() {
+        return "prompt".to_string();
+    }
+    suggestion.chars().take(MAX_NAME_LEN).collect()
+}
+
+/// Suggest a valid argument name (see `formatter::validate_variable_name`).
+pub fn suggest_argument_name(name: &str) -> String {
+    let mut suggestion: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if unicode_ident::is_xid_continue(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if suggestion.is_empty() {
+        return "arg".to_string();
+    }
+    if suggestion.starts_with(|c: char| !unicode_ident::is_xid_start(c) && c != '_') {
+        suggestion.insert(0, '_');
+    }
+    suggestion
+}
+
+/// Warn about invalid prompt names, renaming them to the suggestion when `autofix` is set.
+pub fn check_prompt_names(prompts: &mut [PromptData], autofix: bool) {
+    for prompt in prompts {
+        if validate_prompt_name(&prompt.name) {
+            continue;
+        }
+        let suggestion = suggest_prompt_name(&prompt.name);
+        if autofix {
+            tracing::warn!(
+                file = %prompt.path,
+                "Renamed prompt '{}' to '{}' to satisfy MCP naming rules",
+                prompt.name,
+                suggestion
+            );
+            prompt.name = suggestion;
+        } else {
+            tracing::warn!(
+                file = %prompt.path,
+                "Prompt name '{}' may be rejected by MCP clients, consider '{}' (or use --autofix-names)",
+                prompt.name, suggestion
+            );
+        }
+    }
+}
+
+/// What to do when several prompts share a name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Fail loading.
+    Error,
+    /// Keep the first prompt.
+    Skip,
+    /// Keep the last prompt.
+    Overwrite,
+    /// Keep all, renaming later ones to `name-2`, `name-3`, ...
+    Suffix,
+}
+
+pub fn get_duplicate_policy(policy: &str) -> Result<DuplicatePolicy> {
+    match policy {
+        "error" => Ok(DuplicatePolicy::Error),
+        "skip" => Ok(DuplicatePolicy::Skip),
+        "overwrite" => Ok(DuplicatePolicy::Overwrite),
+        "suffix" => Ok(DuplicatePolicy::Suffix),
+        _ => anyhow::bail!("Unknown duplicate policy: {}", policy),
+    }
+}
+
+/// Apply `policy` to prompts sharing a name, in load order (earlier sources and
+/// files first), warning with both file paths.
+pub fn resolve_duplicates(
+    prompts: Vec<PromptData>,
+    policy: DuplicatePolicy,
+) -> Result<Vec<PromptData>> {
+    let mut names: HashSet<String> = HashSet::new();
+    let mut resolved: Vec<PromptData> = Vec::with_capacity(prompts.len());
+    for mut prompt in prompts {
+        if names.insert(prompt.name.clone()) {
+            resolved.push(prompt);
+            continue;
+        }
+        let existing = resolved.iter().position(|p| p.name == prompt.name).unwrap();
+        let first = &resolved[existing].path;
+        match policy {
+            DuplicatePolicy::Error => anyhow::bail!(
+                "Duplicate prompt name '{}' in {} and {}",
+                prompt.name,
+                resolved[existing].origin(),
+                prompt.origin()
+            ),
+            DuplicatePolicy::Skip => tracing::warn!(
+                "Duplicate prompt name '{}', keeping {} and skipping {}",
+                prompt.name,
+                first,
+                prompt.path
+            ),
+            DuplicatePolicy::Overwrite => {
+                tracing::warn!(
+                    "Duplicate prompt name '{}', {} overrides {}",
+                    prompt.name,
+                    prompt.path,
+                    first
+                );
+                resolved[existing] = prompt;
+            }
+            DuplicatePolicy::Suffix => {
+                let suggestion = (2..)
+                    .map(|i| format!("{}-{}", prompt.name, i))
+                    .find(|name| !names.contains(name))
+                    .unwrap();
+                tracing::warn!(
+                    "Duplicate prompt name '{}' in {} and {}, renaming the latter to '{}'",
+                    prompt.name,
+                    first,
+                    prompt.path,
+                    suggestion
+                );
+                names.insert(suggestion.clone());
+                prompt.name = suggestion;
+                resolved.push(prompt);
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_prompt_name() {
+        assert!(validate_prompt_name("code-review"));
+        assert!(validate_prompt_name("v1.2_final"));
+        assert!(!validate_prompt_name(""));
+        assert!(!validate_prompt_name("code review"));
+        assert!(!validate_prompt_name("dev/commit"));
+        assert!(!validate_prompt_name(&"a".repeat(129)));
+    }
+
+    #[test]
+    fn test_suggest_prompt_name() {
+        assert_eq!(suggest_prompt_name("Code Review!"), "Code-Review");
+        assert_eq!(suggest_prompt_name("dev / commit"), "dev-commit");
+        assert_eq!(suggest_prompt_name("???"), "prompt");
+        assert_eq!(suggest_prompt_name(&"a".repeat(200)).len(), 128);
+    }
+
+    #[test]
+    fn test_suggest_argument_name() {
+        assert_eq!(suggest_argument_name("file-path"), "file_path");
+        assert_eq!(suggest_argument_name("1st"), "_1st");
+        assert_eq!(suggest_argument_name(""), "arg");
+        assert_eq!(suggest_argument_name("ファイル パス"), "ファイル_パス");
+    }
+
+    #[test]
+    fn test_check_prompt_names_autofix() {
+        let mut prompts = vec![PromptData {
+            name: "my prompt".to_string(),
+            title: "My Prompt".to_string(),
+            description: String::new(),
+            arguments: vec![],
+            content: String::new(),
+            ..Default::default()
+        }];
+        check_prompt_names(&mut prompts, false);
+        assert_eq!(prompts[0].name, "my prompt");
+        check_prompt_names(&mut prompts, true);
+        assert_eq!(prompts[0].name, "my-prompt");
+    }
+
+    fn prompt(name: &str, path: &str) -> PromptData {
+        PromptData {
+            name: name.to_string(),
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve_duplicates() {
+        let prompts = || {
+            vec![
+                prompt("review", "coding/review.md"),
+                prompt("review", "writing/review.md"),
+                prompt("think", "think.md"),
+            ]
+        };
+        let paths = |prompts: &[PromptData]| -> Vec<(String, String)> {
+            prompts
+                .iter()
+                .map(|p| (p.name.clone(), p.path.clone()))
+                .collect()
+        };
+
+        let err = resolve_duplicates(prompts(), DuplicatePolicy::Error).unwrap_err();
+        assert!(err.to_string().contains("coding/review.md"));
+        assert!(err.to_string().contains("writing/review.md"));
+
+        let mut owned = prompts();
+        owned[1].author = Some("@bob".to_string());
+        owned[1].maintainer = Some("@alice".to_string());
+        let err = resolve_duplicates(owned, DuplicatePolicy::Error).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Duplicate prompt name 'review' in coding/review.md and writing/review.md, maintained by @alice"
+        );
+
+        let skipped = resolve_duplicates(prompts(), DuplicatePolicy::Skip).unwrap();
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[0].path, "coding/review.md");
+
+        let overwritten = resolve_duplicates(prompts(), DuplicatePolicy::Overwrite).unwrap();
+        assert_eq!(overwritten.len(), 2);
+        assert_eq!(overwritten[0].path, "writing/review.md");
+
+        let suffixed = resolve_duplicates(prompts(), DuplicatePolicy::Suffix).unwrap();
+        assert_eq!(
+            paths(&suffixed),
+            vec![
+                ("review".to_string(), "coding/review.md".to_string()),
+                ("review-2".to_string(), "writing/review.md".to_string()),
+                ("think".to_string(), "think.md".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use crate::model::PromptData;
+use anyhow::Result;
+use std::path::Path;
+
+/// File extension of prompt packs produced by `shinkuro bundle`.
+pub const EXTENSION: &str = "skpack";
+
+const MAGIC: &[u8; 8] = b"SKPACK\0\0";
+/// Bumped whenever the layout changes; older packs must be rebuilt.
+const VERSION: u32 = 19;
+/// Magic and version.
+const HEADER_LEN: usize = 8 + 4;
+
+/// Write `prompts` as a pack: a header followed by the bincode-serialized
+/// prompts, sorted by name. For duplicate names the later prompt wins,
+/// matching how sources are merged when serving.
+pub fn write_pack(path: &Path, prompts: &[PromptData]) -> Result<usize> {
+    let mut unique: Vec<&PromptData> = Vec::new();
+    for prompt in prompts {
+        unique.retain(|p| p.name != prompt.name);
+        unique.push(prompt);
+    }
+    unique.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bincode::serialize_into(&mut bytes, &unique)?;
+    std::fs::write(path, bytes)?;
+    Ok(unique.len())
+}
+
+/// Load every prompt of a pack in name order. The pack is a plain serialized
+/// bundle read in one go, which skips markdown and frontmatter parsing entirely.
+pub fn read_pack(path: &Path) -> Result<Vec<PromptData>> {
+    let bytes = std::fs::read(path)?;
+    if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+        anyhow::bail!("{} is not a prompt pack", path.display());
+    }
+    let version = u32::from_le_bytes(bytes[8..HEADER_LEN].try_into()?);
+    if version != VERSION {
+        anyhow::bail!(
+            "{} uses pack format version {}, expected {}; rebuild it with `shinkuro bundle`",
+            path.display(),
+            version,
+            VERSION
+        );
+    }
+    bincode::deserialize(&bytes[HEADER_LEN..])
+        .map_err(|e| anyhow::anyhow!("Corrupt prompt pack {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Argument, Fallback};
+    use crate::test_util::TempDir;
+
+    fn prompt(name: &str, content: &str) -> PromptData {
+        PromptData {
+            name: name.to_string(),
+            title: name.to_string(),
+            description: String::new(),
+            arguments: vec![Argument {
+                name: "user".to_string(),
+                fallback: vec![Fallback::Env("USER".to_string())],
+                ..Default::default()
+            }],
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pack_roundtrip() {
+        let dir = TempDir::new("pack");
+        let path = dir.join("roundtrip.skpack");
+        let prompts = vec![prompt("b", "second"), prompt("a", "first")];
+        assert_eq!(write_pack(&path, &prompts).unwrap(), 2);
+
+        let loaded = read_pack(&path).unwrap();
+        assert_eq!(loaded, vec![prompts[1].clone(), prompts[0].clone()]);
+    }
+
+    #[test]
+    fn test_pack_later_duplicate_wins() {
+        let dir = TempDir::new("pack");
+        let path = dir.join("duplicate.skpack");
+        let prompts = vec![prompt("a", "old"), prompt("a", "new")];
+        assert_eq!(write_pack(&path, &prompts).unwrap(), 1);
+        assert_eq!(read_pack(&path).unwrap()[0].content, "new");
+    }
+
+    #[test]
+    fn test_read_pack_rejects_other_files() {
+        let dir = TempDir::new("pack");
+        let path = dir.join("invalid.skpack");
+        std::fs::write(&path, "# Not a pack").unwrap();
+        assert!(read_pack(&path).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Paths given by users and paths derived from URLs for the cache, on Unix
+//! and Windows alike.
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Names Windows reserves for devices, with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// `path` with a leading `~` replaced by the home directory. On Windows `~\`
+/// works too, and `%NAME%` is replaced by the environment variable like `cmd`
+/// does, e.g. `%USERPROFILE%\prompts`.
+pub fn expand(path: &str) -> PathBuf {
+    let home = shellexpand::tilde("~");
+    let home = (home != "~").then_some(home.as_ref());
+    PathBuf::from(expand_with(path, cfg!(windows), home, |name| {
+        std::env::var(name).ok()
+    }))
+}
+
+/// `path` expanded and made absolute against the working directory. Drive
+/// letters, drive-relative paths like `C:prompts` and UNC paths are resolved
+/// the way Windows does.
+pub fn absolute(path: &str) -> Result<PathBuf> {
+    Ok(std::path::absolute(expand(path))?)
+}
+
+/// `name` as a single path component that's valid on every platform, for
+/// cache folders named after hosts, buckets or URL paths. Characters Windows
+/// reserves become `_`, as do trailing dots and spaces, which it strips.
+/// Device names like `CON` get a trailing `_`.
+pub fn component(name: &str) -> String {
+    let mut component: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let kept = component.trim_end_matches(['.', ' ']).len();
+    component.replace_range(kept.., &"_".repeat(component.len() - kept));
+    let stem = component.split('.').next().unwrap_or_default();
+    if component.is_empty() || RESERVED_NAMES.contains(&stem.to_uppercase().as_str()) {
+        component.push('_');
+    }
+    component
+}
+
+fn expand_with(
+    path: &str,
+    windows: bool,
+    home: Option<&str>,
+    var: impl Fn(&str) -> Option<String>,
+) -> String {
+    let path = if windows {
+        expand_variables(path, var)
+    } else {
+        path.to_string()
+    };
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home))
+            if rest.is_empty() || rest.starts_with('/') || (windows && rest.starts_with('\\')) =>
+        {
+            format!("{}{}", home, rest)
+        }
+        _ => path,
+    }
+}
+
+/// Replace `%NAME%` with the variable's value, leaving unknown names and a
+/// lone `%` as they are.
+fn expand_variables(path: &str, var: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('%') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('%') else {
+            rest = &rest[start..];
+            break;
+        };
+        match var(&after[..end]).filter(|_| end > 0) {
+            Some(value) => {
+                expanded.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                // The closing `%` may open the next variable
+                expanded.push('%');
+                expanded.push_str(&after[..end]);
+                rest = &after[end..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Option<String> {
+        match name {
+            "USERPROFILE" => Some(r"C:\Users\alice".to_string()),
+            "APPDATA" => Some(r"C:\Users\alice\AppData\Roaming".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        let unix = |path| expand_with(path, false, Some("/home/alice"), var);
+        assert_eq!(unix("~"), "/home/alice");
+        assert_eq!(unix("~/prompts"), "/home/alice/prompts");
+        assert_eq!(unix(r"~\prompts"), r"~\prompts");
+        assert_eq!(unix("~bob/prompts"), "~bob/prompts");
+        assert_eq!(unix("%USERPROFILE%/prompts"), "%USERPROFILE%/prompts");
+
+        let windows = |path| expand_with(path, true, Some(r"C:\Users\alice"), var);
+        assert_eq!(windows(r"~\prompts"), r"C:\Users\alice\prompts");
+        assert_eq!(windows("~/prompts"), r"C:\Users\alice/prompts");
+        assert_eq!(windows(r"%USERPROFILE%\prompts"), r"C:\Users\alice\prompts");
+        assert_eq!(
+            windows(r"%APPDATA%\shinkuro\%USERPROFILE%"),
+            r"C:\Users\alice\AppData\Roaming\shinkuro\C:\Users\alice"
+        );
+        assert_eq!(
+            windows(r"100%\%MISSING%\%APPDATA%"),
+            r"100%\%MISSING%\C:\Users\alice\AppData\Roaming"
+        );
+        assert_eq!(windows(r"C:\%%\50%"), r"C:\%%\50%");
+        assert_eq!(
+            windows(r"\\server\share\prompts"),
+            r"\\server\share\prompts"
+        );
+        assert_eq!(expand_with("~/prompts", false, None, var), "~/prompts");
+    }
+
+    #[test]
+    fn test_absolute() {
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(absolute("prompts").unwrap(), cwd.join("prompts"));
+        assert_eq!(absolute("./prompts").unwrap(), cwd.join("prompts"));
+        #[cfg(unix)]
+        assert_eq!(
+            absolute("/srv/prompts").unwrap(),
+            PathBuf::from("/srv/prompts")
+        );
+        #[cfg(windows)]
+        {
+            assert_eq!(
+                absolute(r"D:\prompts").unwrap(),
+                PathBuf::from(r"D:\prompts")
+            );
+            assert_eq!(
+                absolute(r"\\server\share\prompts").unwrap(),
+                PathBuf::from(r"\\server\share\prompts")
+            );
+            assert_eq!(
+                absolute("D:/team/prompts").unwrap(),
+                PathBuf::from(r"D:\team\prompts")
+            );
+        }
+    }
+
+    #[test]
+    fn test_component() {
+        assert_eq!(component("example.com"), "example.com");
+        assert_eq!(component("localhost:8080"), "localhost_8080");
+        assert_eq!(component("[::1]"), "[__1]");
+        assert_eq!(component(r#"a<b>c"d|e?f*g\h"#), "a_b_c_d_e_f_g_h");
+        assert_eq!(component("notes. "), "notes__");
+        assert_eq!(component("con"), "con_");
+        assert_eq!(component("NUL.md"), "NUL.md_");
+        assert_eq!(component("console"), "console");
+        assert_eq!(component(""), "_");
+    }
+}
@@ -0,0 +1,80 @@
+//! Stand-ins for what WASI lacks. There is no thread pool for blocking work or
+//! for tokio's stdio, so in `wasm32-wasip2` builds blocking work runs in place
+//! and stdio is read and written directly, blocking the single-threaded runtime.
+
+use anyhow::Result;
+use std::future::Future;
+
+#[cfg(not(target_os = "wasi"))]
+pub use tokio::io::{stdin, stdout, Stdin, Stdout};
+#[cfg(target_os = "wasi")]
+pub use wasi::{stdin, stdout, Stdin, Stdout};
+
+/// Run `f` on the blocking thread pool. It starts right away, so several can
+/// run concurrently before being awaited.
+pub fn spawn_blocking<F, R>(f: F) -> impl Future<Output = Result<R>>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    #[cfg(not(target_os = "wasi"))]
+    {
+        let handle = tokio::task::spawn_blocking(f);
+        async move { Ok(handle.await?) }
+    }
+    #[cfg(target_os = "wasi")]
+    {
+        let result = f();
+        async move { Ok(result) }
+    }
+}
+
+#[cfg(target_os = "wasi")]
+mod wasi {
+    use std::io::{Read, Write};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    pub struct Stdin(std::io::Stdin);
+
+    pub struct Stdout(std::io::Stdout);
+
+    pub fn stdin() -> Stdin {
+        Stdin(std::io::stdin())
+    }
+
+    pub fn stdout() -> Stdout {
+        Stdout(std::io::stdout())
+    }
+
+    impl AsyncRead for Stdin {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            let read = self.get_mut().0.read(buf.initialize_unfilled())?;
+            buf.advance(read);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncWrite for Stdout {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            data: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Poll::Ready(self.get_mut().0.write(data))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(self.get_mut().0.flush())
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            self.poll_flush(cx)
+        }
+    }
+}
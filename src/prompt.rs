@@ -1,13 +1,156 @@
-use crate::formatter::Formatter;
-use crate::model::PromptData;
+use crate::budget::{BudgetPolicy, RenderBudget};
+use crate::error::Error;
+use crate::formatter::{Formatter, Prepared};
+use crate::hooks::PostRenderHook;
+use crate::model::{ArgumentType, Fallback, PromptData};
 use anyhow::Result;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Clone, Debug)]
 pub struct PromptArgument {
     pub name: String,
     pub description: String,
     pub required: bool,
+    pub fallback: Vec<Fallback>,
+    pub enum_values: Vec<String>,
+    pub arg_type: ArgumentType,
+    pub pattern: Option<regex::Regex>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+}
+
+impl PromptArgument {
+    /// The description with the argument's constraints appended, since MCP
+    /// prompt arguments have no field for them.
+    pub fn describe(&self) -> String {
+        let mut constraints = Vec::new();
+        if self.arg_type != ArgumentType::String {
+            constraints.push(self.arg_type.as_str().to_string());
+        }
+        if let Some(bounds) = self.length_bounds() {
+            constraints.push(format!("{} characters", bounds));
+        }
+        if let Some(pattern) = &self.pattern {
+            constraints.push(format!("matching {}", pattern));
+        }
+        if !self.enum_values.is_empty() {
+            constraints.push(format!("one of: {}", self.enum_values.join(", ")));
+        }
+        match (self.description.is_empty(), constraints.is_empty()) {
+            (_, true) => self.description.clone(),
+            (true, false) => format!("({})", constraints.join("; ")),
+            (false, false) => format!("{} ({})", self.description, constraints.join("; ")),
+        }
+    }
+
+    fn length_bounds(&self) -> Option<String> {
+        match (self.min_length, self.max_length) {
+            (Some(min), Some(max)) => Some(format!("{}-{}", min, max)),
+            (Some(min), None) => Some(format!("at least {}", min)),
+            (None, Some(max)) => Some(format!("at most {}", max)),
+            (None, None) => None,
+        }
+    }
+
+    /// Check a value against the argument's constraints, returning it
+    /// normalized to the argument's type.
+    pub fn validate(&self, value: &str) -> Result<String, String> {
+        // Checked first so oversized values are rejected before any other work
+        let length = value.chars().count();
+        if self.min_length.is_some_and(|min| length < min)
+            || self.max_length.is_some_and(|max| length > max)
+        {
+            return Err(format!(
+                "Invalid value for argument '{}': {} characters, expected {}",
+                self.name,
+                length,
+                self.length_bounds().unwrap_or_default()
+            ));
+        }
+        let Some(value) = self.arg_type.coerce(value) else {
+            return Err(format!(
+                "Invalid value '{}' for argument '{}', expected a {}",
+                value,
+                self.name,
+                self.arg_type.as_str()
+            ));
+        };
+        // The pattern and allowed values apply to each item of a list
+        let items = match self.arg_type {
+            ArgumentType::List => serde_json::from_str(&value).unwrap_or_default(),
+            _ => vec![value.clone()],
+        };
+        for item in &items {
+            if let Some(pattern) = &self.pattern {
+                if !pattern.is_match(item) {
+                    return Err(format!(
+                        "Invalid value '{}' for argument '{}', expected to match {}",
+                        item, self.name, pattern
+                    ));
+                }
+            }
+            if !self.enum_values.is_empty() && !self.enum_values.contains(item) {
+                return Err(format!(
+                    "Invalid value '{}' for argument '{}', expected one of: {}",
+                    item,
+                    self.name,
+                    self.enum_values.join(", ")
+                ));
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Where the value used for an argument came from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgumentSource {
+    Client,
+    Session,
+    Env,
+    Default,
+}
+
+/// Variables resolved when rendering, unless the prompt declares an argument
+/// with the same name.
+pub const BUILTIN_VARIABLES: &[&str] = &["now", "today", "hostname", "cwd"];
+
+fn builtin_value(name: &str) -> Option<String> {
+    match name {
+        "now" => Some(chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false)),
+        "today" => Some(chrono::Local::now().format("%Y-%m-%d").to_string()),
+        "hostname" => Some(hostname()),
+        "cwd" => std::env::current_dir()
+            .ok()
+            .map(|dir| dir.display().to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_os = "wasi"))]
+fn hostname() -> String {
+    gethostname::gethostname().to_string_lossy().into_owned()
+}
+
+/// WASI has no hostname, so use the one passed in the environment.
+#[cfg(target_os = "wasi")]
+fn hostname() -> String {
+    std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string())
+}
+
+/// Rough token count for budgeting, assuming ~4 characters per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+#[derive(Debug)]
+pub struct Rendered {
+    pub text: String,
+    pub sources: HashMap<String, ArgumentSource>,
 }
 
 #[derive(Debug)]
@@ -17,39 +160,100 @@ pub struct MarkdownPrompt {
     pub description: String,
     pub arguments: Vec<PromptArgument>,
     pub content: String,
+    /// The file the prompt was loaded from.
+    pub path: String,
+    /// Used when rendering optional arguments; for required ones only as a
+    /// completion hint.
     pub arg_defaults: HashMap<String, String>,
-    formatter: Formatter,
+    pub post_render: Option<PostRenderHook>,
+    /// Checked after rendering and, when rejecting, also before the
+    /// post-render hook so oversized content never reaches the command.
+    pub budget: RenderBudget,
+    pub tags: Vec<String>,
+    /// Other names the prompt can be looked up by.
+    pub aliases: Vec<String>,
+    pub category: Option<String>,
+    pub version: Option<String>,
+    pub author: Option<String>,
+    pub maintainer: Option<String>,
+    /// Groups of argument names of which exactly one must be provided.
+    pub one_of: Vec<Vec<String>>,
+    /// Fail renders that leave placeholders unresolved instead of keeping them as written.
+    pub strict: bool,
+    /// Set to resolve `{file:path}` placeholders on each render, relative to
+    /// the prompt's folder and sandboxed to this root.
+    pub file_root: Option<PathBuf>,
+    /// The folder of the prompt's file.
+    folder: PathBuf,
+    /// Built-in variables the content uses, resolved on each render.
+    builtins: Vec<String>,
+    formatter: Arc<dyn Formatter>,
+    /// The content compiled once, for renders without file placeholders.
+    prepared: Prepared,
 }
 
 impl MarkdownPrompt {
     pub fn from_prompt_data(
         data: PromptData,
-        formatter: Formatter,
+        formatter: Arc<dyn Formatter>,
         auto_discover: bool,
     ) -> Result<Self> {
+        let mut discovered = formatter.extract_arguments(&data.content)?;
+        let mut builtins: Vec<String> = BUILTIN_VARIABLES
+            .iter()
+            .filter(|name| {
+                !data.arguments.iter().any(|a| a.name == **name) && discovered.remove(**name)
+            })
+            .map(|name| name.to_string())
+            .collect();
+        builtins.sort();
+        // Variables with an inline default like `{branch:-main}` are optional
+        let inline_defaults = formatter.inline_defaults(&data.content)?;
+        let prepared = formatter.prepare(&data.content)?;
         let (arguments, arg_defaults) = if auto_discover {
             if !data.arguments.is_empty() {
                 anyhow::bail!(
                     "prompt_data.arguments must be empty when auto_discover_args is enabled"
                 );
             }
-            let discovered = formatter.extract_arguments(&data.content)?;
             let mut args: Vec<_> = discovered.into_iter().collect();
             args.sort();
             (
                 args.into_iter()
                     .map(|name| PromptArgument {
+                        required: !inline_defaults.contains_key(&name),
                         name,
                         description: String::new(),
-                        required: true,
+                        fallback: Vec::new(),
+                        enum_values: Vec::new(),
+                        arg_type: ArgumentType::String,
+                        pattern: None,
+                        min_length: None,
+                        max_length: None,
                     })
                     .collect(),
-                HashMap::new(),
+                inline_defaults
+                    .keys()
+                    .map(|name| (name.clone(), String::new()))
+                    .collect(),
             )
         } else {
-            let discovered = formatter.extract_arguments(&data.content)?;
+            // Variables with an inline default don't need to be declared
+            let mut declared = data.arguments;
+            let mut undeclared: Vec<_> = discovered
+                .iter()
+                .filter(|name| {
+                    inline_defaults.contains_key(*name)
+                        && !declared.iter().any(|a| a.name == **name)
+                })
+                .collect();
+            undeclared.sort();
+            declared.extend(undeclared.into_iter().map(|name| crate::model::Argument {
+                name: name.clone(),
+                ..Default::default()
+            }));
             let provided: std::collections::HashSet<_> =
-                data.arguments.iter().map(|a| a.name.clone()).collect();
+                declared.iter().map(|a| a.name.clone()).collect();
             if discovered != provided {
                 anyhow::bail!(
                     "Content arguments {:?} don't match provided arguments {:?}",
@@ -58,21 +262,39 @@ impl MarkdownPrompt {
                 );
             }
             let mut defaults = HashMap::new();
-            let args = data
-                .arguments
+            let args = declared
                 .into_iter()
-                .map(|a| {
-                    let required = a.default.is_none();
-                    if let Some(d) = a.default {
+                .map(|a| -> Result<_> {
+                    // Group members are checked together when rendering instead
+                    let grouped = data.one_of.iter().flatten().any(|name| *name == a.name);
+                    let inline = inline_defaults.contains_key(&a.name);
+                    let required = !grouped
+                        && a.required
+                            .unwrap_or(a.default.is_none() && a.fallback.is_empty() && !inline);
+                    // Arguments marked optional without a default render as empty,
+                    // which also selects any inline default
+                    let empty = (grouped || inline || a.required == Some(false)).then(String::new);
+                    if let Some(d) = a.default.or(empty) {
                         defaults.insert(a.name.clone(), d);
                     }
-                    PromptArgument {
+                    let pattern = a
+                        .pattern
+                        .map(|p| regex::Regex::new(&p))
+                        .transpose()
+                        .map_err(|e| anyhow::anyhow!("Invalid pattern for '{}': {}", a.name, e))?;
+                    Ok(PromptArgument {
                         name: a.name,
                         description: a.description,
                         required,
-                    }
+                        fallback: a.fallback,
+                        enum_values: a.enum_values,
+                        arg_type: a.arg_type,
+                        pattern,
+                        min_length: a.min_length,
+                        max_length: a.max_length,
+                    })
                 })
-                .collect();
+                .collect::<Result<_>>()?;
             (args, defaults)
         };
 
@@ -83,30 +305,216 @@ impl MarkdownPrompt {
             arguments,
             content: data.content,
             arg_defaults,
+            post_render: None,
+            budget: RenderBudget {
+                max_bytes: data.max_render_bytes,
+                max_tokens: data.max_render_tokens,
+                ..Default::default()
+            },
+            tags: data.tags,
+            aliases: data.aliases,
+            category: data.category,
+            version: data.version,
+            author: data.author,
+            maintainer: data.maintainer,
+            one_of: data.one_of,
+            strict: data.strict_render.unwrap_or(false),
+            file_root: None,
+            folder: Path::new(&data.path)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            path: data.path,
+            builtins,
             formatter,
+            prepared,
         })
     }
 
-    pub fn render(&self, args: Option<HashMap<String, String>>) -> Result<String, String> {
-        let mut render_args = self.arg_defaults.clone();
-        if let Some(a) = args {
-            render_args.extend(a);
+    /// Render with each argument resolved through its fallback chain:
+    /// client-provided, then each declared fallback in order, then the default literal.
+    pub fn render(
+        &self,
+        args: Option<HashMap<String, String>>,
+        session: &HashMap<String, String>,
+    ) -> crate::Result<Rendered> {
+        let _span = tracing::debug_span!("render", prompt = %self.name).entered();
+        let mut render_args = args.unwrap_or_default();
+        let mut sources = HashMap::new();
+        for name in &self.builtins {
+            if let Some(value) = builtin_value(name) {
+                render_args.insert(name.clone(), value);
+            }
         }
 
         for arg in &self.arguments {
-            if arg.required && !render_args.contains_key(&arg.name) {
-                return Err(format!("Missing required arguments: {{{}}}", arg.name));
+            if let Some(value) = render_args.get(&arg.name) {
+                let value = arg.validate(value).map_err(Error::Argument)?;
+                render_args.insert(arg.name.clone(), value);
+                sources.insert(arg.name.clone(), ArgumentSource::Client);
+                continue;
+            }
+            match self.resolve_fallback(arg, session) {
+                Some((value, source)) => {
+                    // Optional arguments left out render as empty, whatever their constraints
+                    let omitted = source == ArgumentSource::Default && value.is_empty();
+                    let value = if omitted {
+                        value
+                    } else {
+                        arg.validate(&value).map_err(Error::Argument)?
+                    };
+                    render_args.insert(arg.name.clone(), value);
+                    sources.insert(arg.name.clone(), source);
+                }
+                None => {
+                    return Err(Error::Argument(format!(
+                        "Missing required arguments: {{{}}}",
+                        arg.name
+                    )))
+                }
             }
         }
+        for group in &self.one_of {
+            // Empty values count as missing, since some clients send them for unfilled fields
+            let provided: Vec<_> = group
+                .iter()
+                .filter(|name| {
+                    sources.get(*name) != Some(&ArgumentSource::Default)
+                        && render_args.get(*name).is_some_and(|v| !v.is_empty())
+                })
+                .collect();
+            if provided.len() != 1 {
+                let names: Vec<_> = group.iter().map(|name| format!("{{{}}}", name)).collect();
+                return Err(Error::Argument(format!(
+                    "Exactly one of {} must be provided, got {}",
+                    names.join(", "),
+                    provided.len()
+                )));
+            }
+        }
+
+        // Included files may change between renders, so such content is compiled each time
+        let formatted = match &self.file_root {
+            Some(root) => self
+                .include_files(root)
+                .and_then(|content| self.formatter.prepare(&content))
+                .and_then(|prepared| self.format(&prepared, &render_args)),
+            None => self.format(&self.prepared, &render_args),
+        };
+        let mut text = formatted.map_err(|e| {
+            Error::Render(format!("Failed to render prompt '{}': {}", self.name, e))
+        })?;
+        if let Some(hook) = &self.post_render {
+            if self.budget.on_exceed == BudgetPolicy::Reject {
+                self.budget
+                    .check(&self.name, &text)
+                    .map_err(Error::Render)?;
+            }
+            text = hook.apply(&self.name, text).map_err(Error::Render)?;
+        }
+        self.budget
+            .check(&self.name, &text)
+            .map_err(Error::Render)?;
+        tracing::debug!(
+            ?sources,
+            bytes = text.len(),
+            tokens = estimate_tokens(&text),
+            "Rendered"
+        );
+        Ok(Rendered { text, sources })
+    }
 
-        Ok(self.formatter.format(&self.content, &render_args))
+    /// The content with `variables` substituted and other placeholders kept as
+    /// written, without validation, fallbacks or the post-render hook. Used to
+    /// convert prompts for other tools.
+    pub fn substitute(&self, variables: &HashMap<String, String>) -> Result<String> {
+        match &self.file_root {
+            Some(root) => self
+                .formatter
+                .prepare(&self.include_files(root)?)?
+                .format(variables),
+            None => self.prepared.format(variables),
+        }
+    }
+
+    fn format(&self, prepared: &Prepared, variables: &HashMap<String, String>) -> Result<String> {
+        if self.strict {
+            prepared.format_strict(variables)
+        } else {
+            prepared.format(variables)
+        }
+    }
+
+    /// The content with `{file:path}` placeholders replaced by the text of
+    /// the files, which must be under `root`.
+    fn include_files(&self, root: &Path) -> Result<String> {
+        self.formatter.include_files(&self.content, &mut |path| {
+            let file = self
+                .folder
+                .join(path)
+                .canonicalize()
+                .map_err(|e| anyhow::anyhow!("Failed to include '{}': {}", path, e))?;
+            if !file.starts_with(root) {
+                anyhow::bail!("Failed to include '{}': outside the prompt folder", path);
+            }
+            std::fs::read_to_string(&file)
+                .map_err(|e| anyhow::anyhow!("Failed to include '{}': {}", path, e))
+        })
+    }
+
+    fn resolve_fallback(
+        &self,
+        arg: &PromptArgument,
+        session: &HashMap<String, String>,
+    ) -> Option<(String, ArgumentSource)> {
+        for fallback in &arg.fallback {
+            let found = match fallback {
+                Fallback::Session => session
+                    .get(&arg.name)
+                    .map(|v| (v.clone(), ArgumentSource::Session)),
+                Fallback::Env(var) => std::env::var(var).ok().map(|v| (v, ArgumentSource::Env)),
+            };
+            if found.is_some() {
+                return found;
+            }
+        }
+        if arg.required {
+            return None;
+        }
+        self.arg_defaults
+            .get(&arg.name)
+            .map(|v| (v.clone(), ArgumentSource::Default))
+    }
+
+    /// Completion candidates for an argument starting with `prefix`: its enum
+    /// values, or else its non-empty default.
+    pub fn complete(&self, arg_name: &str, prefix: &str) -> Vec<String> {
+        let Some(arg) = self.arguments.iter().find(|a| a.name == arg_name) else {
+            return Vec::new();
+        };
+        let candidates = if arg.enum_values.is_empty() {
+            self.arg_defaults
+                .get(arg_name)
+                .filter(|d| !d.is_empty())
+                .cloned()
+                .into_iter()
+                .collect()
+        } else {
+            arg.enum_values.clone()
+        };
+        candidates
+            .into_iter()
+            .filter(|c| c.starts_with(prefix))
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::formatter::BraceFormatter;
     use crate::model::Argument;
+    use crate::test_util::TempDir;
 
     #[test]
     fn test_markdown_prompt_from_prompt_data() {
@@ -118,11 +526,14 @@ mod tests {
                 name: "user".to_string(),
                 description: "User name".to_string(),
                 default: None,
+                ..Default::default()
             }],
             content: "Hello {user}".to_string(),
+            ..Default::default()
         };
 
-        let prompt = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, false).unwrap();
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
 
         assert_eq!(prompt.name, "test");
         assert_eq!(prompt.title, "Test Prompt");
@@ -143,11 +554,14 @@ mod tests {
                 name: "user".to_string(),
                 description: "User name".to_string(),
                 default: Some("guest".to_string()),
+                ..Default::default()
             }],
             content: "Hello {user}".to_string(),
+            ..Default::default()
         };
 
-        let prompt = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, false).unwrap();
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
 
         assert!(!prompt.arguments[0].required);
         assert_eq!(prompt.arg_defaults.get("user"), Some(&"guest".to_string()));
@@ -161,10 +575,12 @@ mod tests {
             description: "Test".to_string(),
             arguments: vec![],
             content: "Hello world".to_string(),
+            ..Default::default()
         };
 
-        let prompt = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, false).unwrap();
-        let result = prompt.render(None).unwrap();
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        let result = prompt.render(None, &HashMap::new()).unwrap().text;
 
         assert_eq!(result, "Hello world");
     }
@@ -179,14 +595,17 @@ mod tests {
                 name: "name".to_string(),
                 description: "Name".to_string(),
                 default: None,
+                ..Default::default()
             }],
             content: "Hello {name}!".to_string(),
+            ..Default::default()
         };
 
-        let prompt = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, false).unwrap();
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
         let mut args = HashMap::new();
         args.insert("name".to_string(), "Alice".to_string());
-        let result = prompt.render(Some(args)).unwrap();
+        let result = prompt.render(Some(args), &HashMap::new()).unwrap().text;
 
         assert_eq!(result, "Hello Alice!");
     }
@@ -201,12 +620,15 @@ mod tests {
                 name: "name".to_string(),
                 description: "Name".to_string(),
                 default: Some("World".to_string()),
+                ..Default::default()
             }],
             content: "Hello {name}!".to_string(),
+            ..Default::default()
         };
 
-        let prompt = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, false).unwrap();
-        let result = prompt.render(None).unwrap();
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        let result = prompt.render(None, &HashMap::new()).unwrap().text;
 
         assert_eq!(result, "Hello World!");
     }
@@ -221,14 +643,17 @@ mod tests {
                 name: "name".to_string(),
                 description: "Name".to_string(),
                 default: Some("World".to_string()),
+                ..Default::default()
             }],
             content: "Hello {name}!".to_string(),
+            ..Default::default()
         };
 
-        let prompt = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, false).unwrap();
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
         let mut args = HashMap::new();
         args.insert("name".to_string(), "Alice".to_string());
-        let result = prompt.render(Some(args)).unwrap();
+        let result = prompt.render(Some(args), &HashMap::new()).unwrap().text;
 
         assert_eq!(result, "Hello Alice!");
     }
@@ -243,15 +668,21 @@ mod tests {
                 name: "name".to_string(),
                 description: "Name".to_string(),
                 default: None,
+                ..Default::default()
             }],
             content: "Hello {name}!".to_string(),
+            ..Default::default()
         };
 
-        let prompt = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, false).unwrap();
-        let result = prompt.render(None);
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        let result = prompt.render(None, &HashMap::new());
 
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Missing required arguments"));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Missing required arguments"));
     }
 
     #[test]
@@ -262,9 +693,11 @@ mod tests {
             description: "Test".to_string(),
             arguments: vec![],
             content: "Hello {user} from {project}".to_string(),
+            ..Default::default()
         };
 
-        let prompt = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, true).unwrap();
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), true).unwrap();
 
         assert_eq!(prompt.arguments.len(), 2);
         let names: Vec<_> = prompt.arguments.iter().map(|a| a.name.as_str()).collect();
@@ -282,11 +715,13 @@ mod tests {
                 name: "user".to_string(),
                 description: "User".to_string(),
                 default: None,
+                ..Default::default()
             }],
             content: "Hello {user}".to_string(),
+            ..Default::default()
         };
 
-        let result = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, true);
+        let result = MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), true);
 
         assert!(result.is_err());
         assert!(result
@@ -305,13 +740,594 @@ mod tests {
                 name: "user".to_string(),
                 description: "User".to_string(),
                 default: None,
+                ..Default::default()
             }],
             content: "Hello {name}".to_string(),
+            ..Default::default()
         };
 
-        let result = MarkdownPrompt::from_prompt_data(data, Formatter::Brace, false);
+        let result = MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false);
 
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("don't match"));
     }
+
+    #[test]
+    fn test_markdown_prompt_render_fallback_chain() {
+        let data = PromptData {
+            name: "test".to_string(),
+            title: "Test".to_string(),
+            description: "Test".to_string(),
+            arguments: vec![Argument {
+                name: "branch".to_string(),
+                description: "Branch".to_string(),
+                default: Some("main".to_string()),
+                fallback: vec![
+                    Fallback::Session,
+                    Fallback::Env("SHINKURO_TEST_FALLBACK_BRANCH".to_string()),
+                ],
+                ..Default::default()
+            }],
+            content: "On {branch}".to_string(),
+            ..Default::default()
+        };
+
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert!(!prompt.arguments[0].required);
+
+        let rendered = prompt.render(None, &HashMap::new()).unwrap();
+        assert_eq!(rendered.text, "On main");
+        assert_eq!(rendered.sources["branch"], ArgumentSource::Default);
+
+        std::env::set_var("SHINKURO_TEST_FALLBACK_BRANCH", "dev");
+        let rendered = prompt.render(None, &HashMap::new()).unwrap();
+        assert_eq!(rendered.text, "On dev");
+        assert_eq!(rendered.sources["branch"], ArgumentSource::Env);
+
+        let mut session = HashMap::new();
+        session.insert("branch".to_string(), "feature".to_string());
+        let rendered = prompt.render(None, &session).unwrap();
+        assert_eq!(rendered.text, "On feature");
+        assert_eq!(rendered.sources["branch"], ArgumentSource::Session);
+
+        let mut args = HashMap::new();
+        args.insert("branch".to_string(), "release".to_string());
+        let rendered = prompt.render(Some(args), &session).unwrap();
+        assert_eq!(rendered.text, "On release");
+        assert_eq!(rendered.sources["branch"], ArgumentSource::Client);
+    }
+
+    #[test]
+    fn test_markdown_prompt_render_fallback_exhausted() {
+        let data = PromptData {
+            name: "test".to_string(),
+            title: "Test".to_string(),
+            description: "Test".to_string(),
+            arguments: vec![Argument {
+                name: "user".to_string(),
+                description: "User".to_string(),
+                default: None,
+                fallback: vec![Fallback::Session],
+                ..Default::default()
+            }],
+            content: "Hello {user}".to_string(),
+            ..Default::default()
+        };
+
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert!(!prompt.arguments[0].required);
+        let result = prompt.render(None, &HashMap::new());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Missing required arguments"));
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn test_markdown_prompt_enum_argument() {
+        let data = PromptData {
+            name: "test".to_string(),
+            arguments: vec![Argument {
+                name: "lang".to_string(),
+                description: "Language".to_string(),
+                enum_values: vec!["rust".to_string(), "go".to_string()],
+                ..Default::default()
+            }],
+            content: "Write {lang}".to_string(),
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert_eq!(
+            prompt.arguments[0].describe(),
+            "Language (one of: rust, go)"
+        );
+
+        let args = |value: &str| Some(HashMap::from([("lang".to_string(), value.to_string())]));
+        assert_eq!(
+            prompt.render(args("go"), &HashMap::new()).unwrap().text,
+            "Write go"
+        );
+        let err = prompt
+            .render(args("java"), &HashMap::new())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "Invalid value 'java' for argument 'lang', expected one of: rust, go"
+        );
+    }
+
+    #[test]
+    fn test_markdown_prompt_typed_arguments() {
+        let data = PromptData {
+            name: "test".to_string(),
+            arguments: vec![
+                Argument {
+                    name: "count".to_string(),
+                    description: "Count".to_string(),
+                    arg_type: ArgumentType::Number,
+                    ..Default::default()
+                },
+                Argument {
+                    name: "verbose".to_string(),
+                    arg_type: ArgumentType::Boolean,
+                    ..Default::default()
+                },
+            ],
+            content: "Generate {count} test cases, verbose: {verbose}".to_string(),
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert_eq!(prompt.arguments[0].describe(), "Count (number)");
+
+        let args = |count: &str, verbose: &str| {
+            Some(HashMap::from([
+                ("count".to_string(), count.to_string()),
+                ("verbose".to_string(), verbose.to_string()),
+            ]))
+        };
+        assert_eq!(
+            prompt
+                .render(args(" 42 ", "Yes"), &HashMap::new())
+                .unwrap()
+                .text,
+            "Generate 42 test cases, verbose: true"
+        );
+        assert_eq!(
+            prompt
+                .render(args("2.5", "0"), &HashMap::new())
+                .unwrap()
+                .text,
+            "Generate 2.5 test cases, verbose: false"
+        );
+        let err = prompt
+            .render(args("many", "true"), &HashMap::new())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "Invalid value 'many' for argument 'count', expected a number"
+        );
+        assert!(prompt.render(args("1", "maybe"), &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_markdown_prompt_list_argument() {
+        let data = PromptData {
+            name: "review".to_string(),
+            arguments: vec![Argument {
+                name: "files".to_string(),
+                arg_type: ArgumentType::List,
+                pattern: Some(r"\.rs$".to_string()),
+                ..Default::default()
+            }],
+            content: "Review {files|join:\", \"}".to_string(),
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        let render = |files: &str| {
+            let args = HashMap::from([("files".to_string(), files.to_string())]);
+            prompt.render(Some(args), &HashMap::new())
+        };
+        assert_eq!(
+            render(r#"["a.rs", "b.rs"]"#).unwrap().text,
+            "Review a.rs, b.rs"
+        );
+        assert_eq!(
+            render("a.rs\n\n b.rs \n").unwrap().text,
+            "Review a.rs, b.rs"
+        );
+        assert_eq!(
+            render(r#"["a.rs", "b.py"]"#).unwrap_err().to_string(),
+            "Invalid value 'b.py' for argument 'files', expected to match \\.rs$"
+        );
+    }
+
+    #[test]
+    fn test_markdown_prompt_pattern_argument() {
+        let data = PromptData {
+            name: "test".to_string(),
+            arguments: vec![Argument {
+                name: "ticket".to_string(),
+                pattern: Some(r"^[A-Z]+-\d+$".to_string()),
+                ..Default::default()
+            }],
+            content: "Fix {ticket}".to_string(),
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert_eq!(prompt.arguments[0].describe(), r"(matching ^[A-Z]+-\d+$)");
+
+        let args = |value: &str| Some(HashMap::from([("ticket".to_string(), value.to_string())]));
+        assert_eq!(
+            prompt
+                .render(args("ABC-123"), &HashMap::new())
+                .unwrap()
+                .text,
+            "Fix ABC-123"
+        );
+        let err = prompt
+            .render(args("abc"), &HashMap::new())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            r"Invalid value 'abc' for argument 'ticket', expected to match ^[A-Z]+-\d+$"
+        );
+    }
+
+    #[test]
+    fn test_markdown_prompt_length_limits() {
+        let data = PromptData {
+            name: "test".to_string(),
+            arguments: vec![Argument {
+                name: "code".to_string(),
+                min_length: Some(1),
+                max_length: Some(5),
+                ..Default::default()
+            }],
+            content: "Review {code}".to_string(),
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert_eq!(prompt.arguments[0].describe(), "(1-5 characters)");
+
+        let args = |value: &str| Some(HashMap::from([("code".to_string(), value.to_string())]));
+        assert_eq!(
+            prompt.render(args("fn()"), &HashMap::new()).unwrap().text,
+            "Review fn()"
+        );
+        assert!(prompt.render(args("ünï"), &HashMap::new()).is_ok());
+        assert_eq!(
+            prompt
+                .render(args(""), &HashMap::new())
+                .unwrap_err()
+                .to_string(),
+            "Invalid value for argument 'code': 0 characters, expected 1-5"
+        );
+        assert!(prompt.render(args("fn main()"), &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_markdown_prompt_explicit_required() {
+        let data = PromptData {
+            name: "test".to_string(),
+            arguments: vec![
+                Argument {
+                    name: "extra".to_string(),
+                    required: Some(false),
+                    ..Default::default()
+                },
+                Argument {
+                    name: "branch".to_string(),
+                    default: Some("main".to_string()),
+                    required: Some(true),
+                    ..Default::default()
+                },
+            ],
+            content: "On {branch}{extra}".to_string(),
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert!(!prompt.arguments[0].required);
+        assert!(prompt.arguments[1].required);
+
+        let result = prompt.render(None, &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Missing required arguments: {branch}"
+        );
+
+        let args = HashMap::from([("branch".to_string(), "dev".to_string())]);
+        let rendered = prompt.render(Some(args), &HashMap::new()).unwrap();
+        assert_eq!(rendered.text, "On dev");
+
+        assert_eq!(prompt.complete("branch", "m"), vec!["main".to_string()]);
+        assert!(prompt.complete("branch", "x").is_empty());
+        assert!(prompt.complete("extra", "").is_empty());
+    }
+
+    #[test]
+    fn test_markdown_prompt_optional_constrained_arguments() {
+        let data = PromptData {
+            name: "test".to_string(),
+            arguments: vec![
+                Argument {
+                    name: "count".to_string(),
+                    arg_type: ArgumentType::Number,
+                    required: Some(false),
+                    ..Default::default()
+                },
+                Argument {
+                    name: "lang".to_string(),
+                    enum_values: vec!["rust".to_string(), "go".to_string()],
+                    min_length: Some(2),
+                    required: Some(false),
+                    ..Default::default()
+                },
+                Argument {
+                    name: "diff".to_string(),
+                    arg_type: ArgumentType::Boolean,
+                    ..Default::default()
+                },
+                Argument {
+                    name: "file_path".to_string(),
+                    pattern: Some(r"\.rs$".to_string()),
+                    ..Default::default()
+                },
+            ],
+            content: "[{count}][{lang}][{diff}][{file_path}]".to_string(),
+            one_of: vec![vec!["diff".to_string(), "file_path".to_string()]],
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+
+        let args = HashMap::from([("file_path".to_string(), "main.rs".to_string())]);
+        let rendered = prompt.render(Some(args), &HashMap::new()).unwrap();
+        assert_eq!(rendered.text, "[][][][main.rs]");
+        assert_eq!(rendered.sources["count"], ArgumentSource::Default);
+
+        // Provided values are still validated
+        let args = HashMap::from([
+            ("count".to_string(), "many".to_string()),
+            ("diff".to_string(), "yes".to_string()),
+        ]);
+        let result = prompt.render(Some(args), &HashMap::new());
+        assert!(result.unwrap_err().to_string().contains("'count'"));
+    }
+
+    #[test]
+    fn test_markdown_prompt_builtin_variables() {
+        let data = PromptData {
+            name: "status".to_string(),
+            arguments: vec![Argument {
+                name: "cwd".to_string(),
+                ..Default::default()
+            }],
+            content: "As of {today} on {hostname} in {cwd}".to_string(),
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert_eq!(prompt.builtins, vec!["hostname", "today"]);
+        // Declared arguments shadow built-ins
+        assert_eq!(prompt.arguments.len(), 1);
+
+        let args = HashMap::from([
+            ("cwd".to_string(), "/repo".to_string()),
+            ("today".to_string(), "ignored".to_string()),
+        ]);
+        let rendered = prompt.render(Some(args), &HashMap::new()).unwrap();
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert!(rendered.text.starts_with(&format!("As of {} on ", today)));
+        assert!(rendered.text.ends_with(" in /repo"));
+    }
+
+    #[test]
+    fn test_markdown_prompt_inline_defaults() {
+        let data = PromptData {
+            name: "commit".to_string(),
+            arguments: vec![Argument {
+                name: "style".to_string(),
+                ..Default::default()
+            }],
+            content: "Write a {style:-concise} message for {branch:-main}".to_string(),
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        let arguments: Vec<_> = prompt
+            .arguments
+            .iter()
+            .map(|a| (a.name.as_str(), a.required))
+            .collect();
+        assert_eq!(arguments, vec![("style", false), ("branch", false)]);
+        let rendered = prompt.render(None, &HashMap::new()).unwrap();
+        assert_eq!(rendered.text, "Write a concise message for main");
+        let args = HashMap::from([("branch".to_string(), "dev".to_string())]);
+        let rendered = prompt.render(Some(args), &HashMap::new()).unwrap();
+        assert_eq!(rendered.text, "Write a concise message for dev");
+
+        let data = PromptData {
+            name: "commit".to_string(),
+            content: "{style:-concise} {topic}".to_string(),
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), true).unwrap();
+        let arguments: Vec<_> = prompt
+            .arguments
+            .iter()
+            .map(|a| (a.name.as_str(), a.required))
+            .collect();
+        assert_eq!(arguments, vec![("style", false), ("topic", true)]);
+    }
+
+    #[test]
+    fn test_markdown_prompt_one_of() {
+        let data = PromptData {
+            name: "review".to_string(),
+            arguments: vec![
+                Argument {
+                    name: "diff".to_string(),
+                    ..Default::default()
+                },
+                Argument {
+                    name: "file_path".to_string(),
+                    ..Default::default()
+                },
+            ],
+            content: "Review {diff}{file_path}".to_string(),
+            one_of: vec![vec!["diff".to_string(), "file_path".to_string()]],
+            ..Default::default()
+        };
+        let prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert!(prompt.arguments.iter().all(|a| !a.required));
+
+        let args = HashMap::from([("file_path".to_string(), "src/main.rs".to_string())]);
+        let rendered = prompt.render(Some(args), &HashMap::new()).unwrap();
+        assert_eq!(rendered.text, "Review src/main.rs");
+
+        let result = prompt.render(None, &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Exactly one of {diff}, {file_path} must be provided, got 0"
+        );
+        let args = HashMap::from([
+            ("diff".to_string(), "+fn main() {}".to_string()),
+            ("file_path".to_string(), "src/main.rs".to_string()),
+        ]);
+        let result = prompt.render(Some(args), &HashMap::new());
+        assert!(result.unwrap_err().to_string().ends_with("got 2"));
+        let args = HashMap::from([
+            ("diff".to_string(), String::new()),
+            ("file_path".to_string(), "src/main.rs".to_string()),
+        ]);
+        assert!(prompt.render(Some(args), &HashMap::new()).is_ok());
+    }
+
+    #[test]
+    fn test_markdown_prompt_strict_render() {
+        let data = PromptData {
+            name: "greet".to_string(),
+            arguments: vec![Argument {
+                name: "user".to_string(),
+                ..Default::default()
+            }],
+            content: "Hi {user.name} <{user.email}>".to_string(),
+            strict_render: Some(true),
+            ..Default::default()
+        };
+        let mut prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        assert!(prompt.strict);
+        let args = HashMap::from([("user".to_string(), r#"{"name": "Alice"}"#.to_string())]);
+        let result = prompt.render(Some(args.clone()), &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to render prompt 'greet': Unresolved placeholders: {user.email}"
+        );
+
+        prompt.strict = false;
+        let rendered = prompt.render(Some(args), &HashMap::new()).unwrap();
+        assert_eq!(rendered.text, "Hi Alice <{user.email}>");
+    }
+
+    #[test]
+    fn test_markdown_prompt_render_budget() {
+        let data = PromptData {
+            name: "review".to_string(),
+            arguments: vec![Argument {
+                name: "code".to_string(),
+                ..Default::default()
+            }],
+            content: "Review {code}".to_string(),
+            max_render_bytes: Some(16),
+            ..Default::default()
+        };
+        let mut prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap();
+        let args = |code: &str| Some(HashMap::from([("code".to_string(), code.to_string())]));
+        assert!(prompt.render(args("x = 1"), &HashMap::new()).is_ok());
+        let result = prompt.render(args("fn main() {}"), &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Rendered prompt 'review' is 19 bytes, over the budget of 16"
+        );
+
+        // Checked before the command runs, and on its output
+        #[cfg(unix)]
+        {
+            use crate::hooks::FailurePolicy;
+            let hook = |command: &str| PostRenderHook {
+                command: command.to_string(),
+                timeout: std::time::Duration::from_secs(5),
+                on_failure: FailurePolicy::Error,
+            };
+            prompt.post_render = Some(hook("exit 3"));
+            let result = prompt.render(args("fn main() {}"), &HashMap::new());
+            assert!(result.unwrap_err().to_string().contains("over the budget"));
+            prompt.post_render = Some(hook(r#"x=$(cat); printf '%s%s' "$x" "$x""#));
+            let result = prompt.render(args("x = 1"), &HashMap::new());
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "Rendered prompt 'review' is 24 bytes, over the budget of 16"
+            );
+
+            prompt.budget.on_exceed = crate::budget::BudgetPolicy::Warn;
+            let rendered = prompt.render(args("x = 1"), &HashMap::new()).unwrap();
+            assert_eq!(rendered.text, "Review x = 1Review x = 1");
+        }
+    }
+
+    #[test]
+    fn test_markdown_prompt_file_placeholders() {
+        let dir = TempDir::new("file-placeholders");
+        let root = dir.join("prompts");
+        std::fs::create_dir_all(root.join("snippets")).unwrap();
+        std::fs::write(root.join("snippets/rules.md"), "Keep {it} short").unwrap();
+        std::fs::write(dir.join("secret.md"), "secret").unwrap();
+        let data = PromptData {
+            name: "review".to_string(),
+            content: "{file:./snippets/rules.md}\n{file:../secret.md}".to_string(),
+            path: root.join("review.md").display().to_string(),
+            ..Default::default()
+        };
+        let mut prompt =
+            MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), true).unwrap();
+        assert!(prompt.arguments.is_empty());
+
+        let rendered = prompt.render(None, &HashMap::new()).unwrap();
+        assert_eq!(
+            rendered.text,
+            "{file:./snippets/rules.md}\n{file:../secret.md}"
+        );
+
+        prompt.file_root = Some(root.canonicalize().unwrap());
+        let result = prompt.render(None, &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to render prompt 'review': Failed to include '../secret.md': outside the prompt folder"
+        );
+        prompt.content = "Rules: {file:snippets/rules.md}".to_string();
+        let rendered = prompt.render(None, &HashMap::new()).unwrap();
+        assert_eq!(rendered.text, "Rules: Keep {it} short");
+    }
 }
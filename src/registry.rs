@@ -0,0 +1,155 @@
+use crate::prompt::MarkdownPrompt;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Prompts by name and alias, ordered by name so listings are stable.
+///
+/// A prompt shadows an earlier one of the same name, dropping its aliases.
+/// Names always win over aliases, and an alias claimed by several prompts
+/// points at the last one inserted.
+#[derive(Debug, Clone, Default)]
+pub struct PromptRegistry {
+    prompts: BTreeMap<String, Arc<MarkdownPrompt>>,
+    /// Alias to prompt name.
+    aliases: BTreeMap<String, String>,
+}
+
+impl PromptRegistry {
+    /// A registry of `prompts` in load order, warning about conflicting aliases.
+    pub fn new(prompts: impl IntoIterator<Item = MarkdownPrompt>) -> Self {
+        let mut registry = Self::default();
+        for prompt in prompts {
+            registry.insert(prompt);
+        }
+        registry.check_aliases();
+        registry
+    }
+
+    pub fn insert(&mut self, prompt: impl Into<Arc<MarkdownPrompt>>) {
+        let prompt = prompt.into();
+        if self.prompts.contains_key(&prompt.name) {
+            self.aliases.retain(|_, name| *name != prompt.name);
+        }
+        for alias in &prompt.aliases {
+            self.aliases.insert(alias.clone(), prompt.name.clone());
+        }
+        self.prompts.insert(prompt.name.clone(), prompt);
+    }
+
+    /// Insert every prompt of `other`, shadowing prompts of the same name.
+    pub fn extend(&mut self, other: &PromptRegistry) {
+        for prompt in other.prompts.values() {
+            self.insert(prompt.clone());
+        }
+    }
+
+    /// Look a prompt up by name or alias.
+    pub fn get(&self, name: &str) -> Option<&Arc<MarkdownPrompt>> {
+        self.prompts.get(self.resolve_alias(name)?)
+    }
+
+    /// The name of the prompt `name` refers to, which is `name` itself unless
+    /// it's an alias.
+    pub fn resolve_alias<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        if self.prompts.contains_key(name) {
+            return Some(name);
+        }
+        self.aliases.get(name).map(String::as_str)
+    }
+
+    /// Prompts with the tag `tag`, ordered by name.
+    pub fn find_by_tag<'a>(
+        &'a self,
+        tag: &'a str,
+    ) -> impl Iterator<Item = &'a Arc<MarkdownPrompt>> + 'a {
+        self.iter_sorted()
+            .filter(move |prompt| prompt.tags.iter().any(|t| t == tag))
+    }
+
+    /// Prompts ordered by name.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = &Arc<MarkdownPrompt>> {
+        self.prompts.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.prompts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prompts.is_empty()
+    }
+
+    fn check_aliases(&self) {
+        for prompt in self.prompts.values() {
+            for alias in &prompt.aliases {
+                if self.prompts.contains_key(alias) {
+                    tracing::warn!(
+                        file = %prompt.path,
+                        "Alias '{}' of '{}' is also a prompt name, ignoring it",
+                        alias,
+                        prompt.name
+                    );
+                } else if self.aliases[alias] != prompt.name {
+                    tracing::warn!(
+                        file = %prompt.path,
+                        "Alias '{}' of '{}' is taken by '{}'",
+                        alias,
+                        prompt.name,
+                        self.aliases[alias]
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter::BraceFormatter;
+    use crate::model::PromptData;
+
+    fn prompt(name: &str, aliases: &[&str], tags: &[&str]) -> MarkdownPrompt {
+        let data = PromptData {
+            name: name.to_string(),
+            content: name.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        MarkdownPrompt::from_prompt_data(data, Arc::new(BraceFormatter), false).unwrap()
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = PromptRegistry::new([
+            prompt("review", &["cr", "check"], &["rust"]),
+            prompt("commit", &["ci", "check"], &[]),
+            prompt("lint", &["review"], &["rust", "style"]),
+        ]);
+        let names = |prompts: Vec<&Arc<MarkdownPrompt>>| -> Vec<String> {
+            prompts.into_iter().map(|p| p.name.clone()).collect()
+        };
+        assert_eq!(
+            names(registry.iter_sorted().collect()),
+            vec!["commit", "lint", "review"]
+        );
+        assert_eq!(
+            names(registry.find_by_tag("rust").collect()),
+            vec!["lint", "review"]
+        );
+        assert_eq!(registry.resolve_alias("cr"), Some("review"));
+        assert_eq!(registry.resolve_alias("commit"), Some("commit"));
+        assert_eq!(registry.resolve_alias("missing"), None);
+        // Later prompts take contested aliases, names beat aliases
+        assert_eq!(registry.get("check").unwrap().name, "commit");
+        assert_eq!(registry.get("review").unwrap().name, "review");
+
+        // Shadowing a prompt drops its aliases
+        registry.insert(prompt("review", &["rv"], &[]));
+        assert_eq!(registry.len(), 3);
+        assert!(registry.get("cr").is_none());
+        assert_eq!(registry.get("rv").unwrap().name, "review");
+        assert_eq!(registry.find_by_tag("rust").count(), 1);
+    }
+}
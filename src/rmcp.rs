@@ -0,0 +1,204 @@
+//! Serve a [`PromptLibrary`] with the official Rust MCP SDK, [`rmcp`](::rmcp),
+//! either as a whole server or mounted next to existing prompts and tools.
+
+use crate::error::Error;
+use crate::library::PromptLibrary;
+use crate::mcp::{argument_values, prompt_json};
+use crate::prompt::MarkdownPrompt;
+use ::rmcp::handler::server::router::prompt::{PromptRoute, PromptRouter};
+use ::rmcp::model::{
+    ErrorCode, GetPromptRequestParams, GetPromptResponse, GetPromptResult, Implementation,
+    JsonObject, ListPromptsResult, MetaObject, PaginatedRequestParams, Prompt, PromptMessage, Role,
+    ServerCapabilities, ServerConfig,
+};
+use ::rmcp::service::RequestContext;
+use ::rmcp::{ErrorData, RoleServer, ServerHandler};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+impl PromptLibrary {
+    /// A route per prompt, to merge into the router of an existing server,
+    /// e.g. `Self::prompt_router() + library.prompt_router()`. Aliases are
+    /// only resolved when serving the library itself.
+    pub fn prompt_router<S: Send + Sync + 'static>(&self) -> PromptRouter<S> {
+        let mut router = PromptRouter::new();
+        for prompt in self.registry().iter_sorted() {
+            let prompt = prompt.clone();
+            router.add_route(PromptRoute::new_dyn(attr(&prompt), move |context| {
+                let prompt = prompt.clone();
+                Box::pin(async move { get_prompt(prompt, context.arguments).await })
+            }));
+        }
+        router
+    }
+}
+
+/// The library as a prompts-only server, e.g.
+/// `library.serve(rmcp::transport::stdio()).await?`.
+impl ServerHandler for PromptLibrary {
+    fn get_info(&self) -> ServerConfig {
+        ServerConfig::new(ServerCapabilities::builder().enable_prompts().build())
+            .with_server_info(Implementation::new("shinkuro", env!("CARGO_PKG_VERSION")))
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, ErrorData> {
+        Ok(ListPromptsResult::with_all_items(
+            self.prompts().map(attr).collect(),
+        ))
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResponse, ErrorData> {
+        let prompt = self
+            .registry()
+            .get(&request.name)
+            .ok_or_else(|| error(Error::PromptNotFound(request.name.clone())))?;
+        get_prompt(prompt.clone(), request.arguments).await
+    }
+}
+
+/// The prompt as listed in `prompts/list`, metadata included.
+fn attr(prompt: &MarkdownPrompt) -> Prompt {
+    serde_json::from_value(prompt_json(prompt)).expect("prompt JSON matches the MCP schema")
+}
+
+/// Render on a blocking thread, since post-render commands block. Session
+/// fallbacks aren't available here.
+async fn get_prompt(
+    prompt: Arc<MarkdownPrompt>,
+    arguments: Option<JsonObject>,
+) -> Result<GetPromptResponse, ErrorData> {
+    let args = arguments.and_then(|a| argument_values(&Value::Object(a)));
+    let rendered = tokio::task::spawn_blocking(move || prompt.render(args, &HashMap::new()))
+        .await
+        .map_err(|e| error(Error::Render(e.to_string())))?
+        .map_err(error)?;
+    let mut result = GetPromptResult::new(vec![PromptMessage::new_text(Role::User, rendered.text)]);
+    let meta = json!({ "argumentSources": rendered.sources });
+    if let Value::Object(meta) = meta {
+        result.meta = Some(MetaObject(meta));
+    }
+    Ok(result.into())
+}
+
+fn error(error: Error) -> ErrorData {
+    ErrorData::new(ErrorCode(error.code()), error.to_string(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use ::rmcp::ServiceExt;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines, ReadHalf};
+
+    type Client = (
+        tokio::io::WriteHalf<DuplexStream>,
+        Lines<BufReader<ReadHalf<DuplexStream>>>,
+    );
+
+    /// Send `message`, returning the response to it, if it's a request.
+    async fn send((write, lines): &mut Client, message: Value) -> Option<Value> {
+        write
+            .write_all(format!("{}\n", message).as_bytes())
+            .await
+            .unwrap();
+        message.get("id")?;
+        let line = lines.next_line().await.unwrap().unwrap();
+        Some(serde_json::from_str(&line).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_rmcp() {
+        let dir = TempDir::new("rmcp");
+        std::fs::write(
+            dir.join("review.md"),
+            "---\ntags: [rust]\naliases: [cr]\narguments:\n  - name: code\n---\nReview {code}",
+        )
+        .unwrap();
+        let library = PromptLibrary::builder().folder(&*dir).load().await.unwrap();
+
+        // Mounted next to other prompts
+        let router: PromptRouter<()> = library.prompt_router();
+        let prompts = router.list_all();
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].name, "review");
+        assert_eq!(prompts[0].arguments.as_ref().unwrap()[0].name, "code");
+        assert_eq!(prompts[0].meta.as_ref().unwrap().0["tags"], json!(["rust"]));
+
+        // Served on its own
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let running = library.serve(tokio::io::split(server)).await.unwrap();
+            running.waiting().await.unwrap();
+        });
+        let (read, write) = tokio::io::split(client);
+        let mut client = (write, BufReader::new(read).lines());
+        let request = |id: u64, method: &str, params: Value| json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+
+        let initialized = send(
+            &mut client,
+            request(
+                1,
+                "initialize",
+                json!({
+                    "protocolVersion": "2025-06-18",
+                    "capabilities": {},
+                    "clientInfo": { "name": "test", "version": "1.0" }
+                }),
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(initialized["result"]["serverInfo"]["name"], "shinkuro");
+        send(
+            &mut client,
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        )
+        .await;
+
+        let list = send(&mut client, request(2, "prompts/list", json!({})))
+            .await
+            .unwrap();
+        assert_eq!(list["result"]["prompts"][0]["name"], "review");
+
+        let get = send(
+            &mut client,
+            request(
+                3,
+                "prompts/get",
+                json!({ "name": "cr", "arguments": { "code": "fn main() {}" } }),
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            get["result"]["messages"][0]["content"]["text"],
+            "Review fn main() {}"
+        );
+        assert_eq!(get["result"]["_meta"]["argumentSources"]["code"], "client");
+
+        let missing = send(
+            &mut client,
+            request(4, "prompts/get", json!({ "name": "review" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(missing["error"]["code"], -32602);
+        let unknown = send(
+            &mut client,
+            request(5, "prompts/get", json!({ "name": "deploy" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(unknown["error"]["message"], "Prompt not found");
+    }
+}
@@ -0,0 +1,101 @@
+use crate::loader::SyncStatus;
+use crate::paths;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Split `s3://bucket/prefix` into the bucket and the (possibly empty) prefix.
+fn parse_s3_url(url: &str) -> Result<(&str, &str)> {
+    let Some(rest) = url.strip_prefix("s3://") else {
+        anyhow::bail!("Invalid S3 URL: {}", url);
+    };
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        anyhow::bail!("Invalid S3 URL: {}", url);
+    }
+    Ok((bucket, prefix.trim_matches('/')))
+}
+
+/// Cache directory for an S3 source: `<cache_dir>/s3/<bucket>/<prefix>` with the
+/// prefix flattened like HTTP sources.
+pub fn get_cache_path(url: &str, cache_dir: &str) -> Result<PathBuf> {
+    let (bucket, prefix) = parse_s3_url(url)?;
+    let flattened = prefix.replace('/', "_");
+    Ok(paths::expand(cache_dir)
+        .join("s3")
+        .join(paths::component(bucket))
+        .join(paths::component(&flattened)))
+}
+
+/// Download the `.md` objects under the URL into `root` with `aws s3 sync`,
+/// which resolves credentials through the standard AWS chain (environment,
+/// profiles, SSO, instance roles). `AWS_ENDPOINT_URL` selects an S3-compatible
+/// service.
+pub fn sync_or_update(
+    url: &str,
+    root: &Path,
+    auto_pull: bool,
+    proxy: Option<&str>,
+) -> Result<SyncStatus> {
+    let cached = root.exists();
+    if cached && !auto_pull {
+        return Ok(SyncStatus::Cached);
+    }
+    match sync(url, root, proxy) {
+        Ok(()) => Ok(SyncStatus::Fresh),
+        Err(e) if cached => {
+            tracing::warn!(source = %url, error = %e, "Failed to sync, serving cached copy");
+            Ok(SyncStatus::Stale)
+        }
+        Err(e) => Err(anyhow::anyhow!("S3 sync failed: {}", e)),
+    }
+}
+
+fn sync(url: &str, root: &Path, proxy: Option<&str>) -> Result<()> {
+    let (bucket, prefix) = parse_s3_url(url)?;
+    let remote = format!("s3://{}/{}", bucket, prefix);
+    std::fs::create_dir_all(root)?;
+    let mut command = std::process::Command::new("aws");
+    if let Some(proxy) = proxy {
+        command.env("HTTPS_PROXY", proxy).env("HTTP_PROXY", proxy);
+    }
+    let output = command
+        .args(["s3", "sync", &remote])
+        .arg(root)
+        .args([
+            "--exclude",
+            "*",
+            "--include",
+            "*.md",
+            "--delete",
+            "--only-show-errors",
+        ])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s3_url() {
+        assert_eq!(
+            parse_s3_url("s3://bucket/team/prompts/").unwrap(),
+            ("bucket", "team/prompts")
+        );
+        assert_eq!(parse_s3_url("s3://bucket").unwrap(), ("bucket", ""));
+        assert!(parse_s3_url("s3:///prompts").is_err());
+        assert!(parse_s3_url("https://bucket/prompts").is_err());
+    }
+
+    #[test]
+    fn test_get_cache_path() {
+        let path = get_cache_path("s3://bucket/team/prompts", "/cache").unwrap();
+        assert_eq!(path, PathBuf::from("/cache/s3/bucket/team_prompts"));
+        let path = get_cache_path("s3://bucket/", "/cache").unwrap();
+        assert_eq!(path, PathBuf::from("/cache/s3/bucket/_"));
+    }
+}
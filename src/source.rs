@@ -0,0 +1,88 @@
+use crate::frontmatter::env_fallback_allowed;
+use crate::loader::{self, GitOptions, ResolvedFolder, ScanOptions, Source, SyncStatus};
+use crate::model::{Fallback, PromptData};
+use crate::{pack, paths, platform};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::fmt;
+
+/// Where a [`PromptLibrary`](crate::PromptLibrary) loads prompts from.
+/// Implement it for backends like Notion, Confluence or a database, and
+/// add them with [`PromptLibraryBuilder::add_source`](crate::PromptLibraryBuilder::add_source).
+/// `Display` names the source in logs and source statuses.
+#[async_trait]
+pub trait PromptSource: fmt::Display + Send + Sync {
+    /// Fetch and parse the prompts. Names may repeat; duplicates are
+    /// resolved by the library.
+    async fn load(&self) -> Result<Vec<PromptData>>;
+}
+
+/// A local folder, prompt pack or archive, or a git, HTTP(S) or S3 source
+/// fetched into the cache.
+#[derive(Debug, Clone)]
+pub struct BuiltinSource {
+    pub source: Source,
+    pub cache_dir: String,
+    pub git_options: GitOptions,
+    pub scan_options: ScanOptions,
+}
+
+impl BuiltinSource {
+    /// Clone, pull or download a non-pack source as needed, blocking on git
+    /// and the network.
+    pub fn resolve(&self) -> Result<ResolvedFolder> {
+        let folder = loader::resolve_source(&self.source, &self.cache_dir, &self.git_options)?;
+        if folder.status == SyncStatus::Stale {
+            tracing::warn!("Prompts in {} may be stale", folder.path.display());
+        }
+        Ok(folder)
+    }
+
+    /// Resolve the source and scan it, or read the prompt pack.
+    pub fn load_blocking(&self) -> Result<(Vec<PromptData>, SyncStatus)> {
+        if let Source::Pack(path) = &self.source {
+            let mut prompts = pack::read_pack(&paths::expand(path))?;
+            drop_disallowed_env_fallbacks(&mut prompts, &self.scan_options.allow_env);
+            return Ok((prompts, SyncStatus::Local));
+        }
+        let folder = self.resolve()?;
+        let prompts = loader::scan_markdown_files(&folder.path, &self.scan_options)?;
+        Ok((prompts, folder.status))
+    }
+}
+
+/// Packs were parsed when they were built, so apply the `env:` allow-list of
+/// this server to them as the scan would.
+fn drop_disallowed_env_fallbacks(prompts: &mut [PromptData], allow_env: &[String]) {
+    for prompt in prompts {
+        for arg in &mut prompt.arguments {
+            arg.fallback.retain(|f| match f {
+                Fallback::Env(var) if !env_fallback_allowed(var, allow_env) => {
+                    tracing::warn!(
+                        prompt = %prompt.name,
+                        argument = %arg.name,
+                        "'env:{}' is not an allowed environment variable, ignoring",
+                        var
+                    );
+                    false
+                }
+                _ => true,
+            });
+        }
+    }
+}
+
+impl fmt::Display for BuiltinSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+#[async_trait]
+impl PromptSource for BuiltinSource {
+    async fn load(&self) -> Result<Vec<PromptData>> {
+        let source = self.clone();
+        let (prompts, _) = platform::spawn_blocking(move || source.load_blocking()).await??;
+        Ok(prompts)
+    }
+}
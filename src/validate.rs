@@ -0,0 +1,128 @@
+use crate::frontmatter::Diagnostic;
+use crate::loader::FolderCheck;
+use crate::model::PromptData;
+use crate::naming;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// What `shinkuro validate` found, printed as JSON.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    /// Whether no problem is an error.
+    pub valid: bool,
+    pub files: usize,
+    pub prompts: usize,
+    pub problems: Vec<Diagnostic>,
+}
+
+impl Report {
+    /// Add a source's prompts and problems. Prompt names are checked against
+    /// MCP naming rules and each other, and each prompt is built with `build`
+    /// to check its arguments against its content.
+    pub fn add(&mut self, check: FolderCheck, build: impl Fn(PromptData) -> Result<()>) {
+        self.files += check.files;
+        self.prompts += check.prompts.len();
+        self.problems.extend(check.diagnostics);
+        let mut paths: HashMap<String, String> = HashMap::new();
+        for prompt in check.prompts {
+            let problem = |field: &str, message: String, error: bool| Diagnostic {
+                file: PathBuf::from(&prompt.path),
+                line: None,
+                field: field.to_string(),
+                message,
+                error,
+            };
+            if !naming::validate_prompt_name(&prompt.name) {
+                self.problems.push(problem(
+                    "name",
+                    format!(
+                        "prompt name '{}' may be rejected by MCP clients, consider '{}'",
+                        prompt.name,
+                        naming::suggest_prompt_name(&prompt.name)
+                    ),
+                    false,
+                ));
+            }
+            match paths.entry(prompt.name.clone()) {
+                Entry::Occupied(first) => self.problems.push(problem(
+                    "name",
+                    format!(
+                        "duplicate prompt name '{}', also used in {}",
+                        prompt.name,
+                        first.get()
+                    ),
+                    true,
+                )),
+                Entry::Vacant(entry) => {
+                    entry.insert(prompt.path.clone());
+                }
+            }
+            if let Err(e) = build(prompt.clone()) {
+                self.problems.push(problem("", e.to_string(), true));
+            }
+        }
+        self.valid = !self.problems.iter().any(|p| p.error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(name: &str, path: &str) -> PromptData {
+        PromptData {
+            name: name.to_string(),
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::default();
+        let check = FolderCheck {
+            files: 2,
+            prompts: vec![
+                prompt("review", "a.md"),
+                prompt("review", "b.md"),
+                prompt("bad name", "c.md"),
+            ],
+            diagnostics: Vec::new(),
+        };
+        report.add(check, |prompt| {
+            if prompt.path == "c.md" {
+                anyhow::bail!("Content arguments don't match");
+            }
+            Ok(())
+        });
+        assert!(!report.valid);
+        assert_eq!((report.files, report.prompts), (2, 3));
+        let problems: Vec<_> = report
+            .problems
+            .iter()
+            .map(|p| (p.file.to_str().unwrap(), p.error))
+            .collect();
+        assert_eq!(problems, [("b.md", true), ("c.md", false), ("c.md", true)]);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["problems"][0]["severity"], "error");
+        assert_eq!(
+            json["problems"][0]["message"],
+            "duplicate prompt name 'review', also used in a.md"
+        );
+
+        let mut report = Report::default();
+        report.add(
+            FolderCheck {
+                files: 1,
+                prompts: vec![prompt("review", "a.md")],
+                diagnostics: Vec::new(),
+            },
+            |_| Ok(()),
+        );
+        assert!(report.valid);
+        assert!(report.problems.is_empty());
+    }
+}